@@ -1,5 +1,6 @@
 // src/bsp/bsp_level.rs
 
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use parking_lot::RwLock;
@@ -7,8 +8,11 @@ use parking_lot::RwLock;
 use crate::{
     bsp::{
         BspNode, SegPosition, BoundingBox,
-        EPSILON, BLOCK_SIZE, Point2D, BSP_DEPTH_LIMIT
+        BLOCK_SIZE, Point2D, BSP_DEPTH_LIMIT
     },
+    bsp::bsp_partition::{choose_partition, PartitionLine, PartitionOptions},
+    bsp::bsp_lumps::NodeFormat,
+    bsp::bsp_reject::RejectMode,
     document::Document,
     bsp::bsp_util::Line2D,
     map::{LineDef, Vertex, Sector},
@@ -22,6 +26,10 @@ pub struct Seg {
     pub length: f64,
     /// If you want to sort segs by something in the linedef, add that field to `LineDef`.
     pub linedef: Option<Arc<LineDef>>,
+    /// Index of `linedef` in the document.
+    pub linedef_index: usize,
+    /// Distance along the linedef from where this side starts to `start`.
+    pub offset: f64,
     pub side: SegmentSide,
     /// For two-sided linedefs, the front/back segs can reference each other.
     pub partner: Option<Arc<Seg>>,
@@ -53,11 +61,16 @@ pub struct Block {
 }
 
 impl Block {
+    /// An empty blockmap covering `bounds`. The origin is rounded down to a
+    /// multiple of 8, where the classic node builders put it.
     pub fn new(bounds: BoundingBox) -> Self {
-        let x = (bounds.min_x as i32) / BLOCK_SIZE;
-        let y = (bounds.min_y as i32) / BLOCK_SIZE;
-        let w = ((bounds.max_x - bounds.min_x) as i32 / BLOCK_SIZE) + 1;
-        let h = ((bounds.max_y - bounds.min_y) as i32 / BLOCK_SIZE) + 1;
+        if !(bounds.min_x <= bounds.max_x && bounds.min_y <= bounds.max_y) {
+            return Self::default();
+        }
+        let x = bounds.min_x.floor() as i32 & !7;
+        let y = bounds.min_y.floor() as i32 & !7;
+        let w = (bounds.max_x.ceil() as i32 - x) / BLOCK_SIZE + 1;
+        let h = (bounds.max_y.ceil() as i32 - y) / BLOCK_SIZE + 1;
         let size = (w * h) as usize;
 
         Self {
@@ -69,16 +82,104 @@ impl Block {
         }
     }
 
+    /// The cell in column `cx` and row `cy`, counted from the origin.
     pub fn get_cell_mut(&mut self, cx: i32, cy: i32) -> Option<&mut Vec<usize>> {
-        let rel_x = cx - self.x;
-        let rel_y = cy - self.y;
-        if rel_x >= 0 && rel_x < self.width && rel_y >= 0 && rel_y < self.height {
-            let idx = (rel_y * self.width + rel_x) as usize;
+        if cx >= 0 && cx < self.width && cy >= 0 && cy < self.height {
+            let idx = (cy * self.width + cx) as usize;
             self.cells.get_mut(idx)
         } else {
             None
         }
     }
+
+    /// Adds `linedef` to every cell the line from `a` to `b` passes through.
+    /// Cells span 0 to 127 from their corner, so a line along the edge
+    /// between two cells goes in the one above or to the right.
+    pub fn add_line(&mut self, linedef: usize, a: Point2D, b: Point2D) {
+        let size = BLOCK_SIZE as f64;
+        let column = |x: f64| ((x - self.x as f64) / size).floor() as i32;
+        let row = |y: f64| ((y - self.y as f64) / size).floor() as i32;
+        let (columns, rows) = (column(a.x.min(b.x))..=column(a.x.max(b.x)), row(a.y.min(b.y))..=row(a.y.max(b.y)));
+
+        for cy in rows {
+            for cx in columns.clone() {
+                let min_x = self.x as f64 + cx as f64 * size;
+                let min_y = self.y as f64 + cy as f64 * size;
+                if line_touches_box(a, b, min_x, min_y, min_x + size - 1.0, min_y + size - 1.0) {
+                    if let Some(cell) = self.get_cell_mut(cx, cy) {
+                        cell.push(linedef);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The BLOCKMAP lump: origin and size, an offset per cell, then each
+    /// cell's linedef list between a 0 and a -1. Cells with the same list
+    /// share one copy. Vanilla reads the offsets as signed 16-bit words, so
+    /// everything has to start within the first 64 KB.
+    pub fn to_lump(&self) -> Result<Vec<u8>, String> {
+        let header = 4 + self.cells.len();
+        let mut lists: Vec<u16> = Vec::new();
+        let mut shared: HashMap<&[usize], usize> = HashMap::new();
+        let mut offsets = Vec::with_capacity(self.cells.len());
+        for cell in &self.cells {
+            if let Some(&linedef) = cell.iter().find(|&&l| l >= 0xFFFF) {
+                return Err(format!("Linedef {} is past what BLOCKMAP can number", linedef));
+            }
+            let offset = *shared.entry(cell.as_slice()).or_insert_with(|| {
+                let offset = header + lists.len();
+                lists.push(0);
+                lists.extend(cell.iter().map(|&l| l as u16));
+                lists.push(0xFFFF);
+                offset
+            });
+            offsets.push(offset);
+        }
+        if offsets.iter().any(|&offset| offset > i16::MAX as usize) {
+            return Err(format!(
+                "BLOCKMAP is {} bytes after merging identical blocks, past the vanilla limit of 64 KB",
+                (header + lists.len()) * 2
+            ));
+        }
+
+        let coord = |v: i32| i16::try_from(v).map_err(|_| format!("Blockmap origin {} is out of range", v));
+        let mut words = vec![coord(self.x)? as u16, coord(self.y)? as u16, self.width as u16, self.height as u16];
+        words.extend(offsets.iter().map(|&o| o as u16));
+        words.extend(lists);
+        Ok(words.iter().flat_map(|w| w.to_le_bytes()).collect())
+    }
+}
+
+/// Whether the line from `a` to `b` touches the closed box, by clipping the
+/// line against each edge in turn (Liang-Barsky).
+fn line_touches_box(a: Point2D, b: Point2D, min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> bool {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let (mut enter, mut leave) = (0.0f64, 1.0f64);
+    for (p, q) in [(-dx, a.x - min_x), (dx, max_x - a.x), (-dy, a.y - min_y), (dy, max_y - a.y)] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return false;
+            }
+        } else if p < 0.0 {
+            enter = enter.max(q / p);
+        } else {
+            leave = leave.min(q / p);
+        }
+    }
+    enter <= leave
+}
+
+/// Settings for a node build.
+#[derive(Debug, Clone, Default)]
+pub struct BuildOptions {
+    /// How partition lines are scored and sampled.
+    pub partition: PartitionOptions,
+    /// How the REJECT lump is filled.
+    pub reject: RejectMode,
+    /// The layout of the node lumps; `None` picks vanilla when the level
+    /// fits and XNOD when it does not.
+    pub node_format: Option<NodeFormat>,
 }
 
 // --------------------------------------------------------------------
@@ -99,11 +200,19 @@ pub struct BspLevel {
 
     /// The list of all Segs built from the Document’s linedefs.
     pub segs: Arc<RwLock<Vec<Arc<Seg>>>>,
+
+    /// Settings used by `build`.
+    pub options: BuildOptions,
 }
 
 impl BspLevel {
     /// Create a new BspLevel from an Arc<RwLock<Document>>.
     pub fn new(doc: Arc<RwLock<Document>>) -> Self {
+        Self::with_options(doc, BuildOptions::default())
+    }
+
+    /// Create a new BspLevel that builds with the given options.
+    pub fn with_options(doc: Arc<RwLock<Document>>, options: BuildOptions) -> Self {
         let bounds = Self::compute_map_bounds(&doc);
         let blockmap = Block::new(bounds);
 
@@ -113,6 +222,7 @@ impl BspLevel {
             subsectors: Arc::new(RwLock::new(Vec::new())),
             blocks: Arc::new(RwLock::new(blockmap)),
             segs: Arc::new(RwLock::new(Vec::new())),
+            options,
         }
     }
 
@@ -142,7 +252,7 @@ impl BspLevel {
 
         let mut seglist = Vec::with_capacity(linedefs_ref.len() * 2);
 
-        for linedef in linedefs_ref.iter() {
+        for (linedef_index, linedef) in linedefs_ref.iter().enumerate() {
             let start_v = vertices_ref
                 .get(linedef.start)
                 .ok_or_else(|| format!("Invalid start vertex {}", linedef.start))?;
@@ -158,6 +268,8 @@ impl BspLevel {
                     angle: Self::compute_angle(start_v, end_v),
                     length: Self::compute_length(start_v, end_v),
                     linedef: Some(linedef.clone()), // if linedef is Arc<LineDef>, else wrap
+                    linedef_index,
+                    offset: 0.0,
                     side: SegmentSide::Front,
                     partner: None,
                 });
@@ -172,6 +284,8 @@ impl BspLevel {
                     angle: Self::compute_angle(end_v, start_v),
                     length: Self::compute_length(end_v, start_v),
                     linedef: Some(linedef.clone()),
+                    linedef_index,
+                    offset: 0.0,
                     side: SegmentSide::Back,
                     partner: None,
                 });
@@ -179,36 +293,9 @@ impl BspLevel {
             }
         }
 
-        self.link_partner_segs(&seglist);
         Ok(seglist)
     }
 
-    /// For any linedef that’s two-sided, link the front/back seg so each has `partner`.
-    fn link_partner_segs(&self, segs: &[Arc<Seg>]) {
-        for i in 0..segs.len() {
-            for j in (i + 1)..segs.len() {
-                // If they share the same linedef pointer & different sides, they are front/back pairs
-                if let (Some(ld_i), Some(ld_j)) = (&segs[i].linedef, &segs[j].linedef) {
-                    // pointer-equality check
-                    if Arc::ptr_eq(ld_i, ld_j) && segs[i].side != segs[j].side {
-                        // Link them
-                        // 1) Make a local clone of each seg Arc
-                        let seg_i = segs[i].clone();
-                        let seg_j = segs[j].clone();
-
-                        // 2) Attempt to mutate each seg’s `partner` if we have a unique Arc.
-                        if let Some(i_mut) = Arc::get_mut(&mut (seg_i.clone())) {
-                            i_mut.partner = Some(seg_j.clone());
-                        }
-                        if let Some(j_mut) = Arc::get_mut(&mut (seg_j.clone())) {
-                            j_mut.partner = Some(seg_i.clone());
-                        }
-                    }
-                }
-            }
-        }
-    }
-
     // ----------------------------------------------------------------
     // Step 2: recursively build the BSP
     // ----------------------------------------------------------------
@@ -219,22 +306,14 @@ impl BspLevel {
         if segs.is_empty() {
             return Ok(BspNode::empty_leaf());
         }
-        if segs.len() <= 3 {
-            return Ok(BspNode::create_leaf(segs));
-        }
 
-        let partition = self.choose_partition(&segs)?;
-        let (mut front_list, mut back_list, spanning) = self.split_list(&segs, &partition)?;
-
-        for seg in spanning {
-            let (f_seg, b_seg) = self.split_seg(&seg, &partition)?;
-            if let Some(s) = f_seg {
-                front_list.push(Arc::new(s));
-            }
-            if let Some(s) = b_seg {
-                back_list.push(Arc::new(s));
-            }
-        }
+        // No partition means the segs already bound a convex region.
+        let Some(index) = choose_partition(&segs, &self.options.partition) else {
+            return Ok(BspNode::create_leaf(segs));
+        };
+        let partition = self.partition_line(&segs[index]);
+        let line = PartitionLine::new(partition.start, partition.end);
+        let (front_list, back_list) = self.split_list(&segs, &line);
 
         let front_node = self.build_bsp_tree(front_list, depth + 1)?;
         let back_node = self.build_bsp_tree(back_list, depth + 1)?;
@@ -244,80 +323,67 @@ impl BspLevel {
         Ok(node)
     }
 
-    fn choose_partition(&self, segs: &[Arc<Seg>]) -> Result<Line2D, String> {
-        if segs.is_empty() {
-            return Err("No segs to partition".into());
+    /// The line through the whole linedef of `seg`, pointing the same way.
+    /// Split segs end at rounded-off points, but the linedef's vertices are
+    /// whole map units and keep the line exactly where the engine sees it.
+    fn partition_line(&self, seg: &Seg) -> Line2D {
+        let doc = self.doc.read();
+        let vertices = doc.vertices.read();
+        let ends = seg.linedef.as_ref().and_then(|linedef| {
+            Some((vertices.get(linedef.start)?, vertices.get(linedef.end)?))
+        });
+        match ends {
+            Some((a, b)) => {
+                let (a, b) = if seg.side == SegmentSide::Front { (a, b) } else { (b, a) };
+                Line2D::new(Point2D::new(a.x as f64, a.y as f64), Point2D::new(b.x as f64, b.y as f64))
+            }
+            None => Line2D::new(seg.start, seg.end),
         }
-        Ok(Line2D::new(segs[0].start, segs[0].end))
     }
 
-    fn split_list(
-        &self,
-        segs: &[Arc<Seg>],
-        part: &Line2D,
-    ) -> Result<(Vec<Arc<Seg>>, Vec<Arc<Seg>>, Vec<Arc<Seg>>), String> {
+    /// Sorts `segs` to the front and back of `line`, cutting the ones that
+    /// cross it. Segs on the line go with the side they face.
+    fn split_list(&self, segs: &[Arc<Seg>], line: &PartitionLine) -> (Vec<Arc<Seg>>, Vec<Arc<Seg>>) {
         let mut front = Vec::new();
         let mut back = Vec::new();
-        let mut span = Vec::new();
 
         for seg in segs {
-            match self.classify_seg(seg, part) {
+            match line.classify(seg) {
                 SegPosition::Front => front.push(seg.clone()),
                 SegPosition::Back => back.push(seg.clone()),
-                SegPosition::Spanning => span.push(seg.clone()),
-                SegPosition::Coincident => {
-                    front.push(seg.clone());
-                    back.push(seg.clone());
+                SegPosition::Coincident if line.same_direction(seg) => front.push(seg.clone()),
+                SegPosition::Coincident => back.push(seg.clone()),
+                SegPosition::Spanning => {
+                    let (first, second) = Self::split_seg(seg, line.intersection(seg));
+                    if line.distance(&seg.start) > 0.0 {
+                        front.push(first);
+                        back.push(second);
+                    } else {
+                        back.push(first);
+                        front.push(second);
+                    }
                 }
             }
         }
-        Ok((front, back, span))
-    }
-
-    fn classify_seg(&self, seg: &Seg, line: &Line2D) -> SegPosition {
-        let side_a = line.classify_point(&seg.start);
-        let side_b = line.classify_point(&seg.end);
-
-        if side_a > EPSILON && side_b > EPSILON {
-            SegPosition::Front
-        } else if side_a < -EPSILON && side_b < -EPSILON {
-            SegPosition::Back
-        } else if (side_a > EPSILON && side_b < -EPSILON)
-            || (side_a < -EPSILON && side_b > EPSILON)
-        {
-            SegPosition::Spanning
-        } else {
-            SegPosition::Coincident
-        }
+        (front, back)
     }
 
-    fn split_seg(&self, seg: &Arc<Seg>, line: &Line2D) -> Result<(Option<Seg>, Option<Seg>), String> {
-        let seg_line = Line2D::from_seg(seg);
-        if let Some(Point2D { x: ix, y: iy }) = line.intersect(&seg_line) {
-            let intersect = Point2D::new(ix, iy);
-            let front_seg = Seg {
-                start: seg.start,
-                end: intersect,
-                angle: 0.0,  // you could recalc if needed
-                length: 0.0, // recalc if needed
-                linedef: seg.linedef.clone(),
-                side: seg.side,
-                partner: None,
-            };
-            let back_seg = Seg {
-                start: intersect,
-                end: seg.end,
-                angle: 0.0,
-                length: 0.0,
+    /// Cuts `seg` in two at `point`. Both halves keep the seg's angle.
+    fn split_seg(seg: &Arc<Seg>, point: Point2D) -> (Arc<Seg>, Arc<Seg>) {
+        let piece = |start: Point2D, end: Point2D| {
+            Arc::new(Seg {
+                start,
+                end,
+                angle: seg.angle,
+                length: (end.x - start.x).hypot(end.y - start.y),
                 linedef: seg.linedef.clone(),
+                linedef_index: seg.linedef_index,
+                offset: seg.offset + (start.x - seg.start.x).hypot(start.y - seg.start.y),
                 side: seg.side,
                 partner: None,
-            };
-            Ok((Some(front_seg), Some(back_seg)))
-        } else {
-            // might be parallel or have no single intersection
-            Err("Cannot split seg: no single intersection found".into())
-        }
+            })
+        };
+        (piece(seg.start, point), piece(point, seg.end))
     }
 
     fn compute_node_bbox(&self, segs: &[Arc<Seg>]) -> BoundingBox {
@@ -346,19 +412,70 @@ impl BspLevel {
     // ----------------------------------------------------------------
     // Step 3: Build blockmap
     // ----------------------------------------------------------------
+    /// Rasterises every linedef into the 128-unit cells it crosses. The
+    /// blockmap covers the linedefs only; lone vertices do not widen it.
     fn build_blockmap(&self) -> Result<(), String> {
-        // TODO if desired
+        let doc = self.doc.read();
+        let linedefs = doc.linedefs.read();
+        let vertices = doc.vertices.read();
+
+        let mut lines = Vec::with_capacity(linedefs.len());
+        let mut bounds = BoundingBox::new_empty();
+        for linedef in linedefs.iter() {
+            let point = |index: usize| {
+                vertices.get(index)
+                    .map(|v| Point2D::new(v.x as f64, v.y as f64))
+                    .ok_or_else(|| format!("Invalid vertex {}", index))
+            };
+            let (a, b) = (point(linedef.start)?, point(linedef.end)?);
+            bounds.expand_point(a.x, a.y);
+            bounds.expand_point(b.x, b.y);
+            lines.push((a, b));
+        }
+
+        let mut block = Block::new(bounds);
+        for (index, (a, b)) in lines.into_iter().enumerate() {
+            block.add_line(index, a, b);
+        }
+        *self.blocks.write() = block;
         Ok(())
     }
 
     // ----------------------------------------------------------------
     // Step 4: Process subsectors
     // ----------------------------------------------------------------
+    /// Lists the leaves of the tree as subsectors, front before back, and
+    /// their segs in the same order. This is the numbering the node lumps use.
     fn process_subsectors(&self) -> Result<(), String> {
-        // TODO if desired
+        let mut subsectors = Vec::new();
+        if let Some(root) = self.root.read().as_ref() {
+            let doc = self.doc.read();
+            self.collect_subsectors(root, &doc, &mut subsectors);
+        }
+        *self.segs.write() = subsectors.iter().flat_map(|s| s.segs.iter().cloned()).collect();
+        *self.subsectors.write() = subsectors;
         Ok(())
     }
 
+    fn collect_subsectors(&self, node: &BspNode, doc: &Document, out: &mut Vec<Arc<Subsector>>) {
+        if let (Some(front), Some(back)) = (&node.front, &node.back) {
+            self.collect_subsectors(front, doc, out);
+            self.collect_subsectors(back, doc, out);
+            return;
+        }
+        let sector = node.segs.first().and_then(|seg| {
+            let linedef = seg.linedef.as_ref()?;
+            let side = if seg.side == SegmentSide::Front { linedef.right } else { linedef.left };
+            let sidedef = doc.sidedefs.read().get(usize::try_from(side).ok()?)?.clone();
+            doc.get_sector_from_side(&sidedef)
+        });
+        out.push(Arc::new(Subsector {
+            segs: node.segs.clone(),
+            bbox: BoundingBox::from_segs(&node.segs),
+            sector,
+        }));
+    }
+
     // ----------------------------------------------------------------
     // Some utility fns
     // ----------------------------------------------------------------
@@ -384,3 +501,39 @@ impl BspLevel {
         bb
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bsp::bsp_partition::tests::room;
+
+    #[test]
+    fn test_blockmap() {
+        // Nothing to cover: an empty blockmap rather than an overflow.
+        let empty = Block::new(BoundingBox::new_empty());
+        assert_eq!((empty.width, empty.height), (0, 0));
+
+        let bsp = BspLevel::new(Arc::new(RwLock::new(room(true))));
+        bsp.build().unwrap();
+        let block = bsp.blocks.read();
+        assert_eq!((block.x, block.y, block.width, block.height), (0, 0, 5, 5));
+        // The corner holds the two walls meeting there, and the pillar's
+        // corners the two sides meeting at each.
+        assert_eq!(block.cells[0], vec![0, 3]);
+        assert_eq!(block.cells[5 + 1], vec![4, 7]);
+        assert_eq!(block.cells[2 * 5 + 2], vec![5, 6]);
+        assert!(block.cells[5 + 3].is_empty());
+
+        let lump = block.to_lump().unwrap();
+        let word = |i: usize| u16::from_le_bytes([lump[i * 2], lump[i * 2 + 1]]);
+        assert_eq!((word(0), word(1), word(2), word(3)), (0, 0, 5, 5));
+        // Empty cells share one list.
+        assert_eq!(word(4 + 5 + 3), word(4 + 2 * 5 + 3));
+        let first = word(4) as usize;
+        assert_eq!((word(first), word(first + 1), word(first + 2), word(first + 3)), (0, 0, 3, 0xFFFF));
+
+        // Too many cells for 16-bit offsets even with every list shared.
+        let huge = Block::new(BoundingBox::new(0.0, 0.0, 200.0 * 128.0, 200.0 * 128.0));
+        assert!(huge.to_lump().is_err());
+    }
+}
@@ -0,0 +1,671 @@
+// src/bsp/bsp_lumps.rs
+// Serializing a built tree into the level's node lumps, and reading them back.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use log::warn;
+
+use crate::bsp::{BoundingBox, BspLevel, BspNode, Point2D, MAX_SEGS};
+use crate::bsp::bsp_level::SegmentSide;
+use crate::bsp::bsp_reject::build_reject;
+use crate::document::wad::Lump;
+use crate::map::Vertex;
+
+/// Marks a vanilla NODES child number as a subsector rather than another node.
+pub const SUBSECTOR_BIT: u16 = 0x8000;
+/// The same mark in the 32-bit child numbers of the extended formats.
+pub const SUBSECTOR_BIT_32: u32 = 0x8000_0000;
+/// Size of a vanilla NODES record.
+const NODE_SIZE: usize = 28;
+/// Vertex numbers in vanilla SEGS are 16 bits; ports read them unsigned.
+pub const MAX_VERTICES: usize = 65536;
+/// The start of a DeePBSP NODES lump.
+const DEEPBSP_MAGIC: &[u8; 8] = b"xNd4\0\0\0\0";
+/// The most a ZNOD lump is inflated to, so a corrupt one cannot exhaust memory.
+const MAX_ZNOD_SIZE: u64 = 64 << 20;
+
+/// The layouts node lumps can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeFormat {
+    /// SEGS, SSECTORS and NODES as Doom reads them, numbered in 16 bits.
+    Vanilla,
+    /// DeePBSP's version 4: the same lumps with 32-bit seg and vertex numbers.
+    DeepBsp,
+    /// ZDoom extended nodes: everything in NODES, split vertices included
+    /// in fixed point.
+    Xnod,
+    /// XNOD compressed with zlib.
+    Znod,
+}
+
+impl NodeFormat {
+    /// Whether split vertices keep their fraction. The others go into
+    /// VERTEXES, which holds whole map units.
+    fn fractional(self) -> bool {
+        matches!(self, NodeFormat::Xnod | NodeFormat::Znod)
+    }
+}
+
+impl fmt::Display for NodeFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            NodeFormat::Vanilla => "vanilla",
+            NodeFormat::DeepBsp => "DeePBSP",
+            NodeFormat::Xnod => "XNOD",
+            NodeFormat::Znod => "ZNOD",
+        })
+    }
+}
+
+/// A node's child: another node or a subsector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Child {
+    Node(usize),
+    Subsector(usize),
+}
+
+/// A seg as the node lumps store it. XNOD leaves out the angle and offset,
+/// which read back as 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MapSeg {
+    pub v1: usize,
+    pub v2: usize,
+    pub angle: i16,
+    pub linedef: usize,
+    /// 0 for the linedef's front side, 1 for its back.
+    pub side: u8,
+    /// Distance along the linedef. The lumps that store it hold 16 bits.
+    pub offset: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MapSubsector {
+    pub first: usize,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MapNode {
+    pub x: i16,
+    pub y: i16,
+    pub dx: i16,
+    pub dy: i16,
+    /// The right and then the left child's box: top, bottom, left, right.
+    pub bbox: [[i16; 4]; 2],
+    /// The right (front) and then the left (back) child.
+    pub children: [Child; 2],
+}
+
+/// A node tree in the form every format stores, numbered the same way.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeData {
+    /// How many of the level's own vertices come before the split ones.
+    pub original_vertices: usize,
+    /// Vertices made by splits.
+    pub vertices: Vec<Point2D>,
+    pub segs: Vec<MapSeg>,
+    pub subsectors: Vec<MapSubsector>,
+    /// Children before their parents, so the root is last.
+    pub nodes: Vec<MapNode>,
+}
+
+impl NodeData {
+    /// Numbers the tree of `bsp`. Split points are rounded to whole map
+    /// units unless `fractional`.
+    pub fn from_bsp(bsp: &BspLevel, fractional: bool) -> Result<Self, String> {
+        let root = bsp.root.read();
+        let root = root.as_ref().ok_or("The nodes have not been built")?;
+        let subsectors = bsp.subsectors.read();
+
+        let mut table = VertexTable::new(&bsp.doc.read().vertices.read(), fractional);
+        let mut data = NodeData::default();
+        for subsector in subsectors.iter() {
+            if subsector.segs.is_empty() {
+                return Err("The tree has an empty subsector".into());
+            }
+            data.subsectors.push(MapSubsector { first: data.segs.len(), count: subsector.segs.len() });
+            for seg in &subsector.segs {
+                data.segs.push(MapSeg {
+                    v1: table.get(&seg.start),
+                    v2: table.get(&seg.end),
+                    angle: binary_angle(seg.angle),
+                    linedef: seg.linedef_index,
+                    side: if seg.side == SegmentSide::Front { 0 } else { 1 },
+                    offset: seg.offset.round() as i32,
+                });
+            }
+        }
+        let mut next_subsector = 0;
+        data.add_node(root, &mut next_subsector)?;
+        data.original_vertices = table.existing;
+        data.vertices = table.added;
+        Ok(data)
+    }
+
+    /// Adds the nodes below `node` children first. Returns the child number
+    /// and bounding box of `node`.
+    fn add_node(&mut self, node: &BspNode, next_subsector: &mut usize) -> Result<(Child, BoundingBox), String> {
+        let (Some(partition), Some(front), Some(back)) = (&node.partition, &node.front, &node.back) else {
+            let number = *next_subsector;
+            *next_subsector += 1;
+            return Ok((Child::Subsector(number), BoundingBox::from_segs(&node.segs)));
+        };
+        let (right, right_box) = self.add_node(front, next_subsector)?;
+        let (left, left_box) = self.add_node(back, next_subsector)?;
+
+        let coord = |value: f64| {
+            i16::try_from(value.round() as i64).map_err(|_| format!("Node coordinate {} is out of range", value))
+        };
+        // Top, bottom, left, right.
+        let corners = |bbox: &BoundingBox| -> Result<[i16; 4], String> {
+            Ok([coord(bbox.max_y.ceil())?, coord(bbox.min_y.floor())?, coord(bbox.min_x.floor())?, coord(bbox.max_x.ceil())?])
+        };
+        self.nodes.push(MapNode {
+            x: coord(partition.start.x)?,
+            y: coord(partition.start.y)?,
+            dx: coord(partition.end.x - partition.start.x)?,
+            dy: coord(partition.end.y - partition.start.y)?,
+            bbox: [corners(&right_box)?, corners(&left_box)?],
+            children: [right, left],
+        });
+
+        let mut bbox = right_box;
+        bbox.combine(&left_box);
+        Ok((Child::Node(self.nodes.len() - 1), bbox))
+    }
+
+    fn vertex_count(&self) -> usize {
+        self.original_vertices + self.vertices.len()
+    }
+
+    /// Fails with the reason if the vanilla lumps cannot number everything.
+    pub fn check_vanilla(&self) -> Result<(), String> {
+        if self.segs.len() > MAX_SEGS {
+            return Err(format!("{} segs is more than the {} vanilla SEGS can hold", self.segs.len(), MAX_SEGS));
+        }
+        if self.vertex_count() > MAX_VERTICES {
+            return Err(format!("{} vertices is more than vanilla SEGS can number", self.vertex_count()));
+        }
+        if self.subsectors.len() > SUBSECTOR_BIT as usize || self.nodes.len() > SUBSECTOR_BIT as usize {
+            return Err(format!("{} subsectors is more than vanilla NODES can number", self.subsectors.len()));
+        }
+        if let Some(seg) = self.segs.iter().find(|seg| i16::try_from(seg.offset).is_err()) {
+            return Err(format!("A seg {} units along linedef {} is too far for vanilla SEGS", seg.offset, seg.linedef));
+        }
+        Ok(())
+    }
+
+    /// SEGS of 12 bytes, SSECTORS of 4 and NODES of 28.
+    pub fn write_vanilla(&self) -> Result<[Lump; 3], String> {
+        self.check_vanilla()?;
+        let child = |child: Child| match child {
+            Child::Node(n) => n as u16,
+            Child::Subsector(n) => n as u16 | SUBSECTOR_BIT,
+        };
+        let write = || -> io::Result<[Lump; 3]> {
+            let mut segs = Vec::with_capacity(self.segs.len() * 12);
+            for seg in &self.segs {
+                segs.write_u16::<LE>(seg.v1 as u16)?;
+                segs.write_u16::<LE>(seg.v2 as u16)?;
+                write_seg_tail(&mut segs, seg)?;
+            }
+            let mut ssectors = Vec::with_capacity(self.subsectors.len() * 4);
+            for subsector in &self.subsectors {
+                ssectors.write_u16::<LE>(subsector.count as u16)?;
+                ssectors.write_u16::<LE>(subsector.first as u16)?;
+            }
+            let mut nodes = Vec::with_capacity(self.nodes.len() * NODE_SIZE);
+            for node in &self.nodes {
+                write_node_head(&mut nodes, node)?;
+                nodes.write_u16::<LE>(child(node.children[0]))?;
+                nodes.write_u16::<LE>(child(node.children[1]))?;
+            }
+            Ok([Lump::new("SEGS", segs), Lump::new("SSECTORS", ssectors), Lump::new("NODES", nodes)])
+        };
+        write().map_err(|e| e.to_string())
+    }
+
+    /// DeePBSP's version 4: SEGS of 16 bytes, SSECTORS of 6 and a NODES of
+    /// 32-byte records after an 8-byte signature.
+    pub fn write_deepbsp(&self) -> Result<[Lump; 3], String> {
+        let write = || -> io::Result<[Lump; 3]> {
+            let mut segs = Vec::with_capacity(self.segs.len() * 16);
+            for seg in &self.segs {
+                segs.write_u32::<LE>(seg.v1 as u32)?;
+                segs.write_u32::<LE>(seg.v2 as u32)?;
+                write_seg_tail(&mut segs, seg)?;
+            }
+            let mut ssectors = Vec::with_capacity(self.subsectors.len() * 6);
+            for subsector in &self.subsectors {
+                ssectors.write_u16::<LE>(subsector.count as u16)?;
+                ssectors.write_u32::<LE>(subsector.first as u32)?;
+            }
+            let mut nodes = DEEPBSP_MAGIC.to_vec();
+            write_nodes_32(&mut nodes, &self.nodes)?;
+            Ok([Lump::new("SEGS", segs), Lump::new("SSECTORS", ssectors), Lump::new("NODES", nodes)])
+        };
+        write().map_err(|e| e.to_string())
+    }
+
+    /// ZDoom's extended nodes, all in the NODES lump, compressed if
+    /// `compress`. SEGS and SSECTORS are left empty.
+    pub fn write_extended(&self, compress: bool) -> Result<[Lump; 3], String> {
+        let write = || -> io::Result<Vec<u8>> {
+            let mut body = Vec::new();
+            body.write_u32::<LE>(self.original_vertices as u32)?;
+            body.write_u32::<LE>(self.vertices.len() as u32)?;
+            for vertex in &self.vertices {
+                body.write_i32::<LE>(to_fixed(vertex.x))?;
+                body.write_i32::<LE>(to_fixed(vertex.y))?;
+            }
+            body.write_u32::<LE>(self.subsectors.len() as u32)?;
+            for subsector in &self.subsectors {
+                body.write_u32::<LE>(subsector.count as u32)?;
+            }
+            body.write_u32::<LE>(self.segs.len() as u32)?;
+            for seg in &self.segs {
+                body.write_u32::<LE>(seg.v1 as u32)?;
+                body.write_u32::<LE>(seg.v2 as u32)?;
+                body.write_u16::<LE>(seg.linedef as u16)?;
+                body.write_u8(seg.side)?;
+            }
+            body.write_u32::<LE>(self.nodes.len() as u32)?;
+            write_nodes_32(&mut body, &self.nodes)?;
+
+            if compress {
+                let mut encoder = ZlibEncoder::new(b"ZNOD".to_vec(), Compression::default());
+                encoder.write_all(&body)?;
+                encoder.finish()
+            } else {
+                let mut nodes = b"XNOD".to_vec();
+                nodes.extend(body);
+                Ok(nodes)
+            }
+        };
+        let nodes = write().map_err(|e| e.to_string())?;
+        Ok([Lump::new("SEGS", Vec::new()), Lump::new("SSECTORS", Vec::new()), Lump::new("NODES", nodes)])
+    }
+
+    /// Reads node lumps in any of the formats, told apart by the start of
+    /// NODES. `vertex_count` is the number of vertices in VERTEXES.
+    pub fn read(segs: &[u8], ssectors: &[u8], nodes: &[u8], vertex_count: usize) -> Result<(NodeFormat, Self), String> {
+        let read = || -> io::Result<(NodeFormat, Self)> {
+            match nodes.get(..4) {
+                Some(b"XNOD") => Ok((NodeFormat::Xnod, Self::read_extended(&mut &nodes[4..])?)),
+                Some(b"ZNOD") => Ok((NodeFormat::Znod, Self::read_extended(&mut ZlibDecoder::new(&nodes[4..]).take(MAX_ZNOD_SIZE))?)),
+                _ if nodes.starts_with(DEEPBSP_MAGIC) => Ok((NodeFormat::DeepBsp, Self::read_deepbsp(segs, ssectors, nodes, vertex_count)?)),
+                _ => Ok((NodeFormat::Vanilla, Self::read_vanilla(segs, ssectors, nodes, vertex_count)?)),
+            }
+        };
+        read().map_err(|e| format!("Bad node lumps: {}", e))
+    }
+
+    fn read_vanilla(segs: &[u8], ssectors: &[u8], nodes: &[u8], vertex_count: usize) -> io::Result<Self> {
+        let mut data = NodeData { original_vertices: vertex_count, ..Default::default() };
+        for mut record in segs.chunks_exact(12) {
+            let (v1, v2) = (record.read_u16::<LE>()? as usize, record.read_u16::<LE>()? as usize);
+            data.segs.push(read_seg_tail(&mut record, v1, v2)?);
+        }
+        for mut record in ssectors.chunks_exact(4) {
+            let count = record.read_u16::<LE>()? as usize;
+            data.subsectors.push(MapSubsector { first: record.read_u16::<LE>()? as usize, count });
+        }
+        let child = |raw: u16| {
+            if raw & SUBSECTOR_BIT != 0 {
+                Child::Subsector((raw & !SUBSECTOR_BIT) as usize)
+            } else {
+                Child::Node(raw as usize)
+            }
+        };
+        for mut record in nodes.chunks_exact(NODE_SIZE) {
+            let mut node = read_node_head(&mut record)?;
+            node.children = [child(record.read_u16::<LE>()?), child(record.read_u16::<LE>()?)];
+            data.nodes.push(node);
+        }
+        Ok(data)
+    }
+
+    fn read_deepbsp(segs: &[u8], ssectors: &[u8], nodes: &[u8], vertex_count: usize) -> io::Result<Self> {
+        let mut data = NodeData { original_vertices: vertex_count, ..Default::default() };
+        for mut record in segs.chunks_exact(16) {
+            let (v1, v2) = (record.read_u32::<LE>()? as usize, record.read_u32::<LE>()? as usize);
+            data.segs.push(read_seg_tail(&mut record, v1, v2)?);
+        }
+        for mut record in ssectors.chunks_exact(6) {
+            let count = record.read_u16::<LE>()? as usize;
+            data.subsectors.push(MapSubsector { first: record.read_u32::<LE>()? as usize, count });
+        }
+        let mut nodes = &nodes[DEEPBSP_MAGIC.len()..];
+        let count = nodes.len() / 32;
+        data.nodes = read_nodes_32(&mut nodes, count)?;
+        Ok(data)
+    }
+
+    fn read_extended<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut data = NodeData { original_vertices: reader.read_u32::<LE>()? as usize, ..Default::default() };
+        let count = reader.read_u32::<LE>()?;
+        for _ in 0..count {
+            let x = from_fixed(reader.read_i32::<LE>()?);
+            data.vertices.push(Point2D::new(x, from_fixed(reader.read_i32::<LE>()?)));
+        }
+        let count = reader.read_u32::<LE>()?;
+        let mut first = 0;
+        for _ in 0..count {
+            let count = reader.read_u32::<LE>()? as usize;
+            data.subsectors.push(MapSubsector { first, count });
+            first += count;
+        }
+        let count = reader.read_u32::<LE>()?;
+        for _ in 0..count {
+            let (v1, v2) = (reader.read_u32::<LE>()? as usize, reader.read_u32::<LE>()? as usize);
+            let linedef = reader.read_u16::<LE>()? as usize;
+            data.segs.push(MapSeg { v1, v2, angle: 0, linedef, side: reader.read_u8()?, offset: 0 });
+        }
+        let count = reader.read_u32::<LE>()? as usize;
+        data.nodes = read_nodes_32(reader, count)?;
+        Ok(data)
+    }
+}
+
+/// The lumps a node builder writes for a level.
+#[derive(Debug)]
+pub struct NodeLumps {
+    /// The layout the node lumps were written in.
+    pub format: NodeFormat,
+    /// Vertices created by splits. They follow the level's own vertices in
+    /// VERTEXES, which the segs number them by. Empty for XNOD and ZNOD,
+    /// which keep them in NODES.
+    pub vertices: Vec<Vertex>,
+    /// SEGS, SSECTORS, NODES, REJECT and BLOCKMAP.
+    pub lumps: Vec<Lump>,
+}
+
+impl NodeLumps {
+    /// The lumps of a level with no lines: all five present but empty, so
+    /// they replace whatever the level had before.
+    pub fn empty() -> Self {
+        let lumps = ["SEGS", "SSECTORS", "NODES", "REJECT", "BLOCKMAP"].map(Lump::marker);
+        Self { format: NodeFormat::Vanilla, vertices: Vec::new(), lumps: Vec::from(lumps) }
+    }
+
+    /// Writes `bsp` in the format its options ask for, with its reject
+    /// table and blockmap. Without a format, the vanilla one is used if the
+    /// level, its blockmap included, fits in it, and XNOD if not; split
+    /// points are then rounded to whole units either way.
+    pub fn new(bsp: &BspLevel) -> Result<Self, String> {
+        let blockmap = bsp.blocks.read().to_lump();
+        let (format, data) = match bsp.options.node_format {
+            Some(format) => (format, NodeData::from_bsp(bsp, format.fractional())?),
+            None => {
+                let data = NodeData::from_bsp(bsp, false)?;
+                let fits = data.check_vanilla().is_ok() && blockmap.is_ok();
+                (if fits { NodeFormat::Vanilla } else { NodeFormat::Xnod }, data)
+            }
+        };
+        Self::write(bsp, format, data, blockmap)
+    }
+
+    fn write(bsp: &BspLevel, format: NodeFormat, data: NodeData, blockmap: Result<Vec<u8>, String>) -> Result<Self, String> {
+        let nodes = match format {
+            NodeFormat::Vanilla => data.write_vanilla()?,
+            NodeFormat::DeepBsp => data.write_deepbsp()?,
+            NodeFormat::Xnod => data.write_extended(false)?,
+            NodeFormat::Znod => data.write_extended(true)?,
+        };
+        let blockmap = match blockmap {
+            Ok(blockmap) => blockmap,
+            // Ports that read the extended formats build their own
+            // blockmap when the lump is empty.
+            Err(e) if format != NodeFormat::Vanilla => {
+                warn!("{}; writing an empty BLOCKMAP", e);
+                Vec::new()
+            }
+            Err(e) => return Err(e),
+        };
+        let vertices = if format.fractional() {
+            Vec::new()
+        } else {
+            data.vertices.iter().map(|v| Vertex::new(v.x as i32, v.y as i32)).collect()
+        };
+
+        let mut lumps = Vec::from(nodes);
+        lumps.push(Lump::new("REJECT", build_reject(&bsp.doc.read(), bsp.options.reject)));
+        lumps.push(Lump::new("BLOCKMAP", blockmap));
+        Ok(Self { format, vertices, lumps })
+    }
+}
+
+/// Numbers seg ends by vertex, reusing the level's vertices where a point
+/// lands on one and adding new vertices for the rest. Points are compared
+/// in 16.16 fixed point, or rounded to whole units unless `fractional`.
+struct VertexTable {
+    index: HashMap<(i32, i32), usize>,
+    fractional: bool,
+    existing: usize,
+    added: Vec<Point2D>,
+}
+
+impl VertexTable {
+    fn new(vertices: &[Arc<Vertex>], fractional: bool) -> Self {
+        let mut index = HashMap::with_capacity(vertices.len());
+        for (i, vertex) in vertices.iter().enumerate() {
+            index.entry((vertex.x << 16, vertex.y << 16)).or_insert(i);
+        }
+        Self { index, fractional, existing: vertices.len(), added: Vec::new() }
+    }
+
+    fn get(&mut self, point: &Point2D) -> usize {
+        let fixed = |v: f64| if self.fractional { to_fixed(v) } else { (v.round() as i32) << 16 };
+        let key = (fixed(point.x), fixed(point.y));
+        let next = self.existing + self.added.len();
+        *self.index.entry(key).or_insert_with(|| {
+            self.added.push(Point2D::new(from_fixed(key.0), from_fixed(key.1)));
+            next
+        })
+    }
+}
+
+fn to_fixed(value: f64) -> i32 {
+    (value * 65536.0).round() as i32
+}
+
+fn from_fixed(value: i32) -> f64 {
+    value as f64 / 65536.0
+}
+
+/// Angles in SEGS are binary angles: a full turn is 65536.
+fn binary_angle(radians: f64) -> i16 {
+    (radians.to_degrees() * 65536.0 / 360.0).round() as i32 as i16
+}
+
+/// Angle, linedef, side and offset, shared by vanilla and DeePBSP segs.
+fn write_seg_tail(out: &mut Vec<u8>, seg: &MapSeg) -> io::Result<()> {
+    out.write_i16::<LE>(seg.angle)?;
+    out.write_u16::<LE>(seg.linedef as u16)?;
+    out.write_i16::<LE>(seg.side as i16)?;
+    // Only texture alignment suffers from a clamped offset.
+    let offset = i16::try_from(seg.offset).unwrap_or_else(|_| {
+        warn!("Seg offset {} on linedef {} does not fit in 16 bits; clamped", seg.offset, seg.linedef);
+        seg.offset.clamp(i16::MIN.into(), i16::MAX.into()) as i16
+    });
+    out.write_i16::<LE>(offset)
+}
+
+fn read_seg_tail(record: &mut &[u8], v1: usize, v2: usize) -> io::Result<MapSeg> {
+    let angle = record.read_i16::<LE>()?;
+    let linedef = record.read_u16::<LE>()? as usize;
+    let side = record.read_i16::<LE>()? as u8;
+    Ok(MapSeg { v1, v2, angle, linedef, side, offset: record.read_i16::<LE>()?.into() })
+}
+
+/// The partition and boxes, which all formats store alike.
+fn write_node_head(out: &mut Vec<u8>, node: &MapNode) -> io::Result<()> {
+    for value in [node.x, node.y, node.dx, node.dy].iter().chain(node.bbox.iter().flatten()) {
+        out.write_i16::<LE>(*value)?;
+    }
+    Ok(())
+}
+
+fn read_node_head<R: Read>(reader: &mut R) -> io::Result<MapNode> {
+    let mut values = [0i16; 12];
+    for value in &mut values {
+        *value = reader.read_i16::<LE>()?;
+    }
+    let [x, y, dx, dy, a0, a1, a2, a3, b0, b1, b2, b3] = values;
+    Ok(MapNode { x, y, dx, dy, bbox: [[a0, a1, a2, a3], [b0, b1, b2, b3]], children: [Child::Node(0); 2] })
+}
+
+/// Nodes with 32-bit child numbers, as DeePBSP and XNOD store them.
+fn write_nodes_32(out: &mut Vec<u8>, nodes: &[MapNode]) -> io::Result<()> {
+    for node in nodes {
+        write_node_head(out, node)?;
+        for child in node.children {
+            out.write_u32::<LE>(match child {
+                Child::Node(n) => n as u32,
+                Child::Subsector(n) => n as u32 | SUBSECTOR_BIT_32,
+            })?;
+        }
+    }
+    Ok(())
+}
+
+fn read_nodes_32<R: Read>(reader: &mut R, count: usize) -> io::Result<Vec<MapNode>> {
+    let child = |raw: u32| {
+        if raw & SUBSECTOR_BIT_32 != 0 {
+            Child::Subsector((raw & !SUBSECTOR_BIT_32) as usize)
+        } else {
+            Child::Node(raw as usize)
+        }
+    };
+    // The count comes from the lump, so it is not trusted for preallocation.
+    let mut nodes = Vec::new();
+    for _ in 0..count {
+        let mut node = read_node_head(reader)?;
+        node.children = [child(reader.read_u32::<LE>()?), child(reader.read_u32::<LE>()?)];
+        nodes.push(node);
+    }
+    Ok(nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use byteorder::{ReadBytesExt, LE};
+    use parking_lot::RwLock;
+    use crate::bsp::bsp_partition::tests::room;
+    use crate::bsp::BuildOptions;
+    use crate::bsp::bsp_level::Block;
+    use crate::document::Document;
+    use super::*;
+
+    #[test]
+    fn test_vanilla_lumps() {
+        let doc = room(true);
+        let mut wad = Cursor::new(Vec::new());
+        doc.save_wad(&mut wad).unwrap();
+
+        let level: Vec<Lump> = doc.lumps().into_iter().skip(1).collect();
+        let names: Vec<&str> = level.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, ["THINGS", "LINEDEFS", "SIDEDEFS", "VERTEXES", "SEGS", "SSECTORS", "NODES", "SECTORS", "REJECT", "BLOCKMAP"]);
+        let vertex_count = level[3].data.len() / 4;
+        let seg_count = level[4].data.len() / 12;
+        let subsector_count = level[5].data.len() / 4;
+        assert_eq!(level[6].data.len() / NODE_SIZE, subsector_count - 1);
+        // The two walls cut by the pillar's lines gained vertices.
+        assert!(vertex_count > 8);
+
+        let mut segs = Cursor::new(&level[4].data);
+        for _ in 0..seg_count {
+            let (v1, v2) = (segs.read_u16::<LE>().unwrap(), segs.read_u16::<LE>().unwrap());
+            assert!((v1 as usize) < vertex_count && (v2 as usize) < vertex_count && v1 != v2);
+            segs.set_position(segs.position() + 8);
+        }
+        // Subsectors cover the segs in order.
+        let mut ssectors = Cursor::new(&level[5].data);
+        let mut next = 0;
+        for _ in 0..subsector_count {
+            let count = ssectors.read_u16::<LE>().unwrap();
+            assert_eq!(ssectors.read_u16::<LE>().unwrap(), next);
+            next += count;
+        }
+        assert_eq!(next as usize, seg_count);
+        // The root is last and refers to earlier nodes or to subsectors.
+        let mut nodes = Cursor::new(&level[6].data);
+        nodes.set_position((level[6].data.len() - 4) as u64);
+        for _ in 0..2 {
+            let child = nodes.read_u16::<LE>().unwrap();
+            assert!(child & SUBSECTOR_BIT != 0 || (child as usize) < subsector_count - 2);
+        }
+
+        // Loading the level again leaves the split vertices out.
+        let mut reloaded = Document::new();
+        wad.set_position(0);
+        reloaded.load_wad(&mut wad).unwrap();
+        tokio::runtime::Runtime::new().unwrap()
+            .block_on(reloaded.load_level_async("MAP01", &mut wad))
+            .unwrap();
+        assert_eq!(reloaded.vertices.read().len(), 8);
+    }
+
+    #[test]
+    fn test_node_formats() {
+        let bsp = BspLevel::new(Arc::new(RwLock::new(room(true))));
+        bsp.build().unwrap();
+        assert_eq!(NodeLumps::new(&bsp).unwrap().format, NodeFormat::Vanilla);
+
+        for format in [NodeFormat::Vanilla, NodeFormat::DeepBsp, NodeFormat::Xnod, NodeFormat::Znod] {
+            let data = NodeData::from_bsp(&bsp, format.fractional()).unwrap();
+            let options = BuildOptions { node_format: Some(format), ..Default::default() };
+            let level = BspLevel::with_options(bsp.doc.clone(), options);
+            level.build().unwrap();
+            let written = NodeLumps::new(&level).unwrap();
+            assert_eq!(written.format, format);
+            let [segs, ssectors, nodes] = [0, 1, 2].map(|i| &written.lumps[i].data);
+            let (found, read) = NodeData::read(segs, ssectors, nodes, data.vertex_count()).unwrap();
+            assert_eq!(found, format, "{}", format);
+
+            assert_eq!(read.subsectors, data.subsectors);
+            assert_eq!(read.nodes, data.nodes);
+            if format.fractional() {
+                assert_eq!((read.original_vertices, &read.vertices), (8, &data.vertices));
+                for (a, b) in read.segs.iter().zip(&data.segs) {
+                    assert_eq!((a.v1, a.v2, a.linedef, a.side), (b.v1, b.v2, b.linedef, b.side));
+                }
+            } else {
+                assert_eq!(written.vertices.len(), data.vertices.len());
+                assert_eq!(read.segs, data.segs);
+            }
+        }
+
+        let mut crowded = NodeData::from_bsp(&bsp, false).unwrap();
+        let seg = crowded.segs[0];
+        crowded.segs.resize(MAX_SEGS + 1, seg);
+        assert!(crowded.check_vanilla().is_err());
+        assert!(crowded.write_vanilla().is_err());
+        assert!(crowded.write_deepbsp().is_ok());
+
+        // Offsets too long for 16 bits keep vanilla out, and DeePBSP clamps them.
+        let mut far = NodeData::from_bsp(&bsp, false).unwrap();
+        far.segs[0].offset = 40_000;
+        assert!(far.check_vanilla().is_err());
+        let [segs, ssectors, nodes] = far.write_deepbsp().unwrap().map(|lump| lump.data);
+        let (_, read) = NodeData::read(&segs, &ssectors, &nodes, far.vertex_count()).unwrap();
+        assert_eq!(read.segs[0].offset, i16::MAX as i32);
+
+        // A blockmap too big for vanilla moves automatic mode to XNOD.
+        *bsp.blocks.write() = Block::new(BoundingBox::new(0.0, 0.0, 200.0 * 128.0, 200.0 * 128.0));
+        let written = NodeLumps::new(&bsp).unwrap();
+        assert_eq!(written.format, NodeFormat::Xnod);
+        assert!(written.lumps.iter().any(|lump| lump.name == "BLOCKMAP" && lump.data.is_empty()));
+
+        // A node count far past the end of the lump fails instead of allocating.
+        let mut corrupt = b"XNOD".to_vec();
+        corrupt.extend([0; 16]);
+        corrupt.extend(u32::MAX.to_le_bytes());
+        assert!(NodeData::read(&[], &[], &corrupt, 0).is_err());
+    }
+}
@@ -0,0 +1,241 @@
+// src/bsp/bsp_partition.rs
+// Choosing partition lines for the node builder.
+
+use std::sync::Arc;
+use rayon::prelude::*;
+
+use crate::bsp::{Point2D, Seg, SegPosition};
+
+/// Points closer than this (in map units) to a partition line count as on
+/// it. Splitting nearer than this to a seg's end would leave a sliver seg.
+pub const DIST_EPSILON: f64 = 1.0 / 128.0;
+
+/// Weights for scoring candidate partition lines. Lower cost is better.
+#[derive(Debug, Clone)]
+pub struct PartitionOptions {
+    /// Cost of each seg the partition cuts in two.
+    pub split_weight: f64,
+    /// Cost of each seg of difference between the two sides.
+    pub balance_weight: f64,
+    /// With more segs than this in a node, only `sample_size` evenly spaced
+    /// segs are tried as partitions. `None` always tries every seg.
+    pub sample_above: Option<usize>,
+    pub sample_size: usize,
+}
+
+impl Default for PartitionOptions {
+    /// Tries every seg up to a few thousand, which covers most maps.
+    fn default() -> Self {
+        Self {
+            split_weight: 8.0,
+            balance_weight: 1.0,
+            sample_above: Some(4096),
+            sample_size: 128,
+        }
+    }
+}
+
+impl PartitionOptions {
+    /// Samples early and sparsely: much quicker on large maps, at the cost of
+    /// a few more splits.
+    pub fn fast() -> Self {
+        Self {
+            sample_above: Some(256),
+            sample_size: 32,
+            ..Self::default()
+        }
+    }
+}
+
+/// The infinite line through a seg, used to sort other segs to its sides.
+#[derive(Debug, Clone, Copy)]
+pub struct PartitionLine {
+    pub start: Point2D,
+    pub dx: f64,
+    pub dy: f64,
+    length: f64,
+}
+
+impl PartitionLine {
+    pub fn new(start: Point2D, end: Point2D) -> Self {
+        let (dx, dy) = (end.x - start.x, end.y - start.y);
+        Self { start, dx, dy, length: dx.hypot(dy).max(f64::MIN_POSITIVE) }
+    }
+
+    pub fn from_seg(seg: &Seg) -> Self {
+        Self::new(seg.start, seg.end)
+    }
+
+    /// Signed distance of `point` from the line: positive on the front
+    /// (right) side, negative on the back.
+    pub fn distance(&self, point: &Point2D) -> f64 {
+        (self.dy * (point.x - self.start.x) - self.dx * (point.y - self.start.y)) / self.length
+    }
+
+    /// Which side `seg` is on. Segs lying on the line are `Coincident`; see
+    /// [`PartitionLine::same_direction`] for where they belong.
+    pub fn classify(&self, seg: &Seg) -> SegPosition {
+        let a = self.distance(&seg.start);
+        let b = self.distance(&seg.end);
+        if a.abs() <= DIST_EPSILON && b.abs() <= DIST_EPSILON {
+            SegPosition::Coincident
+        } else if a >= -DIST_EPSILON && b >= -DIST_EPSILON {
+            SegPosition::Front
+        } else if a <= DIST_EPSILON && b <= DIST_EPSILON {
+            SegPosition::Back
+        } else {
+            SegPosition::Spanning
+        }
+    }
+
+    /// True if `seg` points the same way as the line. Coincident segs that
+    /// do go on the front side, the others on the back.
+    pub fn same_direction(&self, seg: &Seg) -> bool {
+        self.dx * (seg.end.x - seg.start.x) + self.dy * (seg.end.y - seg.start.y) > 0.0
+    }
+
+    /// Where the line crosses `seg`, which must be `Spanning`.
+    pub fn intersection(&self, seg: &Seg) -> Point2D {
+        let a = self.distance(&seg.start);
+        let b = self.distance(&seg.end);
+        let t = a / (a - b);
+        Point2D::new(
+            seg.start.x + t * (seg.end.x - seg.start.x),
+            seg.start.y + t * (seg.end.y - seg.start.y),
+        )
+    }
+}
+
+/// Picks the seg whose line makes the best partition of `segs`, or `None`
+/// if no seg has anything behind it: the segs then enclose a convex region
+/// and form a subsector.
+///
+/// Each candidate costs `split_weight` per seg it splits plus
+/// `balance_weight` per seg of difference between its front and back.
+/// A candidate with nothing behind it divides nothing and is skipped, as is
+/// a zero-length seg, which has no direction to divide by.
+pub fn choose_partition(segs: &[Arc<Seg>], options: &PartitionOptions) -> Option<usize> {
+    let sampled = options.sample_above.is_some_and(|limit| segs.len() > limit);
+    if sampled {
+        let step = segs.len().div_ceil(options.sample_size.max(1));
+        let candidates: Vec<usize> = (0..segs.len()).step_by(step).collect();
+        if let Some(best) = best_candidate(segs, &candidates, options) {
+            return Some(best);
+        }
+        // The sample missed every useful line; that does not make the node
+        // convex, so fall back to trying them all.
+    }
+    let candidates: Vec<usize> = (0..segs.len()).collect();
+    best_candidate(segs, &candidates, options)
+}
+
+fn best_candidate(segs: &[Arc<Seg>], candidates: &[usize], options: &PartitionOptions) -> Option<usize> {
+    candidates
+        .par_iter()
+        .filter_map(|&index| partition_cost(segs, &segs[index], options).map(|cost| (cost, index)))
+        .min_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)))
+        .map(|(_, index)| index)
+}
+
+fn partition_cost(segs: &[Arc<Seg>], candidate: &Seg, options: &PartitionOptions) -> Option<f64> {
+    let line = PartitionLine::from_seg(candidate);
+    if line.length < DIST_EPSILON {
+        return None;
+    }
+    let (mut front, mut back, mut splits) = (0usize, 0usize, 0usize);
+    for seg in segs {
+        match line.classify(seg) {
+            SegPosition::Front => front += 1,
+            SegPosition::Back => back += 1,
+            SegPosition::Spanning => splits += 1,
+            SegPosition::Coincident if line.same_direction(seg) => front += 1,
+            SegPosition::Coincident => back += 1,
+        }
+    }
+    if back == 0 && splits == 0 {
+        return None;
+    }
+    let imbalance = (front as f64 - back as f64).abs();
+    Some(splits as f64 * options.split_weight + imbalance * options.balance_weight)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use parking_lot::RwLock;
+    use crate::bsp::{BspLevel, BspNode, BuildOptions};
+    use crate::document::Document;
+    use crate::map::SideDef;
+
+    /// A 512x512 room, with a 128x128 pillar in the middle if `pillar`.
+    pub(crate) fn room(pillar: bool) -> Document {
+        let mut doc = Document::new();
+        doc.add_sector(0, 128, "FLOOR4_8".into(), "CEIL3_5".into(), 160, 0);
+        doc.sidedefs.write().push(Arc::new(SideDef::new(
+            0, 0, "-".into(), "-".into(), "STARTAN2".into(), 0,
+        )));
+        let square = |doc: &mut Document, points: [(i32, i32); 4]| {
+            let first = doc.vertices.read().len();
+            for (x, y) in points {
+                doc.add_vertex(x, y);
+            }
+            for i in 0..4 {
+                doc.add_linedef(first + i, first + (i + 1) % 4, 0, -1);
+            }
+        };
+        // The walls wind clockwise and face in; the pillar winds the other
+        // way and faces out.
+        square(&mut doc, [(0, 0), (0, 512), (512, 512), (512, 0)]);
+        if pillar {
+            square(&mut doc, [(192, 192), (320, 192), (320, 320), (192, 320)]);
+        }
+        doc
+    }
+
+    /// Checks every leaf is convex and returns the number of segs in leaves.
+    fn leaf_segs(node: &BspNode) -> usize {
+        if !node.is_leaf() {
+            return leaf_segs(node.front.as_ref().unwrap()) + leaf_segs(node.back.as_ref().unwrap());
+        }
+        for seg in &node.segs {
+            let line = PartitionLine::from_seg(seg);
+            for other in &node.segs {
+                let position = line.classify(other);
+                assert!(matches!(position, SegPosition::Front | SegPosition::Coincident), "leaf is not convex");
+            }
+        }
+        node.segs.len()
+    }
+
+    #[test]
+    fn test_partition_choice() {
+        // A convex room needs no partition at all.
+        let bsp = BspLevel::new(Arc::new(RwLock::new(room(false))));
+        bsp.build().unwrap();
+        assert!(bsp.root.read().as_ref().unwrap().is_leaf());
+
+        // Any line of the pillar cuts two walls; a good choice of lines
+        // reuses those cuts rather than making new ones.
+        let bsp = BspLevel::new(Arc::new(RwLock::new(room(true))));
+        bsp.build().unwrap();
+        let segs = leaf_segs(bsp.root.read().as_ref().unwrap());
+        assert!((8..=12).contains(&segs), "{} segs after splitting", segs);
+
+        // Without a split penalty the tree may split more, but stays valid.
+        let options = BuildOptions {
+            partition: PartitionOptions { split_weight: 0.0, sample_above: Some(2), sample_size: 1, ..Default::default() },
+            ..Default::default()
+        };
+        let bsp = BspLevel::with_options(Arc::new(RwLock::new(room(true))), options);
+        bsp.build().unwrap();
+        assert!(leaf_segs(bsp.root.read().as_ref().unwrap()) >= 8);
+
+        // A zero-length line is never a partition, so the room stays one leaf.
+        let mut doc = room(false);
+        let point = doc.add_vertex(256, 256);
+        doc.add_linedef(point, point, 0, -1);
+        let bsp = BspLevel::new(Arc::new(RwLock::new(doc)));
+        bsp.build().unwrap();
+        assert!(bsp.root.read().as_ref().unwrap().is_leaf());
+    }
+}
@@ -0,0 +1,237 @@
+// src/bsp/bsp_reject.rs
+// Building the REJECT table from sector line-of-sight.
+
+use crate::bsp::Point2D;
+use crate::document::Document;
+
+/// How much work a search from one sector may do before it gives up and
+/// lets that sector see everything it is connected to. Stepping through a
+/// portal costs the cube of the path's length, as testing for a line
+/// through the path does.
+const SEARCH_BUDGET: usize = 2_000_000;
+/// How much work the searches from all sectors may do together. Once it is
+/// spent, the remaining sectors see everything they are connected to.
+const TOTAL_BUDGET: usize = 50_000_000;
+
+/// How the REJECT lump is filled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RejectMode {
+    /// Every bit clear: no pair of sectors is rejected. Quick to write, but
+    /// monsters check sight against the player everywhere on the map.
+    #[default]
+    Zeroed,
+    /// Full visibility analysis: only pairs that could have a line of sight
+    /// through two-sided lines are left clear. Work is capped, so very large
+    /// maps get a coarser table rather than a long wait.
+    Full,
+}
+
+/// A two-sided line seen from one of its sectors, leading to `to`. `left`
+/// and `right` are its ends as seen by someone looking through it.
+#[derive(Debug, Clone, Copy)]
+struct Portal {
+    to: usize,
+    left: Point2D,
+    right: Point2D,
+}
+
+/// The REJECT lump for `doc`: one bit per pair of sectors, row by row, set
+/// where the two sectors cannot see each other.
+pub fn build_reject(doc: &Document, mode: RejectMode) -> Vec<u8> {
+    let sectors = doc.sectors.read().len();
+    let mut table = vec![0u8; (sectors * sectors).div_ceil(8)];
+    if mode == RejectMode::Zeroed {
+        return table;
+    }
+
+    let portals = sector_portals(doc, sectors);
+    let mut budget = TOTAL_BUDGET;
+    for source in 0..sectors {
+        for (target, seen) in sight_from(source, &portals, &mut budget).into_iter().enumerate() {
+            if !seen {
+                let bit = source * sectors + target;
+                table[bit / 8] |= 1 << (bit % 8);
+            }
+        }
+    }
+    table
+}
+
+/// The portals out of each sector. Lines with the same sector on both sides
+/// lead nowhere new and are left out.
+fn sector_portals(doc: &Document, sectors: usize) -> Vec<Vec<Portal>> {
+    let linedefs = doc.linedefs.read();
+    let sidedefs = doc.sidedefs.read();
+    let vertices = doc.vertices.read();
+    let sector_of = |side: i32| {
+        let sidedef = sidedefs.get(usize::try_from(side).ok()?)?;
+        usize::try_from(sidedef.sector).ok().filter(|&s| s < sectors)
+    };
+
+    let mut portals = vec![Vec::new(); sectors];
+    for linedef in linedefs.iter() {
+        let (Some(front), Some(back)) = (sector_of(linedef.right), sector_of(linedef.left)) else {
+            continue;
+        };
+        let (Some(start), Some(end)) = (vertices.get(linedef.start), vertices.get(linedef.end)) else {
+            continue;
+        };
+        if front == back {
+            continue;
+        }
+        let start = Point2D::new(start.x as f64, start.y as f64);
+        let end = Point2D::new(end.x as f64, end.y as f64);
+        // Looking from the front (right) side, the start is on the left.
+        portals[front].push(Portal { to: back, left: start, right: end });
+        portals[back].push(Portal { to: front, left: end, right: start });
+    }
+    portals
+}
+
+/// Which sectors `source` may see. A sector counts as seen if some straight
+/// line passes through every portal on a path to it; the search follows
+/// paths without loops and stops at portals no such line gets through.
+/// The work done is taken from `total`.
+fn sight_from(source: usize, portals: &[Vec<Portal>], total: &mut usize) -> Vec<bool> {
+    let budget = SEARCH_BUDGET.min(*total);
+    let mut search = Search {
+        portals,
+        seen: vec![false; portals.len()],
+        on_path: vec![false; portals.len()],
+        chain: Vec::new(),
+        budget,
+    };
+    search.seen[source] = true;
+    search.on_path[source] = true;
+    let finished = search.walk(source);
+    *total -= budget - search.budget;
+    if !finished {
+        // Too many paths to follow: fall back to everything reachable.
+        search.seen = reachable_from(source, portals);
+    }
+    search.seen
+}
+
+struct Search<'a> {
+    portals: &'a [Vec<Portal>],
+    seen: Vec<bool>,
+    on_path: Vec<bool>,
+    chain: Vec<Portal>,
+    budget: usize,
+}
+
+impl Search<'_> {
+    /// Returns false if the budget ran out.
+    fn walk(&mut self, sector: usize) -> bool {
+        for &portal in &self.portals[sector] {
+            if self.on_path[portal.to] {
+                continue;
+            }
+            let cost = (self.chain.len() + 1).pow(3);
+            if self.budget < cost {
+                return false;
+            }
+            self.budget -= cost;
+
+            self.chain.push(portal);
+            if line_through(&self.chain) {
+                self.seen[portal.to] = true;
+                self.on_path[portal.to] = true;
+                let finished = self.walk(portal.to);
+                self.on_path[portal.to] = false;
+                if !finished {
+                    return false;
+                }
+            }
+            self.chain.pop();
+        }
+        true
+    }
+}
+
+/// Whether one straight line can pass through every portal in `chain`: one
+/// with every left end on its left and every right end on its right. If any
+/// such line exists, one runs through two of the ends, so those are tried.
+fn line_through(chain: &[Portal]) -> bool {
+    if chain.len() <= 1 {
+        return true;
+    }
+    let points: Vec<Point2D> = chain.iter().flat_map(|p| [p.left, p.right]).collect();
+    let side = |a: &Point2D, b: &Point2D, p: &Point2D| (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x);
+    let separates = |a: &Point2D, b: &Point2D| {
+        chain.iter().all(|p| side(a, b, &p.left) >= 0.0 && side(a, b, &p.right) <= 0.0)
+    };
+    points.iter().enumerate().any(|(i, a)| {
+        points[i + 1..].iter().any(|b| a != b && (separates(a, b) || separates(b, a)))
+    })
+}
+
+fn reachable_from(source: usize, portals: &[Vec<Portal>]) -> Vec<bool> {
+    let mut seen = vec![false; portals.len()];
+    let mut stack = vec![source];
+    seen[source] = true;
+    while let Some(sector) = stack.pop() {
+        for portal in &portals[sector] {
+            if !seen[portal.to] {
+                seen[portal.to] = true;
+                stack.push(portal.to);
+            }
+        }
+    }
+    seen
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::map::SideDef;
+    use super::*;
+
+    /// Three 128x128 rooms in a row, doorways in the middle of the walls
+    /// between them, and a fourth room north of the last one, reached by a
+    /// doorway near its west corner. Walls don't matter to the table.
+    fn rooms() -> Document {
+        let mut doc = Document::new();
+        for sector in 0..4 {
+            doc.add_sector(0, 128, "FLOOR4_8".into(), "CEIL3_5".into(), 160, 0);
+            doc.sidedefs.write().push(Arc::new(SideDef::new(0, 0, "-".into(), "-".into(), "-".into(), sector)));
+        }
+        let line = |doc: &mut Document, a: (i32, i32), b: (i32, i32), right: i16, left: i16| {
+            let (a, b) = (doc.add_vertex(a.0, a.1), doc.add_vertex(b.0, b.1));
+            doc.add_linedef(a, b, right, left);
+        };
+        line(&mut doc, (128, 48), (128, 80), 1, 0);
+        line(&mut doc, (256, 48), (256, 80), 2, 1);
+        line(&mut doc, (264, 128), (280, 128), 2, 3);
+        doc
+    }
+
+    fn rejected(table: &[u8], a: usize, b: usize) -> bool {
+        let bit = a * 4 + b;
+        table[bit / 8] & (1 << (bit % 8)) != 0
+    }
+
+    #[test]
+    fn test_reject() {
+        let doc = rooms();
+        assert_eq!(build_reject(&doc, RejectMode::Zeroed), vec![0; 2]);
+
+        let table = build_reject(&doc, RejectMode::Full);
+        assert_eq!(table.len(), 2);
+        for a in 0..4 {
+            assert!(!rejected(&table, a, a));
+            for b in 0..4 {
+                assert_eq!(rejected(&table, a, b), rejected(&table, b, a));
+            }
+        }
+        assert!(!rejected(&table, 0, 1) && !rejected(&table, 0, 2));
+        assert!(!rejected(&table, 1, 3) && !rejected(&table, 2, 3));
+        // Through two doorways in line, the view is too narrow to turn the
+        // corner into the fourth room.
+        assert!(rejected(&table, 0, 3));
+
+        // With the work for the map used up, a sector sees all it connects to.
+        let portals = sector_portals(&doc, 4);
+        assert_eq!(sight_from(0, &portals, &mut 0), vec![true; 4]);
+    }
+}
@@ -1,11 +1,17 @@
 // src/bsp/mod.rs (CORRECTED)
 pub mod bsp_level;
+pub mod bsp_lumps;
 pub mod bsp_node;
+pub mod bsp_partition;
+pub mod bsp_reject;
 mod bsp_procedural; // Not public, used internally
 mod bsp_util; // Not public, used internally
 pub mod debug_viz; // Make it public
-pub use bsp_level::BspLevel; // Export Seg and Block
+pub use bsp_level::{BspLevel, BuildOptions}; // Export Seg and Block
+pub use bsp_lumps::{NodeFormat, NodeLumps};
 pub use bsp_node::BspNode;
+pub use bsp_partition::PartitionOptions;
+pub use bsp_reject::RejectMode;
 pub use bsp_util::{Line2D, Point2D, BoundingBox}; // Re-export geometry types
 pub use bsp_level::Seg;
 
@@ -0,0 +1,220 @@
+// src/document/archive.rs
+
+//! PK3 (ZIP) archive support.
+//!
+//! A PK3 keeps each map as a WAD under `maps/`, and other resources as loose
+//! files known by their path. The file name without extension, cut to eight
+//! characters, is only a short name for display and WAD export. The
+//! top-level directory decides the namespace: `flats/`, `patches/`,
+//! `sprites/` and `textures/` work like the marker ranges of a WAD.
+
+use std::fs;
+use std::io::{self, Cursor, Read, Seek, Write};
+use std::path::Path;
+use std::sync::Arc;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::document::resources::Namespace;
+use crate::document::wad::Lump;
+
+/// A single file stored in an archive.
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    /// Path inside the archive, using `/` separators.
+    pub path: String,
+    pub data: Vec<u8>,
+    /// Index of the file in the archive it was read from, until it is
+    /// replaced. Such files are copied over compressed as they were.
+    source_index: Option<usize>,
+}
+
+/// An in-memory PK3/ZIP archive.
+#[derive(Debug, Clone, Default)]
+pub struct Pk3Archive {
+    entries: Vec<ArchiveEntry>,
+    /// The ZIP the archive was read from, if any.
+    source: Option<Arc<[u8]>>,
+}
+
+impl Pk3Archive {
+    /// True if `data` starts like a ZIP file.
+    pub fn is_archive(data: &[u8]) -> bool {
+        data.starts_with(b"PK\x03\x04") || data.starts_with(b"PK\x05\x06")
+    }
+
+    /// Reads every file of the archive into memory. Directories are skipped.
+    pub fn from_bytes(data: &[u8]) -> io::Result<Self> {
+        let mut zip = ZipArchive::new(Cursor::new(data)).map_err(zip_error)?;
+        let mut entries = Vec::with_capacity(zip.len());
+        for i in 0..zip.len() {
+            let mut file = zip.by_index(i).map_err(zip_error)?;
+            if file.is_dir() {
+                continue;
+            }
+            let mut data = Vec::with_capacity(file.size() as usize);
+            file.read_to_end(&mut data)?;
+            entries.push(ArchiveEntry { path: file.name().replace('\\', "/"), data, source_index: Some(i) });
+        }
+        Ok(Self { entries, source: Some(data.into()) })
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn entries(&self) -> &[ArchiveEntry] {
+        &self.entries
+    }
+
+    /// The contents of the file at `path` (case-insensitive).
+    pub fn get(&self, path: &str) -> Option<&[u8]> {
+        self.entries
+            .iter()
+            .find(|e| e.path.eq_ignore_ascii_case(path))
+            .map(|e| e.data.as_slice())
+    }
+
+    /// Replaces the file at `path`, or adds it if missing.
+    pub fn set(&mut self, path: &str, data: Vec<u8>) {
+        match self.entries.iter_mut().find(|e| e.path.eq_ignore_ascii_case(path)) {
+            Some(entry) => {
+                entry.data = data;
+                entry.source_index = None;
+            }
+            None => self.entries.push(ArchiveEntry { path: path.to_string(), data, source_index: None }),
+        }
+    }
+
+    /// Paths of the map WADs stored under `maps/`.
+    pub fn map_wads(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .filter(|e| {
+                let lower = e.path.to_ascii_lowercase();
+                lower.starts_with("maps/") && lower.ends_with(".wad")
+            })
+            .map(|e| e.path.clone())
+            .collect()
+    }
+
+    /// The loose files as lumps named by their full path, with the namespace
+    /// given by their top-level directory. Map WADs are not included.
+    pub fn lumps(&self) -> Vec<(Lump, Namespace)> {
+        self.entries
+            .iter()
+            .filter_map(|e| {
+                let namespace = namespace_for_path(&e.path)?;
+                Some((Lump::new(e.path.clone(), e.data.clone()), namespace))
+            })
+            .collect()
+    }
+
+    /// Writes the archive. Files unchanged since loading are copied in their
+    /// original compressed form; new and replaced ones are deflated.
+    pub fn write<W: Write + Seek>(&self, writer: W) -> io::Result<()> {
+        let mut source = match &self.source {
+            Some(data) => Some(ZipArchive::new(Cursor::new(data.as_ref())).map_err(zip_error)?),
+            None => None,
+        };
+        let mut zip = ZipWriter::new(writer);
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        for entry in &self.entries {
+            if let (Some(index), Some(source)) = (entry.source_index, source.as_mut()) {
+                let file = source.by_index_raw(index).map_err(zip_error)?;
+                zip.raw_copy_file(file).map_err(zip_error)?;
+                continue;
+            }
+            zip.start_file(entry.path.as_str(), options).map_err(zip_error)?;
+            zip.write_all(&entry.data)?;
+        }
+        zip.finish().map_err(zip_error)?;
+        Ok(())
+    }
+}
+
+/// The short lump name for an archive path: the file name without
+/// extension, uppercased and cut to 8 characters. Different paths can share
+/// one, so it is for display and WAD export, not for telling files apart.
+pub fn lump_name_from_path(path: &str) -> String {
+    let file = path.rsplit('/').next().unwrap_or(path);
+    let stem = file.split('.').next().unwrap_or(file);
+    stem.to_ascii_uppercase().chars().take(8).collect()
+}
+
+/// The namespace of a loose file, or `None` for files that are not lumps
+/// (map WADs under `maps/`).
+fn namespace_for_path(path: &str) -> Option<Namespace> {
+    let (dir, _) = match path.split_once('/') {
+        Some(split) => split,
+        None => return Some(Namespace::Global),
+    };
+    match dir.to_ascii_lowercase().as_str() {
+        "maps" => None,
+        "flats" => Some(Namespace::Flats),
+        "patches" => Some(Namespace::Patches),
+        "sprites" => Some(Namespace::Sprites),
+        "textures" | "hires" => Some(Namespace::Textures),
+        _ => Some(Namespace::Global),
+    }
+}
+
+fn zip_error(e: zip::result::ZipError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_round_trip() {
+        let mut archive = Pk3Archive::default();
+        archive.set("maps/map01.wad", b"PWAD....".to_vec());
+        archive.set("flats/floor7_1.png", vec![1, 2, 3]);
+        archive.set("mapinfo.txt", b"map MAP01 \"Test\"".to_vec());
+
+        let mut cursor = Cursor::new(Vec::new());
+        archive.write(&mut cursor).unwrap();
+        let data = cursor.into_inner();
+        assert!(Pk3Archive::is_archive(&data));
+
+        let loaded = Pk3Archive::from_bytes(&data).unwrap();
+        assert_eq!(loaded.map_wads(), vec!["maps/map01.wad".to_string()]);
+        assert_eq!(loaded.get("FLATS/FLOOR7_1.PNG"), Some(&[1u8, 2, 3][..]));
+
+        let lumps = loaded.lumps();
+        assert_eq!(lumps.len(), 2);
+        assert_eq!(lumps[0].0.name, "flats/floor7_1.png");
+        assert_eq!(lumps[0].1, Namespace::Flats);
+        assert_eq!(lumps[1].0.name, "mapinfo.txt");
+        assert_eq!(lumps[1].1, Namespace::Global);
+        assert_eq!(lump_name_from_path("textures/wall/brick_long.png"), "BRICK_LO");
+    }
+
+    #[test]
+    fn test_unchanged_entries_copied_raw() {
+        let mut cursor = Cursor::new(Vec::new());
+        let mut zip = ZipWriter::new(&mut cursor);
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        zip.start_file("mapinfo.txt", stored).unwrap();
+        zip.write_all(b"map MAP01 \"Test\"").unwrap();
+        zip.start_file("maps/map01.wad", stored).unwrap();
+        zip.write_all(b"PWAD....").unwrap();
+        zip.finish().unwrap();
+        drop(zip);
+
+        let mut archive = Pk3Archive::from_bytes(cursor.get_ref()).unwrap();
+        archive.set("maps/map01.wad", b"PWAD,,,,".to_vec());
+        let mut out = Cursor::new(Vec::new());
+        archive.write(&mut out).unwrap();
+
+        let mut written = ZipArchive::new(Cursor::new(out.into_inner())).unwrap();
+        let method = |zip: &mut ZipArchive<_>, name| zip.by_name(name).unwrap().compression();
+        assert_eq!(method(&mut written, "mapinfo.txt"), CompressionMethod::Stored);
+        assert_eq!(method(&mut written, "maps/map01.wad"), CompressionMethod::Deflated);
+        let loaded = Pk3Archive::from_bytes(written.into_inner().get_ref()).unwrap();
+        assert_eq!(loaded.get("mapinfo.txt"), Some(&b"map MAP01 \"Test\""[..]));
+        assert_eq!(loaded.get("maps/map01.wad"), Some(&b"PWAD,,,,"[..]));
+    }
+}
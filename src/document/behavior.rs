@@ -0,0 +1,901 @@
+// src/document/behavior.rs
+
+//! Parser and disassembler for compiled ACS (the BEHAVIOR lump).
+//!
+//! Three object formats exist:
+//! - `ACS\0`: Hexen's original layout, a script directory and string table
+//!   at the end of the lump.
+//! - `ACSE`: ZDoom's chunked layout (SPTR, STRL, FUNC, ...), 4-byte p-code.
+//! - `ACSe`: as ACSE, but with compressed p-code (1 or 2 byte opcodes and
+//!   1-byte variable indices).
+//!
+//! ACSE/ACSe lumps may also start with `ACS\0` and hide their chunks behind
+//! an old-style directory so that Hexen-only ports can still run them.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AcsError {
+    #[error("Not an ACS object file (header {0:?})")]
+    BadMagic([u8; 4]),
+
+    #[error("BEHAVIOR lump truncated at offset {0}")]
+    Truncated(usize),
+}
+
+/// The object file layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcsFormat {
+    /// `ACS\0`, as written for Hexen.
+    Acs0,
+    /// `ACSE`, chunked with 4-byte p-code.
+    Enhanced,
+    /// `ACSe`, chunked with compressed p-code.
+    Compact,
+}
+
+impl AcsFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            AcsFormat::Acs0 => "ACS0",
+            AcsFormat::Enhanced => "ACSE",
+            AcsFormat::Compact => "ACSe",
+        }
+    }
+}
+
+/// When a script runs, from its declaration (`script 1 OPEN`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptType {
+    Closed,
+    Open,
+    Respawn,
+    Death,
+    Enter,
+    Pickup,
+    BlueReturn,
+    RedReturn,
+    WhiteReturn,
+    Lightning,
+    Unloading,
+    Disconnect,
+    Return,
+    Event,
+    Kill,
+    Reopen,
+    Unknown(u16),
+}
+
+impl ScriptType {
+    pub fn from_code(code: u16) -> Self {
+        match code {
+            0 => ScriptType::Closed,
+            1 => ScriptType::Open,
+            2 => ScriptType::Respawn,
+            3 => ScriptType::Death,
+            4 => ScriptType::Enter,
+            5 => ScriptType::Pickup,
+            6 => ScriptType::BlueReturn,
+            7 => ScriptType::RedReturn,
+            8 => ScriptType::WhiteReturn,
+            12 => ScriptType::Lightning,
+            13 => ScriptType::Unloading,
+            14 => ScriptType::Disconnect,
+            15 => ScriptType::Return,
+            16 => ScriptType::Event,
+            17 => ScriptType::Kill,
+            18 => ScriptType::Reopen,
+            other => ScriptType::Unknown(other),
+        }
+    }
+
+    /// The keyword used in ACS source; empty for closed scripts.
+    pub fn keyword(self) -> String {
+        match self {
+            ScriptType::Closed => String::new(),
+            ScriptType::Open => "OPEN".into(),
+            ScriptType::Respawn => "RESPAWN".into(),
+            ScriptType::Death => "DEATH".into(),
+            ScriptType::Enter => "ENTER".into(),
+            ScriptType::Pickup => "PICKUP".into(),
+            ScriptType::BlueReturn => "BLUERETURN".into(),
+            ScriptType::RedReturn => "REDRETURN".into(),
+            ScriptType::WhiteReturn => "WHITERETURN".into(),
+            ScriptType::Lightning => "LIGHTNING".into(),
+            ScriptType::Unloading => "UNLOADING".into(),
+            ScriptType::Disconnect => "DISCONNECT".into(),
+            ScriptType::Return => "RETURN".into(),
+            ScriptType::Event => "EVENT".into(),
+            ScriptType::Kill => "KILL".into(),
+            ScriptType::Reopen => "REOPEN".into(),
+            ScriptType::Unknown(code) => format!("TYPE{}", code),
+        }
+    }
+}
+
+/// A script entry point.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AcsScript {
+    /// Script number; named scripts have negative numbers.
+    pub number: i32,
+    pub name: Option<String>,
+    pub kind: ScriptType,
+    pub arg_count: u32,
+    /// Offset of the p-code in the lump.
+    pub address: u32,
+    /// SFLG bits: 1 = NET, 2 = CLIENTSIDE.
+    pub flags: u16,
+    /// Local variable count from SVCT, if the compiler wrote one.
+    pub var_count: Option<u16>,
+}
+
+impl AcsScript {
+    /// `1`, or `"name"` for named scripts.
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(name) => format!("\"{}\"", name),
+            None => self.number.to_string(),
+        }
+    }
+}
+
+/// A function from the FUNC chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AcsFunction {
+    pub name: Option<String>,
+    pub arg_count: u8,
+    pub local_count: u8,
+    pub returns_value: bool,
+    /// Library the function comes from, for imported functions.
+    pub import: u8,
+    /// Offset of the p-code; 0 for imported functions.
+    pub address: u32,
+}
+
+impl AcsFunction {
+    pub fn is_imported(&self) -> bool {
+        self.address == 0
+    }
+}
+
+/// A map variable or array, merged from MINI, ARAY and MEXP.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MapVariable {
+    pub index: u32,
+    /// Exported name (libraries only).
+    pub name: Option<String>,
+    pub initial: i32,
+    /// Element count for arrays.
+    pub array_size: Option<u32>,
+}
+
+/// A parsed BEHAVIOR lump.
+#[derive(Debug, Clone)]
+pub struct BehaviorLump {
+    pub format: AcsFormat,
+    pub scripts: Vec<AcsScript>,
+    pub functions: Vec<AcsFunction>,
+    pub strings: Vec<String>,
+    pub map_vars: Vec<MapVariable>,
+    /// Map variables imported from libraries: (index, name).
+    pub imported_vars: Vec<(u32, String)>,
+    /// Libraries named by `#import`.
+    pub libraries: Vec<String>,
+    data: Vec<u8>,
+    /// End of the p-code area.
+    code_end: usize,
+}
+
+impl BehaviorLump {
+    pub fn parse(data: &[u8]) -> Result<Self, AcsError> {
+        let magic: [u8; 4] = data.get(0..4).ok_or(AcsError::Truncated(0))?.try_into().unwrap();
+        let dir_offset = read_u32(data, 4)? as usize;
+        let mut lump = BehaviorLump {
+            format: AcsFormat::Acs0,
+            scripts: Vec::new(),
+            functions: Vec::new(),
+            strings: Vec::new(),
+            map_vars: Vec::new(),
+            imported_vars: Vec::new(),
+            libraries: Vec::new(),
+            data: data.to_vec(),
+            code_end: dir_offset.min(data.len()),
+        };
+
+        let chunk_start = match &magic {
+            b"ACSE" => Some((AcsFormat::Enhanced, dir_offset)),
+            b"ACSe" => Some((AcsFormat::Compact, dir_offset)),
+            b"ACS\0" => match dir_offset.checked_sub(8).and_then(|o| data.get(o..o + 8)) {
+                Some([a, b, c, d, b'A', b'C', b'S', e]) if *e == b'E' || *e == b'e' => {
+                    let format = if *e == b'E' { AcsFormat::Enhanced } else { AcsFormat::Compact };
+                    Some((format, u32::from_le_bytes([*a, *b, *c, *d]) as usize))
+                }
+                _ => None,
+            },
+            _ => return Err(AcsError::BadMagic(magic)),
+        };
+
+        match chunk_start {
+            None => lump.parse_acs0(dir_offset)?,
+            Some((format, start)) => {
+                lump.format = format;
+                lump.code_end = lump.code_end.min(start);
+                // Direct ACSE/ACSe headers use 12-byte script pointers, the
+                // indirect (ACS\0) form the packed 8-byte layout.
+                lump.parse_chunks(start, magic[3] != 0)?;
+            }
+        }
+        lump.scripts.sort_by_key(|s| s.number);
+        Ok(lump)
+    }
+
+    fn parse_acs0(&mut self, dir_offset: usize) -> Result<(), AcsError> {
+        let data = &self.data;
+        let count = read_u32(data, dir_offset)? as usize;
+        let mut pos = dir_offset + 4;
+        for _ in 0..count {
+            let number = read_u32(data, pos)?;
+            self.scripts.push(AcsScript {
+                number: (number % 1000) as i32,
+                name: None,
+                kind: ScriptType::from_code((number / 1000) as u16),
+                address: read_u32(data, pos + 4)?,
+                arg_count: read_u32(data, pos + 8)?,
+                flags: 0,
+                var_count: None,
+            });
+            pos += 12;
+        }
+        // The strings sit between the p-code and the directory.
+        let string_count = read_u32(data, pos)? as usize;
+        for i in 0..string_count {
+            let offset = read_u32(data, pos + 4 + i * 4)? as usize;
+            self.strings.push(read_cstring(data, offset)?);
+            self.code_end = self.code_end.min(offset);
+        }
+        Ok(())
+    }
+
+    fn parse_chunks(&mut self, start: usize, wide_script_pointers: bool) -> Result<(), AcsError> {
+        let data = self.data.clone();
+        let mut chunks: Vec<(&[u8; 4], &[u8])> = Vec::new();
+        let mut pos = start;
+        while pos + 8 <= data.len() {
+            let id: &[u8; 4] = data[pos..pos + 4].try_into().unwrap();
+            let len = read_u32(&data, pos + 4)? as usize;
+            let body = data.get(pos + 8..pos + 8 + len).ok_or(AcsError::Truncated(pos))?;
+            chunks.push((id, body));
+            pos += 8 + len;
+        }
+
+        let mut vars: BTreeMap<u32, MapVariable> = BTreeMap::new();
+        let mut script_names = Vec::new();
+        let mut function_names = Vec::new();
+        let mut flags = Vec::new();
+        let mut var_counts = Vec::new();
+
+        for (id, body) in chunks {
+            match id {
+                b"SPTR" if wide_script_pointers => {
+                    for entry in body.chunks_exact(12) {
+                        self.scripts.push(AcsScript {
+                            number: i16::from_le_bytes([entry[0], entry[1]]) as i32,
+                            name: None,
+                            kind: ScriptType::from_code(u16::from_le_bytes([entry[2], entry[3]])),
+                            address: read_u32(entry, 4)?,
+                            arg_count: read_u32(entry, 8)?,
+                            flags: 0,
+                            var_count: None,
+                        });
+                    }
+                }
+                b"SPTR" => {
+                    for entry in body.chunks_exact(8) {
+                        self.scripts.push(AcsScript {
+                            number: i16::from_le_bytes([entry[0], entry[1]]) as i32,
+                            name: None,
+                            kind: ScriptType::from_code(entry[2] as u16),
+                            arg_count: entry[3] as u32,
+                            address: read_u32(entry, 4)?,
+                            flags: 0,
+                            var_count: None,
+                        });
+                    }
+                }
+                b"SFLG" => flags.extend(u16_pairs(body)),
+                b"SVCT" => var_counts.extend(u16_pairs(body)),
+                b"SNAM" => script_names = read_name_table(body)?,
+                b"FNAM" => function_names = read_name_table(body)?,
+                b"FUNC" => {
+                    for entry in body.chunks_exact(8) {
+                        self.functions.push(AcsFunction {
+                            name: None,
+                            arg_count: entry[0],
+                            local_count: entry[1],
+                            returns_value: entry[2] != 0,
+                            import: entry[3],
+                            address: read_u32(entry, 4)?,
+                        });
+                    }
+                }
+                b"STRL" => self.strings = read_string_list(body, false)?,
+                b"STRE" => self.strings = read_string_list(body, true)?,
+                b"MINI" => {
+                    let first = read_u32(body, 0)?;
+                    for (i, value) in body[4..].chunks_exact(4).enumerate() {
+                        let var = vars.entry(first + i as u32).or_default();
+                        var.initial = i32::from_le_bytes(value.try_into().unwrap());
+                    }
+                }
+                b"ARAY" => {
+                    for entry in body.chunks_exact(8) {
+                        let var = vars.entry(read_u32(entry, 0)?).or_default();
+                        var.array_size = Some(read_u32(entry, 4)?);
+                    }
+                }
+                b"MEXP" => {
+                    for (index, name) in read_name_table(body)?.into_iter().enumerate() {
+                        vars.entry(index as u32).or_default().name = Some(name);
+                    }
+                }
+                b"MIMP" => {
+                    let mut pos = 0;
+                    while pos + 4 < body.len() {
+                        let index = read_u32(body, pos)?;
+                        let name = read_cstring(body, pos + 4)?;
+                        pos += 4 + name.len() + 1;
+                        self.imported_vars.push((index, name));
+                    }
+                }
+                b"LOAD" => {
+                    self.libraries = body
+                        .split(|&b| b == 0)
+                        .filter(|name| !name.is_empty())
+                        .map(|name| String::from_utf8_lossy(name).to_string())
+                        .collect();
+                }
+                _ => {}
+            }
+        }
+
+        for script in &mut self.scripts {
+            if script.number < 0 {
+                script.name = script_names.get((-1 - script.number) as usize).cloned();
+            }
+            script.flags = flags.iter().find(|(n, _)| *n as i16 as i32 == script.number).map_or(0, |f| f.1);
+            script.var_count = var_counts.iter().find(|(n, _)| *n as i16 as i32 == script.number).map(|v| v.1);
+        }
+        for (function, name) in self.functions.iter_mut().zip(function_names) {
+            function.name = Some(name);
+        }
+        self.map_vars = vars
+            .into_iter()
+            .map(|(index, var)| MapVariable { index, ..var })
+            .collect();
+        Ok(())
+    }
+
+    /// Disassembles the code at `address` up to the next script or function.
+    pub fn disassemble(&self, address: u32) -> Vec<Instruction> {
+        let start = address as usize;
+        let end = self
+            .code_addresses()
+            .into_iter()
+            .find(|&a| a > start)
+            .unwrap_or(self.code_end)
+            .min(self.code_end);
+        let mut reader = CodeReader {
+            data: &self.data[..end.max(start).min(self.data.len())],
+            pos: start,
+            compact: self.format == AcsFormat::Compact,
+        };
+
+        let mut instructions = Vec::new();
+        while reader.pos < reader.data.len() {
+            let at = reader.pos as u32;
+            match reader.instruction() {
+                Some((opcode, operands)) => {
+                    let comment = self.comment(opcode, &operands);
+                    instructions.push(Instruction { address: at, opcode, operands, comment });
+                }
+                None => {
+                    instructions.push(Instruction {
+                        address: at,
+                        opcode: u16::MAX,
+                        operands: Vec::new(),
+                        comment: Some("undecodable p-code; listing stops here".into()),
+                    });
+                    break;
+                }
+            }
+        }
+        instructions
+    }
+
+    /// A readable listing of every script and function.
+    pub fn listing(&self) -> String {
+        let mut out = format!(
+            "// {} object, {} scripts, {} functions, {} strings\n",
+            self.format.name(),
+            self.scripts.len(),
+            self.functions.len(),
+            self.strings.len()
+        );
+        for script in &self.scripts {
+            out.push('\n');
+            out.push_str(&self.script_header(script));
+            out.push('\n');
+            for instruction in self.disassemble(script.address) {
+                out.push_str(&format!("    {}\n", instruction));
+            }
+        }
+        for (index, function) in self.functions.iter().enumerate() {
+            out.push('\n');
+            out.push_str(&self.function_header(index, function));
+            out.push('\n');
+            if !function.is_imported() {
+                for instruction in self.disassemble(function.address) {
+                    out.push_str(&format!("    {}\n", instruction));
+                }
+            }
+        }
+        out
+    }
+
+    /// `script 1 OPEN (0 args, 2 vars)`.
+    pub fn script_header(&self, script: &AcsScript) -> String {
+        let mut header = format!("script {}", script.label());
+        let keyword = script.kind.keyword();
+        if !keyword.is_empty() {
+            header.push(' ');
+            header.push_str(&keyword);
+        }
+        if script.flags & 1 != 0 {
+            header.push_str(" NET");
+        }
+        if script.flags & 2 != 0 {
+            header.push_str(" CLIENTSIDE");
+        }
+        header.push_str(&format!(" ({} args", script.arg_count));
+        if let Some(vars) = script.var_count {
+            header.push_str(&format!(", {} vars", vars));
+        }
+        header.push(')');
+        header
+    }
+
+    /// `function 0 name (2 args, 1 locals, returns value)`.
+    pub fn function_header(&self, index: usize, function: &AcsFunction) -> String {
+        format!(
+            "function {} {} ({} args, {} locals{}{})",
+            index,
+            function.name.as_deref().unwrap_or("?"),
+            function.arg_count,
+            function.local_count,
+            if function.returns_value { ", returns value" } else { "" },
+            if function.is_imported() { format!(", imported from library {}", function.import) } else { String::new() },
+        )
+    }
+
+    /// Start offsets of all scripts and local functions, sorted.
+    fn code_addresses(&self) -> Vec<usize> {
+        let mut addresses: Vec<usize> = self
+            .scripts
+            .iter()
+            .map(|s| s.address as usize)
+            .chain(self.functions.iter().filter(|f| !f.is_imported()).map(|f| f.address as usize))
+            .collect();
+        addresses.sort_unstable();
+        addresses.dedup();
+        addresses
+    }
+
+    /// Names the target of calls to script functions. CALLFUNC numbers the
+    /// engine's built-in functions instead, so it is left unnamed.
+    fn comment(&self, opcode: u16, operands: &[i32]) -> Option<String> {
+        let mnemonic = opcode_info(opcode)?.0;
+        let function = match mnemonic {
+            "CALL" | "CALLDISCARD" | "PUSHFUNCTION" => operands.first(),
+            _ => None,
+        }?;
+        self.functions
+            .get(*function as usize)
+            .and_then(|f| f.name.clone())
+    }
+}
+
+/// One decoded p-code instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub address: u32,
+    pub opcode: u16,
+    pub operands: Vec<i32>,
+    pub comment: Option<String>,
+}
+
+impl Instruction {
+    pub fn mnemonic(&self) -> &'static str {
+        opcode_info(self.opcode).map_or("???", |info| info.0)
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:06}: {}", self.address, self.mnemonic())?;
+        for (i, operand) in self.operands.iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { " " } else { ", " }, operand)?;
+        }
+        if let Some(comment) = &self.comment {
+            write!(f, "  ; {}", comment)?;
+        }
+        Ok(())
+    }
+}
+
+/// How an opcode's operands are encoded.
+#[derive(Debug, Clone, Copy)]
+enum Args {
+    None,
+    /// 32-bit words.
+    Words(u8),
+    /// A variable, function or special index: one byte in compact code, else a word.
+    Index,
+    /// An index-sized action special followed by `n` words.
+    Special(u8),
+    /// A special byte followed by `n` byte arguments.
+    SpecialBytes(u8),
+    /// `n` byte arguments.
+    Bytes(u8),
+    /// A byte count followed by that many bytes.
+    ByteList,
+    /// A word-aligned table of (value, target) pairs.
+    SortedCases,
+    /// An index-sized argument count and a 16-bit (compact) or word function index.
+    CallFunc,
+}
+
+struct CodeReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    compact: bool,
+}
+
+impl CodeReader<'_> {
+    fn byte(&mut self) -> Option<i32> {
+        let b = *self.data.get(self.pos)?;
+        self.pos += 1;
+        Some(b as i32)
+    }
+
+    fn word(&mut self) -> Option<i32> {
+        let bytes = self.data.get(self.pos..self.pos + 4)?;
+        self.pos += 4;
+        Some(i32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn short(&mut self) -> Option<i32> {
+        let bytes = self.data.get(self.pos..self.pos + 2)?;
+        self.pos += 2;
+        Some(u16::from_le_bytes([bytes[0], bytes[1]]) as i32)
+    }
+
+    fn index(&mut self) -> Option<i32> {
+        if self.compact { self.byte() } else { self.word() }
+    }
+
+    fn opcode(&mut self) -> Option<u16> {
+        if !self.compact {
+            return self.word().map(|w| w as u16);
+        }
+        let first = self.byte()? as u16;
+        if first >= 240 {
+            Some(240 + ((first - 240) << 8) + self.byte()? as u16)
+        } else {
+            Some(first)
+        }
+    }
+
+    fn instruction(&mut self) -> Option<(u16, Vec<i32>)> {
+        let opcode = self.opcode()?;
+        let (_, args) = opcode_info(opcode)?;
+        let mut operands = Vec::new();
+        match args {
+            Args::None => {}
+            Args::Words(n) => {
+                for _ in 0..n {
+                    operands.push(self.word()?);
+                }
+            }
+            Args::Index => operands.push(self.index()?),
+            Args::Special(n) => {
+                operands.push(self.index()?);
+                for _ in 0..n {
+                    operands.push(self.word()?);
+                }
+            }
+            Args::SpecialBytes(n) => {
+                for _ in 0..=n {
+                    operands.push(self.byte()?);
+                }
+            }
+            Args::Bytes(n) => {
+                for _ in 0..n {
+                    operands.push(self.byte()?);
+                }
+            }
+            Args::ByteList => {
+                let count = self.byte()?;
+                for _ in 0..count {
+                    operands.push(self.byte()?);
+                }
+            }
+            Args::SortedCases => {
+                self.pos = (self.pos + 3) & !3;
+                let count = self.word()?.max(0) as usize;
+                // Each case is two words; more than the code holds is malformed.
+                if count > self.data.len().saturating_sub(self.pos) / 8 {
+                    return None;
+                }
+                for _ in 0..count * 2 {
+                    operands.push(self.word()?);
+                }
+            }
+            Args::CallFunc => {
+                operands.push(self.index()?);
+                operands.push(if self.compact { self.short()? } else { self.word()? });
+            }
+        }
+        Some((opcode, operands))
+    }
+}
+
+fn opcode_info(opcode: u16) -> Option<(&'static str, Args)> {
+    OPCODES.get(opcode as usize).copied()
+}
+
+use Args::{ByteList as L, Bytes as B, CallFunc as F, Index as I, None as N, SortedCases as C, Special as S, SpecialBytes as Sb, Words as W};
+
+/// Opcode names and operand layouts, indexed by opcode number.
+const OPCODES: &[(&str, Args)] = &[
+    ("NOP", N), ("TERMINATE", N), ("SUSPEND", N), ("PUSHNUMBER", W(1)),
+    ("LSPEC1", S(0)), ("LSPEC2", S(0)), ("LSPEC3", S(0)), ("LSPEC4", S(0)), ("LSPEC5", S(0)),
+    ("LSPEC1DIRECT", S(1)), ("LSPEC2DIRECT", S(2)), ("LSPEC3DIRECT", S(3)), ("LSPEC4DIRECT", S(4)), ("LSPEC5DIRECT", S(5)),
+    ("ADD", N), ("SUBTRACT", N), ("MULTIPLY", N), ("DIVIDE", N), ("MODULUS", N),
+    ("EQ", N), ("NE", N), ("LT", N), ("GT", N), ("LE", N), ("GE", N),
+    ("ASSIGNSCRIPTVAR", I), ("ASSIGNMAPVAR", I), ("ASSIGNWORLDVAR", I),
+    ("PUSHSCRIPTVAR", I), ("PUSHMAPVAR", I), ("PUSHWORLDVAR", I),
+    ("ADDSCRIPTVAR", I), ("ADDMAPVAR", I), ("ADDWORLDVAR", I),
+    ("SUBSCRIPTVAR", I), ("SUBMAPVAR", I), ("SUBWORLDVAR", I),
+    ("MULSCRIPTVAR", I), ("MULMAPVAR", I), ("MULWORLDVAR", I),
+    ("DIVSCRIPTVAR", I), ("DIVMAPVAR", I), ("DIVWORLDVAR", I),
+    ("MODSCRIPTVAR", I), ("MODMAPVAR", I), ("MODWORLDVAR", I),
+    ("INCSCRIPTVAR", I), ("INCMAPVAR", I), ("INCWORLDVAR", I),
+    ("DECSCRIPTVAR", I), ("DECMAPVAR", I), ("DECWORLDVAR", I),
+    ("GOTO", W(1)), ("IFGOTO", W(1)), ("DROP", N), ("DELAY", N), ("DELAYDIRECT", W(1)),
+    ("RANDOM", N), ("RANDOMDIRECT", W(2)), ("THINGCOUNT", N), ("THINGCOUNTDIRECT", W(2)),
+    ("TAGWAIT", N), ("TAGWAITDIRECT", W(1)), ("POLYWAIT", N), ("POLYWAITDIRECT", W(1)),
+    ("CHANGEFLOOR", N), ("CHANGEFLOORDIRECT", W(2)), ("CHANGECEILING", N), ("CHANGECEILINGDIRECT", W(2)),
+    ("RESTART", N), ("ANDLOGICAL", N), ("ORLOGICAL", N), ("ANDBITWISE", N), ("ORBITWISE", N),
+    ("EORBITWISE", N), ("NEGATELOGICAL", N), ("LSHIFT", N), ("RSHIFT", N), ("UNARYMINUS", N),
+    ("IFNOTGOTO", W(1)), ("LINESIDE", N), ("SCRIPTWAIT", N), ("SCRIPTWAITDIRECT", W(1)),
+    ("CLEARLINESPECIAL", N), ("CASEGOTO", W(2)), ("BEGINPRINT", N), ("ENDPRINT", N),
+    ("PRINTSTRING", N), ("PRINTNUMBER", N), ("PRINTCHARACTER", N), ("PLAYERCOUNT", N),
+    ("GAMETYPE", N), ("GAMESKILL", N), ("TIMER", N), ("SECTORSOUND", N), ("AMBIENTSOUND", N),
+    ("SOUNDSEQUENCE", N), ("SETLINETEXTURE", N), ("SETLINEBLOCKING", N), ("SETLINESPECIAL", N),
+    ("THINGSOUND", N), ("ENDPRINTBOLD", N),
+    // ZDoom extensions.
+    ("ACTIVATORSOUND", N), ("LOCALAMBIENTSOUND", N), ("SETLINEMONSTERBLOCKING", N),
+    ("PLAYERBLUESKULL", N), ("PLAYERREDSKULL", N), ("PLAYERYELLOWSKULL", N), ("PLAYERMASTERSKULL", N),
+    ("PLAYERBLUECARD", N), ("PLAYERREDCARD", N), ("PLAYERYELLOWCARD", N), ("PLAYERMASTERCARD", N),
+    ("PLAYERBLACKSKULL", N), ("PLAYERSILVERSKULL", N), ("PLAYERGOLDSKULL", N),
+    ("PLAYERBLACKCARD", N), ("PLAYERSILVERCARD", N), ("PLAYERONTEAM", N), ("PLAYERTEAM", N),
+    ("PLAYERHEALTH", N), ("PLAYERARMORPOINTS", N), ("PLAYERFRAGS", N), ("PLAYEREXPERT", N),
+    ("BLUETEAMCOUNT", N), ("REDTEAMCOUNT", N), ("BLUETEAMSCORE", N), ("REDTEAMSCORE", N),
+    ("ISONEFLAGCTF", N), ("LSPEC6", S(0)), ("LSPEC6DIRECT", S(6)), ("PRINTNAME", N),
+    ("MUSICCHANGE", N), ("CONSOLECOMMANDDIRECT", W(3)), ("CONSOLECOMMAND", N), ("SINGLEPLAYER", N),
+    ("FIXEDMUL", N), ("FIXEDDIV", N), ("SETGRAVITY", N), ("SETGRAVITYDIRECT", W(1)),
+    ("SETAIRCONTROL", N), ("SETAIRCONTROLDIRECT", W(1)), ("CLEARINVENTORY", N),
+    ("GIVEINVENTORY", N), ("GIVEINVENTORYDIRECT", W(2)), ("TAKEINVENTORY", N),
+    ("TAKEINVENTORYDIRECT", W(2)), ("CHECKINVENTORY", N), ("CHECKINVENTORYDIRECT", W(1)),
+    ("SPAWN", N), ("SPAWNDIRECT", W(6)), ("SPAWNSPOT", N), ("SPAWNSPOTDIRECT", W(4)),
+    ("SETMUSIC", N), ("SETMUSICDIRECT", W(3)), ("LOCALSETMUSIC", N), ("LOCALSETMUSICDIRECT", W(3)),
+    ("PRINTFIXED", N), ("PRINTLOCALIZED", N), ("MOREHUDMESSAGE", N), ("OPTHUDMESSAGE", N),
+    ("ENDHUDMESSAGE", N), ("ENDHUDMESSAGEBOLD", N), ("SETSTYLE", N), ("SETSTYLEDIRECT", W(1)),
+    ("SETFONT", N), ("SETFONTDIRECT", W(1)), ("PUSHBYTE", B(1)),
+    ("LSPEC1DIRECTB", Sb(1)), ("LSPEC2DIRECTB", Sb(2)), ("LSPEC3DIRECTB", Sb(3)),
+    ("LSPEC4DIRECTB", Sb(4)), ("LSPEC5DIRECTB", Sb(5)), ("DELAYDIRECTB", B(1)),
+    ("RANDOMDIRECTB", B(2)), ("PUSHBYTES", L), ("PUSH2BYTES", B(2)), ("PUSH3BYTES", B(3)),
+    ("PUSH4BYTES", B(4)), ("PUSH5BYTES", B(5)), ("SETTHINGSPECIAL", N),
+    ("ASSIGNGLOBALVAR", I), ("PUSHGLOBALVAR", I), ("ADDGLOBALVAR", I), ("SUBGLOBALVAR", I),
+    ("MULGLOBALVAR", I), ("DIVGLOBALVAR", I), ("MODGLOBALVAR", I), ("INCGLOBALVAR", I),
+    ("DECGLOBALVAR", I), ("FADETO", N), ("FADERANGE", N), ("CANCELFADE", N), ("PLAYMOVIE", N),
+    ("SETFLOORTRIGGER", N), ("SETCEILINGTRIGGER", N), ("GETACTORX", N), ("GETACTORY", N),
+    ("GETACTORZ", N), ("STARTTRANSLATION", N), ("TRANSLATIONRANGE1", N), ("TRANSLATIONRANGE2", N),
+    ("ENDTRANSLATION", N), ("CALL", I), ("CALLDISCARD", I), ("RETURNVOID", N), ("RETURNVAL", N),
+    ("PUSHMAPARRAY", I), ("ASSIGNMAPARRAY", I), ("ADDMAPARRAY", I), ("SUBMAPARRAY", I),
+    ("MULMAPARRAY", I), ("DIVMAPARRAY", I), ("MODMAPARRAY", I), ("INCMAPARRAY", I),
+    ("DECMAPARRAY", I), ("DUP", N), ("SWAP", N), ("WRITETOINI", N), ("GETFROMINI", N),
+    ("SIN", N), ("COS", N), ("VECTORANGLE", N), ("CHECKWEAPON", N), ("SETWEAPON", N),
+    ("TAGSTRING", N), ("PUSHWORLDARRAY", I), ("ASSIGNWORLDARRAY", I), ("ADDWORLDARRAY", I),
+    ("SUBWORLDARRAY", I), ("MULWORLDARRAY", I), ("DIVWORLDARRAY", I), ("MODWORLDARRAY", I),
+    ("INCWORLDARRAY", I), ("DECWORLDARRAY", I), ("PUSHGLOBALARRAY", I), ("ASSIGNGLOBALARRAY", I),
+    ("ADDGLOBALARRAY", I), ("SUBGLOBALARRAY", I), ("MULGLOBALARRAY", I), ("DIVGLOBALARRAY", I),
+    ("MODGLOBALARRAY", I), ("INCGLOBALARRAY", I), ("DECGLOBALARRAY", I), ("SETMARINEWEAPON", N),
+    ("SETACTORPROPERTY", N), ("GETACTORPROPERTY", N), ("PLAYERNUMBER", N), ("ACTIVATORTID", N),
+    ("SETMARINESPRITE", N), ("GETSCREENWIDTH", N), ("GETSCREENHEIGHT", N), ("THING_PROJECTILE2", N),
+    ("STRLEN", N), ("SETHUDSIZE", N), ("GETCVAR", N), ("CASEGOTOSORTED", C),
+    ("SETRESULTVALUE", N), ("GETLINEROWOFFSET", N), ("GETACTORFLOORZ", N), ("GETACTORANGLE", N),
+    ("GETSECTORFLOORZ", N), ("GETSECTORCEILINGZ", N), ("LSPEC5RESULT", S(0)), ("GETSIGILPIECES", N),
+    ("GETLEVELINFO", N), ("CHANGESKY", N), ("PLAYERINGAME", N), ("PLAYERISBOT", N),
+    ("SETCAMERATOTEXTURE", N), ("ENDLOG", N), ("GETAMMOCAPACITY", N), ("SETAMMOCAPACITY", N),
+    ("PRINTMAPCHARARRAY", N), ("PRINTWORLDCHARARRAY", N), ("PRINTGLOBALCHARARRAY", N),
+    ("SETACTORANGLE", N), ("GRABINPUT", N), ("SETMOUSEPOINTER", N), ("MOVEMOUSEPOINTER", N),
+    ("SPAWNPROJECTILE", N), ("GETSECTORLIGHTLEVEL", N), ("GETACTORCEILINGZ", N),
+    ("SETACTORPOSITION", N), ("CLEARACTORINVENTORY", N), ("GIVEACTORINVENTORY", N),
+    ("TAKEACTORINVENTORY", N), ("CHECKACTORINVENTORY", N), ("THINGCOUNTNAME", N),
+    ("SPAWNSPOTFACING", N), ("PLAYERCLASS", N),
+    ("ANDSCRIPTVAR", I), ("ANDMAPVAR", I), ("ANDWORLDVAR", I), ("ANDGLOBALVAR", I),
+    ("ANDMAPARRAY", I), ("ANDWORLDARRAY", I), ("ANDGLOBALARRAY", I),
+    ("EORSCRIPTVAR", I), ("EORMAPVAR", I), ("EORWORLDVAR", I), ("EORGLOBALVAR", I),
+    ("EORMAPARRAY", I), ("EORWORLDARRAY", I), ("EORGLOBALARRAY", I),
+    ("ORSCRIPTVAR", I), ("ORMAPVAR", I), ("ORWORLDVAR", I), ("ORGLOBALVAR", I),
+    ("ORMAPARRAY", I), ("ORWORLDARRAY", I), ("ORGLOBALARRAY", I),
+    ("LSSCRIPTVAR", I), ("LSMAPVAR", I), ("LSWORLDVAR", I), ("LSGLOBALVAR", I),
+    ("LSMAPARRAY", I), ("LSWORLDARRAY", I), ("LSGLOBALARRAY", I),
+    ("RSSCRIPTVAR", I), ("RSMAPVAR", I), ("RSWORLDVAR", I), ("RSGLOBALVAR", I),
+    ("RSMAPARRAY", I), ("RSWORLDARRAY", I), ("RSGLOBALARRAY", I),
+    ("GETPLAYERINFO", N), ("CHANGELEVEL", N), ("SECTORDAMAGE", N), ("REPLACETEXTURES", N),
+    ("NEGATEBINARY", N), ("GETACTORPITCH", N), ("SETACTORPITCH", N), ("PRINTBIND", N),
+    ("SETACTORSTATE", N), ("THINGDAMAGE2", N), ("USEINVENTORY", N), ("USEACTORINVENTORY", N),
+    ("CHECKACTORCEILINGTEXTURE", N), ("CHECKACTORFLOORTEXTURE", N), ("GETACTORLIGHTLEVEL", N),
+    ("SETMUGSHOTSTATE", N), ("THINGCOUNTSECTOR", N), ("THINGCOUNTNAMESECTOR", N),
+    ("CHECKPLAYERCAMERA", N), ("MORPHACTOR", N), ("UNMORPHACTOR", N), ("GETPLAYERINPUT", N),
+    ("CLASSIFYACTOR", N), ("PRINTBINARY", N), ("PRINTHEX", N), ("CALLFUNC", F),
+    ("SAVESTRING", N), ("PRINTMAPCHRANGE", N), ("PRINTWORLDCHRANGE", N), ("PRINTGLOBALCHRANGE", N),
+    ("STRCPYTOMAPCHRANGE", N), ("STRCPYTOWORLDCHRANGE", N), ("STRCPYTOGLOBALCHRANGE", N),
+    ("PUSHFUNCTION", I), ("CALLSTACK", N), ("SCRIPTWAITNAMED", N), ("TRANSLATIONRANGE3", N),
+    ("GOTOSTACK", N), ("ASSIGNSCRIPTARRAY", I), ("PUSHSCRIPTARRAY", I), ("ADDSCRIPTARRAY", I),
+    ("SUBSCRIPTARRAY", I), ("MULSCRIPTARRAY", I), ("DIVSCRIPTARRAY", I), ("MODSCRIPTARRAY", I),
+    ("INCSCRIPTARRAY", I), ("DECSCRIPTARRAY", I), ("ANDSCRIPTARRAY", I), ("EORSCRIPTARRAY", I),
+    ("ORSCRIPTARRAY", I), ("LSSCRIPTARRAY", I), ("RSSCRIPTARRAY", I), ("PRINTSCRIPTCHARARRAY", N),
+    ("PRINTSCRIPTCHRANGE", N), ("STRCPYTOSCRIPTCHRANGE", N), ("LSPEC5EX", W(1)),
+    ("LSPEC5EXRESULT", W(1)), ("TRANSLATIONRANGE4", N), ("TRANSLATIONRANGE5", N),
+];
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, AcsError> {
+    let bytes = data.get(offset..offset + 4).ok_or(AcsError::Truncated(offset))?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_cstring(data: &[u8], offset: usize) -> Result<String, AcsError> {
+    let rest = data.get(offset..).ok_or(AcsError::Truncated(offset))?;
+    let end = rest.iter().position(|&b| b == 0).ok_or(AcsError::Truncated(data.len()))?;
+    Ok(String::from_utf8_lossy(&rest[..end]).to_string())
+}
+
+fn u16_pairs(body: &[u8]) -> impl Iterator<Item = (u16, u16)> + '_ {
+    body.chunks_exact(4)
+        .map(|c| (u16::from_le_bytes([c[0], c[1]]), u16::from_le_bytes([c[2], c[3]])))
+}
+
+/// FNAM, SNAM and MEXP: a count, then offsets (from the chunk start) of
+/// NUL-terminated names.
+fn read_name_table(body: &[u8]) -> Result<Vec<String>, AcsError> {
+    let count = read_u32(body, 0)? as usize;
+    (0..count)
+        .map(|i| read_cstring(body, read_u32(body, 4 + i * 4)? as usize))
+        .collect()
+}
+
+/// STRL and STRE: a reserved word, a count, another reserved word, then string
+/// offsets from the chunk start. STRE strings are XORed with a key derived
+/// from their offset.
+fn read_string_list(body: &[u8], encrypted: bool) -> Result<Vec<String>, AcsError> {
+    let count = read_u32(body, 4)? as usize;
+    (0..count)
+        .map(|i| {
+            let offset = read_u32(body, 12 + i * 4)? as usize;
+            if !encrypted {
+                return read_cstring(body, offset);
+            }
+            let key = (offset as u32).wrapping_mul(157135);
+            let mut bytes = Vec::new();
+            for (j, &b) in body.get(offset..).ok_or(AcsError::Truncated(offset))?.iter().enumerate() {
+                let c = b ^ key.wrapping_add(j as u32 / 2) as u8;
+                if c == 0 {
+                    return Ok(String::from_utf8_lossy(&bytes).to_string());
+                }
+                bytes.push(c);
+            }
+            Err(AcsError::Truncated(body.len()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(values: &[i32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_opcode_table() {
+        assert_eq!(OPCODES.len(), 385);
+        assert_eq!(opcode_info(167).unwrap().0, "PUSHBYTE");
+        assert_eq!(opcode_info(203).unwrap().0, "CALL");
+        assert_eq!(opcode_info(256).unwrap().0, "CASEGOTOSORTED");
+        assert_eq!(opcode_info(351).unwrap().0, "CALLFUNC");
+        assert_eq!(opcode_info(364).unwrap().0, "ASSIGNSCRIPTARRAY");
+    }
+
+    #[test]
+    fn test_parse_acs0() {
+        // script 2 ENTER (1 arg): PUSHNUMBER 7; DELAY; TERMINATE
+        let mut data = b"ACS\0".to_vec();
+        let code = words(&[3, 7, 55, 1]);
+        let dir = 8 + code.len() + 6;
+        data.extend(words(&[dir as i32]));
+        data.extend(&code);
+        data.extend(b"hello\0");
+        data.extend(words(&[1, 4002, 8, 1, 1, 8 + code.len() as i32]));
+
+        let lump = BehaviorLump::parse(&data).unwrap();
+        assert_eq!(lump.format, AcsFormat::Acs0);
+        assert_eq!(lump.strings, vec!["hello"]);
+        assert_eq!(lump.scripts.len(), 1);
+        assert_eq!(lump.script_header(&lump.scripts[0]), "script 2 ENTER (1 args)");
+
+        let listing: Vec<String> = lump.disassemble(8).iter().map(|i| i.to_string()).collect();
+        assert_eq!(listing, vec!["000008: PUSHNUMBER 7", "000016: DELAY", "000020: TERMINATE"]);
+    }
+
+    #[test]
+    fn test_sorted_cases_bad_count() {
+        // CASEGOTOSORTED claiming far more cases than the lump holds.
+        let mut data = b"ACS\0".to_vec();
+        let code = words(&[256, i32::MAX, 1, 8]);
+        data.extend(words(&[8 + code.len() as i32]));
+        data.extend(&code);
+        data.extend(words(&[1, 1, 8, 0, 0]));
+
+        let lump = BehaviorLump::parse(&data).unwrap();
+        let listing = lump.disassemble(8);
+        assert_eq!(listing.len(), 1);
+        assert_eq!(listing[0].opcode, u16::MAX);
+    }
+
+    #[test]
+    fn test_parse_compact_chunks() {
+        // script 1 OPEN: PUSHBYTE 5; CALLDISCARD 0; TERMINATE, then function 0: RETURNVOID.
+        let code = vec![167, 5, 204, 0, 1, 205];
+        let mut data = b"ACSe".to_vec();
+        data.extend(words(&[8 + code.len() as i32]));
+        data.extend(&code);
+
+        let mut chunk = |id: &[u8; 4], body: Vec<u8>| {
+            data.extend(id);
+            data.extend(words(&[body.len() as i32]));
+            data.extend(body);
+        };
+        chunk(b"SPTR", [1u16.to_le_bytes(), 1u16.to_le_bytes()].concat().into_iter().chain(words(&[8, 0])).collect());
+        chunk(b"FUNC", vec![0, 0, 0, 0, 13, 0, 0, 0]);
+        chunk(b"FNAM", [words(&[1, 8]), b"fade\0".to_vec()].concat());
+        chunk(b"STRL", [words(&[0, 1, 0, 16]), b"hi\0".to_vec()].concat());
+        chunk(b"MINI", words(&[0, 42]));
+
+        let lump = BehaviorLump::parse(&data).unwrap();
+        assert_eq!(lump.format, AcsFormat::Compact);
+        assert_eq!(lump.strings, vec!["hi"]);
+        assert_eq!(lump.functions[0].name.as_deref(), Some("fade"));
+        assert_eq!(lump.map_vars, vec![MapVariable { index: 0, initial: 42, ..Default::default() }]);
+        assert_eq!(lump.script_header(&lump.scripts[0]), "script 1 OPEN (0 args)");
+
+        let listing: Vec<String> = lump.disassemble(8).iter().map(|i| i.to_string()).collect();
+        assert_eq!(listing, vec!["000008: PUSHBYTE 5", "000010: CALLDISCARD 0  ; fade", "000012: TERMINATE"]);
+        assert_eq!(lump.disassemble(13)[0].mnemonic(), "RETURNVOID");
+    }
+}
@@ -0,0 +1,720 @@
+// src/document/dehconsts.rs
+
+//! DeHackEd and BEX patches, and the thing catalog they modify.
+//!
+//! The catalog starts from the stock Doom II thing table. A patch can give a
+//! thing a new editor number, size or spawn frame, point frames at other
+//! sprites, and rename sprites through `Text` blocks or a BEX `[SPRITES]`
+//! section; the editor only needs what decides how a thing looks on the map,
+//! so weapons, ammo, sounds and code pointers are read past.
+
+use std::collections::{BTreeMap, HashMap};
+
+/// Sprite names in the engine's order; `Frame` blocks refer to them by index.
+pub const SPRITE_NAMES: [&str; 138] = [
+    "TROO", "SHTG", "PUNG", "PISG", "PISF", "SHTF", "SHT2", "CHGG", "CHGF", "MISG",
+    "MISF", "SAWG", "PLSG", "PLSF", "BFGG", "BFGF", "BLUD", "PUFF", "BAL1", "BAL2",
+    "PLSS", "PLSE", "MISL", "BFS1", "BFE1", "BFE2", "TFOG", "IFOG", "PLAY", "POSS",
+    "SPOS", "VILE", "FIRE", "FATB", "FBXP", "SKEL", "MANF", "FATT", "CPOS", "SARG",
+    "HEAD", "BAL7", "BOSS", "BOS2", "SKUL", "SPID", "BSPI", "APLS", "APBX", "CYBR",
+    "PAIN", "SSWV", "KEEN", "BBRN", "BOSF", "ARM1", "ARM2", "BAR1", "BEXP", "FCAN",
+    "BON1", "BON2", "BKEY", "RKEY", "YKEY", "BSKU", "RSKU", "YSKU", "STIM", "MEDI",
+    "SOUL", "PINV", "PSTR", "PINS", "MEGA", "SUIT", "PMAP", "PVIS", "CLIP", "AMMO",
+    "ROCK", "BROK", "CELL", "CELP", "SHEL", "SBOX", "BPAK", "BFUG", "MGUN", "CSAW",
+    "LAUN", "PLAS", "SHOT", "SGN2", "COLU", "SMT2", "GOR1", "POL2", "POL5", "POL4",
+    "POL3", "POL1", "POL6", "GOR2", "GOR3", "GOR4", "GOR5", "SMIT", "COL1", "COL2",
+    "COL3", "COL4", "CAND", "CBRA", "COL6", "TRE1", "TRE2", "ELEC", "CEYE", "FSKU",
+    "COL5", "TBLU", "TGRN", "TRED", "SMBT", "SMGT", "SMRT", "HDB1", "HDB2", "HDB3",
+    "HDB4", "HDB5", "HDB6", "POB1", "POB2", "BRS1", "TLMP", "TLP2",
+];
+
+/// The stock thing table in engine order (`Thing 1` is the player):
+/// name, editor number (-1 if it cannot be placed), radius, height and the
+/// sprite of its spawn frame (empty for things that are never drawn).
+const STOCK_THINGS: [(&str, i32, i32, i32, &str); 137] = [
+    ("Player", -1, 16, 56, "PLAY"),
+    ("Zombieman", 3004, 20, 56, "POSS"),
+    ("Shotgun Guy", 9, 20, 56, "SPOS"),
+    ("Arch-vile", 64, 20, 56, "VILE"),
+    ("Arch-vile Fire", -1, 20, 16, "FIRE"),
+    ("Revenant", 66, 20, 56, "SKEL"),
+    ("Revenant Fireball", -1, 11, 8, "FATB"),
+    ("Fireball Trail", -1, 20, 16, "PUFF"),
+    ("Mancubus", 67, 48, 64, "FATT"),
+    ("Mancubus Fireball", -1, 6, 8, "MANF"),
+    ("Chaingunner", 65, 20, 56, "CPOS"),
+    ("Imp", 3001, 20, 56, "TROO"),
+    ("Demon", 3002, 30, 56, "SARG"),
+    ("Spectre", 58, 30, 56, "SARG"),
+    ("Cacodemon", 3005, 31, 56, "HEAD"),
+    ("Baron of Hell", 3003, 24, 64, "BOSS"),
+    ("Baron Fireball", -1, 6, 16, "BAL7"),
+    ("Hell Knight", 69, 24, 64, "BOS2"),
+    ("Lost Soul", 3006, 16, 56, "SKUL"),
+    ("Spider Mastermind", 7, 128, 100, "SPID"),
+    ("Arachnotron", 68, 64, 64, "BSPI"),
+    ("Cyberdemon", 16, 40, 110, "CYBR"),
+    ("Pain Elemental", 71, 31, 56, "PAIN"),
+    ("Wolfenstein SS", 84, 20, 56, "SSWV"),
+    ("Commander Keen", 72, 16, 72, "KEEN"),
+    ("Boss Brain", 88, 16, 16, "BBRN"),
+    ("Monster Spawner", 89, 20, 32, ""),
+    ("Spawn Spot", 87, 20, 32, ""),
+    ("Spawn Cube", -1, 6, 32, "BOSF"),
+    ("Spawn Fire", -1, 20, 16, "FIRE"),
+    ("Barrel", 2035, 10, 42, "BAR1"),
+    ("Imp Fireball", -1, 6, 8, "BAL1"),
+    ("Cacodemon Fireball", -1, 6, 8, "BAL2"),
+    ("Rocket (projectile)", -1, 11, 8, "MISL"),
+    ("Plasma Bullet", -1, 13, 8, "PLSS"),
+    ("BFG Shot", -1, 13, 8, "BFS1"),
+    ("Arachnotron Plasma", -1, 13, 8, "APLS"),
+    ("Bullet Puff", -1, 20, 16, "PUFF"),
+    ("Blood", -1, 20, 16, "BLUD"),
+    ("Teleport Fog", -1, 20, 16, "TFOG"),
+    ("Item Fog", -1, 20, 16, "IFOG"),
+    ("Teleport Destination", 14, 20, 16, ""),
+    ("BFG Explosion", -1, 20, 16, "BFE2"),
+    ("Green Armor", 2018, 20, 16, "ARM1"),
+    ("Blue Armor", 2019, 20, 16, "ARM2"),
+    ("Health Bonus", 2014, 20, 16, "BON1"),
+    ("Armor Bonus", 2015, 20, 16, "BON2"),
+    ("Blue Keycard", 5, 20, 16, "BKEY"),
+    ("Red Keycard", 13, 20, 16, "RKEY"),
+    ("Yellow Keycard", 6, 20, 16, "YKEY"),
+    ("Yellow Skull Key", 39, 20, 16, "YSKU"),
+    ("Red Skull Key", 38, 20, 16, "RSKU"),
+    ("Blue Skull Key", 40, 20, 16, "BSKU"),
+    ("Stimpack", 2011, 20, 16, "STIM"),
+    ("Medikit", 2012, 20, 16, "MEDI"),
+    ("Soulsphere", 2013, 20, 16, "SOUL"),
+    ("Invulnerability", 2022, 20, 16, "PINV"),
+    ("Berserk", 2023, 20, 16, "PSTR"),
+    ("Partial Invisibility", 2024, 20, 16, "PINS"),
+    ("Radiation Suit", 2025, 20, 16, "SUIT"),
+    ("Computer Map", 2026, 20, 16, "PMAP"),
+    ("Light Amplification Visor", 2045, 20, 16, "PVIS"),
+    ("Megasphere", 83, 20, 16, "MEGA"),
+    ("Clip", 2007, 20, 16, "CLIP"),
+    ("Box of Bullets", 2048, 20, 16, "AMMO"),
+    ("Rocket", 2010, 20, 16, "ROCK"),
+    ("Box of Rockets", 2046, 20, 16, "BROK"),
+    ("Cell Charge", 2047, 20, 16, "CELL"),
+    ("Cell Pack", 17, 20, 16, "CELP"),
+    ("Shotgun Shells", 2008, 20, 16, "SHEL"),
+    ("Box of Shells", 2049, 20, 16, "SBOX"),
+    ("Backpack", 8, 20, 16, "BPAK"),
+    ("BFG9000", 2006, 20, 16, "BFUG"),
+    ("Chaingun", 2002, 20, 16, "MGUN"),
+    ("Chainsaw", 2005, 20, 16, "CSAW"),
+    ("Rocket Launcher", 2003, 20, 16, "LAUN"),
+    ("Plasma Rifle", 2004, 20, 16, "PLAS"),
+    ("Shotgun", 2001, 20, 16, "SHOT"),
+    ("Super Shotgun", 82, 20, 16, "SGN2"),
+    ("Tall Techno Lamp", 85, 16, 16, "TLMP"),
+    ("Short Techno Lamp", 86, 16, 16, "TLP2"),
+    ("Floor Lamp", 2028, 16, 16, "COLU"),
+    ("Tall Green Pillar", 30, 16, 16, "COL1"),
+    ("Short Green Pillar", 31, 16, 16, "COL2"),
+    ("Tall Red Pillar", 32, 16, 16, "COL3"),
+    ("Short Red Pillar", 33, 16, 16, "COL4"),
+    ("Short Red Pillar with Skull", 37, 16, 16, "COL6"),
+    ("Short Green Pillar with Heart", 36, 16, 16, "COL5"),
+    ("Evil Eye", 41, 16, 16, "CEYE"),
+    ("Floating Skull", 42, 16, 16, "FSKU"),
+    ("Burnt Tree", 43, 16, 16, "TRE1"),
+    ("Tall Blue Torch", 44, 16, 16, "TBLU"),
+    ("Tall Green Torch", 45, 16, 16, "TGRN"),
+    ("Tall Red Torch", 46, 16, 16, "TRED"),
+    ("Short Blue Torch", 55, 16, 16, "SMBT"),
+    ("Short Green Torch", 56, 16, 16, "SMGT"),
+    ("Short Red Torch", 57, 16, 16, "SMRT"),
+    ("Stalagmite", 47, 16, 16, "SMIT"),
+    ("Tall Techno Column", 48, 16, 16, "ELEC"),
+    ("Candle", 34, 20, 16, "CAND"),
+    ("Candelabra", 35, 16, 16, "CBRA"),
+    ("Hanging Victim, Twitching", 49, 16, 68, "GOR1"),
+    ("Hanging Victim, Arms Out", 50, 16, 84, "GOR2"),
+    ("Hanging Victim, One-legged", 51, 16, 84, "GOR3"),
+    ("Hanging Pair of Legs", 52, 16, 68, "GOR4"),
+    ("Hanging Leg", 53, 16, 52, "GOR5"),
+    ("Hanging Victim, Arms Out (no block)", 59, 20, 84, "GOR2"),
+    ("Hanging Pair of Legs (no block)", 60, 20, 68, "GOR4"),
+    ("Hanging Victim, One-legged (no block)", 61, 20, 52, "GOR3"),
+    ("Hanging Leg (no block)", 62, 20, 52, "GOR5"),
+    ("Hanging Victim, Twitching (no block)", 63, 20, 68, "GOR1"),
+    ("Dead Cacodemon", 22, 20, 16, "HEAD"),
+    ("Dead Player", 15, 20, 16, "PLAY"),
+    ("Dead Zombieman", 18, 20, 16, "POSS"),
+    ("Dead Demon", 21, 20, 16, "SARG"),
+    ("Dead Lost Soul", 23, 20, 16, "SKUL"),
+    ("Dead Imp", 20, 20, 16, "TROO"),
+    ("Dead Shotgun Guy", 19, 20, 16, "SPOS"),
+    ("Bloody Mess", 10, 20, 16, "PLAY"),
+    ("Bloody Mess 2", 12, 20, 16, "PLAY"),
+    ("Five Skulls Shish Kebab", 28, 16, 16, "POL2"),
+    ("Pool of Blood and Bones", 24, 20, 16, "POL5"),
+    ("Skull on a Pole", 27, 16, 16, "POL4"),
+    ("Pile of Skulls and Candles", 29, 16, 16, "POL3"),
+    ("Impaled Human", 25, 16, 16, "POL1"),
+    ("Twitching Impaled Human", 26, 16, 16, "POL6"),
+    ("Big Tree", 54, 32, 16, "TRE2"),
+    ("Burning Barrel", 70, 16, 16, "FCAN"),
+    ("Hanging Victim, Guts Removed", 73, 16, 88, "HDB1"),
+    ("Hanging Victim, Guts and Brain Removed", 74, 16, 88, "HDB2"),
+    ("Hanging Torso, Looking Down", 75, 16, 64, "HDB3"),
+    ("Hanging Torso, Open Skull", 76, 16, 64, "HDB4"),
+    ("Hanging Torso, Looking Up", 77, 16, 64, "HDB5"),
+    ("Hanging Torso, Brain Removed", 78, 16, 64, "HDB6"),
+    ("Pool of Blood", 79, 20, 16, "POB1"),
+    ("Pool of Blood 2", 80, 20, 16, "POB2"),
+    ("Pool of Brains", 81, 20, 16, "BRS1"),
+];
+
+/// Map-only things that have no entry in the table; the engine spawns the
+/// player (`Thing 1`) at them.
+const START_SPOTS: [(&str, i32); 5] = [
+    ("Player 1 Start", 1),
+    ("Player 2 Start", 2),
+    ("Player 3 Start", 3),
+    ("Player 4 Start", 4),
+    ("Deathmatch Start", 11),
+];
+
+/// The sprite of each stock frame (state), as an index into the sprite
+/// names. Things whose spawn frame a patch points at a stock frame take
+/// their sprite from here.
+const STOCK_FRAME_SPRITES: [u8; 967] = [
+    0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 3, 4, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 5, 5, 6, 6, 6, 6, 6, 6, 6, 6,
+    6, 6, 6, 6, 6, 6, 6, 6, 6, 7, 7, 7, 7, 7, 7, 8, 8, 9, 9, 9,
+    9, 9, 9, 10, 10, 10, 10, 11, 11, 11, 11, 11, 11, 11, 12, 12, 12, 12, 12, 13,
+    13, 14, 14, 14, 14, 14, 14, 14, 15, 15, 16, 16, 16, 17, 17, 17, 17, 18, 18, 18,
+    18, 18, 19, 19, 19, 19, 19, 20, 20, 21, 21, 21, 21, 21, 22, 23, 23, 24, 24, 24,
+    24, 24, 24, 25, 25, 25, 25, 22, 22, 22, 26, 26, 26, 26, 26, 26, 26, 26, 26, 26,
+    26, 26, 27, 27, 27, 27, 27, 27, 27, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28,
+    28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 29, 29, 29, 29, 29, 29,
+    29, 29, 29, 29, 29, 29, 29, 29, 29, 29, 29, 29, 29, 29, 29, 29, 29, 29, 29, 29,
+    29, 29, 29, 29, 29, 29, 29, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    30, 31, 31, 31, 31, 31, 31, 31, 31, 31, 31, 31, 31, 31, 31, 31, 31, 31, 31, 31,
+    31, 31, 31, 31, 31, 31, 31, 31, 31, 31, 31, 31, 31, 31, 31, 31, 31, 31, 31, 31,
+    31, 32, 32, 32, 32, 32, 32, 32, 32, 32, 32, 32, 32, 32, 32, 32, 32, 32, 32, 32,
+    32, 32, 32, 32, 32, 32, 32, 32, 32, 32, 32, 17, 17, 17, 17, 17, 33, 33, 34, 34,
+    34, 35, 35, 35, 35, 35, 35, 35, 35, 35, 35, 35, 35, 35, 35, 35, 35, 35, 35, 35,
+    35, 35, 35, 35, 35, 35, 35, 35, 35, 35, 35, 35, 35, 35, 35, 35, 35, 36, 36, 22,
+    22, 22, 37, 37, 37, 37, 37, 37, 37, 37, 37, 37, 37, 37, 37, 37, 37, 37, 37, 37,
+    37, 37, 37, 37, 37, 37, 37, 37, 37, 37, 37, 37, 37, 37, 37, 37, 37, 37, 37, 37,
+    37, 37, 37, 37, 37, 37, 38, 38, 38, 38, 38, 38, 38, 38, 38, 38, 38, 38, 38, 38,
+    38, 38, 38, 38, 38, 38, 38, 38, 38, 38, 38, 38, 38, 38, 38, 38, 38, 38, 38, 38,
+    38, 38, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 39, 39, 39, 39, 39,
+    39, 39, 39, 39, 39, 39, 39, 39, 39, 39, 39, 39, 39, 39, 39, 39, 39, 39, 39, 39,
+    39, 39, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40, 40,
+    40, 40, 41, 41, 41, 41, 41, 42, 42, 42, 42, 42, 42, 42, 42, 42, 42, 42, 42, 42,
+    42, 42, 42, 42, 42, 42, 42, 42, 42, 42, 42, 42, 42, 42, 42, 42, 43, 43, 43, 43,
+    43, 43, 43, 43, 43, 43, 43, 43, 43, 43, 43, 43, 43, 43, 43, 43, 43, 43, 43, 43,
+    43, 43, 43, 43, 43, 44, 44, 44, 44, 44, 44, 44, 44, 44, 44, 44, 44, 44, 44, 44,
+    44, 45, 45, 45, 45, 45, 45, 45, 45, 45, 45, 45, 45, 45, 45, 45, 45, 45, 45, 45,
+    45, 45, 45, 45, 45, 45, 45, 45, 45, 45, 45, 45, 46, 46, 46, 46, 46, 46, 46, 46,
+    46, 46, 46, 46, 46, 46, 46, 46, 46, 46, 46, 46, 46, 46, 46, 46, 46, 46, 46, 46,
+    46, 46, 46, 46, 46, 46, 46, 47, 47, 48, 48, 48, 48, 48, 49, 49, 49, 49, 49, 49,
+    49, 49, 49, 49, 49, 49, 49, 49, 49, 49, 49, 49, 49, 49, 49, 49, 49, 49, 49, 49,
+    49, 50, 50, 50, 50, 50, 50, 50, 50, 50, 50, 50, 50, 50, 50, 50, 50, 50, 50, 50,
+    50, 50, 50, 50, 50, 50, 51, 51, 51, 51, 51, 51, 51, 51, 51, 51, 51, 51, 51, 51,
+    51, 51, 51, 51, 51, 51, 51, 51, 51, 51, 51, 51, 51, 51, 51, 51, 51, 51, 51, 51,
+    51, 51, 51, 52, 52, 52, 52, 52, 52, 52, 52, 52, 52, 52, 52, 52, 52, 52, 53, 53,
+    53, 53, 53, 53, 51, 51, 51, 54, 54, 54, 54, 32, 32, 32, 32, 32, 32, 32, 32, 22,
+    22, 22, 55, 55, 56, 56, 57, 57, 58, 58, 58, 58, 58, 59, 59, 59, 60, 60, 60, 60,
+    60, 60, 61, 61, 61, 61, 61, 61, 62, 62, 63, 63, 64, 64, 65, 65, 66, 66, 67, 67,
+    68, 69, 70, 70, 70, 70, 70, 70, 71, 71, 71, 71, 72, 73, 73, 73, 73, 74, 74, 74,
+    74, 75, 76, 76, 76, 76, 76, 76, 77, 77, 78, 79, 80, 81, 82, 83, 84, 85, 86, 87,
+    88, 89, 90, 91, 92, 93, 94, 95, 96, 96, 96, 96, 28, 28, 97, 98, 99, 100, 100, 101,
+    102, 102, 103, 104, 105, 106, 107, 108, 109, 110, 111, 112, 113, 114, 115, 116, 117, 118, 118, 118,
+    118, 119, 119, 119, 120, 120, 121, 121, 121, 121, 122, 122, 122, 122, 123, 123, 123, 123, 124, 124,
+    124, 124, 125, 125, 125, 125, 126, 126, 126, 126, 127, 128, 129, 130, 131, 132, 133, 134, 135, 136,
+    136, 136, 136, 137, 137, 137, 137,
+];
+
+/// Changes to one `Thing` block.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ThingPatch {
+    /// The name in the block header, as in `Thing 12 (Nightmare Imp)`.
+    pub name: Option<String>,
+    /// `ID #`: the editor number placed in maps.
+    pub editor_number: Option<i32>,
+    /// `Width`, in 16.16 fixed point; this is the radius.
+    pub width: Option<i32>,
+    /// `Height`, in 16.16 fixed point.
+    pub height: Option<i32>,
+    pub initial_frame: Option<usize>,
+    /// Every other field, by lowercase key.
+    pub fields: BTreeMap<String, String>,
+}
+
+/// Changes to one `Frame` block.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FramePatch {
+    /// Index into the sprite names.
+    pub sprite_number: Option<usize>,
+    pub sprite_subnumber: Option<i32>,
+    /// Every other field, by lowercase key.
+    pub fields: BTreeMap<String, String>,
+}
+
+/// A parsed DeHackEd (`.deh`) or BEX (`.bex`) patch.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DehPatch {
+    pub doom_version: Option<i32>,
+    pub patch_format: Option<i32>,
+    /// `Thing` blocks by their 1-based table number.
+    pub things: BTreeMap<usize, ThingPatch>,
+    /// `Frame` blocks by frame number.
+    pub frames: BTreeMap<usize, FramePatch>,
+    /// `Text` blocks as (old text, new text).
+    pub texts: Vec<(String, String)>,
+    /// BEX `[STRINGS]`, by mnemonic.
+    pub strings: BTreeMap<String, String>,
+    /// BEX `[SPRITES]` lines: a sprite number or an old name, and the new name.
+    pub sprites: Vec<(String, String)>,
+    /// Lines that were not understood.
+    pub warnings: Vec<String>,
+}
+
+/// The block or section the parser is in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+    /// Before the first block: `Doom version`, `Patch format`.
+    Header,
+    Thing(usize),
+    Frame(usize),
+    Strings,
+    Sprites,
+    /// A block or section that does not affect things; its lines are skipped.
+    Ignored,
+}
+
+impl DehPatch {
+    /// Parses a patch. Never fails: lines that make no sense are recorded
+    /// in `warnings` and skipped, as the engines do.
+    pub fn parse(text: &str) -> Self {
+        let text = text.replace("\r\n", "\n");
+        let mut patch = DehPatch::default();
+        let mut block = Block::Header;
+        let mut rest = text.as_str();
+        let mut line_no = 0;
+
+        while !rest.is_empty() {
+            let (line, tail) = rest.split_once('\n').unwrap_or((rest, ""));
+            rest = tail;
+            line_no += 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(section) = line.strip_prefix('[') {
+                let section = section.trim_end_matches(']').trim().to_ascii_uppercase();
+                block = match section.as_str() {
+                    "STRINGS" => Block::Strings,
+                    "SPRITES" => Block::Sprites,
+                    _ => Block::Ignored,
+                };
+                continue;
+            }
+
+            if let Some((key, value)) = line.split_once('=') {
+                let mut value = value.trim().to_string();
+                if block == Block::Strings {
+                    // A trailing backslash continues the string on the next line.
+                    while value.ends_with('\\') && !rest.is_empty() {
+                        value.pop();
+                        let (next, tail) = rest.split_once('\n').unwrap_or((rest, ""));
+                        rest = tail;
+                        line_no += 1;
+                        value.push_str(next.trim());
+                    }
+                }
+                patch.set(block, key.trim(), &value, line_no);
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap_or("").to_ascii_lowercase();
+            let number = words.next().and_then(|w| w.parse::<usize>().ok());
+            match (keyword.as_str(), number) {
+                ("thing", Some(n)) => {
+                    block = Block::Thing(n);
+                    let name = line
+                        .split_once('(')
+                        .and_then(|(_, name)| name.rsplit_once(')'))
+                        .map(|(name, _)| name.trim().to_string())
+                        .filter(|name| !name.is_empty());
+                    let thing = patch.things.entry(n).or_default();
+                    if name.is_some() {
+                        thing.name = name;
+                    }
+                }
+                ("frame", Some(n)) => {
+                    block = Block::Frame(n);
+                    patch.frames.entry(n).or_default();
+                }
+                ("text", Some(old_len)) => {
+                    block = Block::Ignored;
+                    let new_len = match words.next().and_then(|w| w.parse::<usize>().ok()) {
+                        Some(len) => len,
+                        None => {
+                            patch.warnings.push(format!("line {}: bad Text header {:?}", line_no, line));
+                            continue;
+                        }
+                    };
+                    // The texts follow the header verbatim, newlines included.
+                    let end = char_offset(rest, old_len + new_len);
+                    let body = &rest[..end];
+                    let split = char_offset(body, old_len);
+                    patch.texts.push((body[..split].to_string(), body[split..].to_string()));
+                    line_no += body.matches('\n').count();
+                    rest = &rest[end..];
+                    rest = rest.split_once('\n').map_or("", |(_, tail)| tail);
+                    line_no += 1;
+                }
+                ("patch", _) => {}
+                ("weapon" | "ammo" | "sound" | "pointer" | "cheat" | "misc" | "sprite" | "include", _) => {
+                    block = Block::Ignored;
+                }
+                _ if block == Block::Ignored => {}
+                _ => patch.warnings.push(format!("line {}: unrecognised {:?}", line_no, line)),
+            }
+        }
+        patch
+    }
+
+    /// Applies one `key = value` line to the current block.
+    fn set(&mut self, block: Block, key: &str, value: &str, line_no: usize) {
+        let lower = key.to_ascii_lowercase();
+        let int = || value.parse::<i64>().ok();
+        let ok = match block {
+            Block::Header => match lower.as_str() {
+                "doom version" => {
+                    self.doom_version = int().map(|v| v as i32);
+                    self.doom_version.is_some()
+                }
+                "patch format" => {
+                    self.patch_format = int().map(|v| v as i32);
+                    self.patch_format.is_some()
+                }
+                _ => false,
+            },
+            Block::Thing(n) => {
+                let thing = self.things.entry(n).or_default();
+                match lower.as_str() {
+                    "id #" => {
+                        thing.editor_number = int().map(|v| v as i32);
+                        thing.editor_number.is_some()
+                    }
+                    "width" => {
+                        thing.width = int().map(|v| v as i32);
+                        thing.width.is_some()
+                    }
+                    "height" => {
+                        thing.height = int().map(|v| v as i32);
+                        thing.height.is_some()
+                    }
+                    "initial frame" => {
+                        thing.initial_frame = int().and_then(|v| usize::try_from(v).ok());
+                        thing.initial_frame.is_some()
+                    }
+                    _ => {
+                        thing.fields.insert(lower, value.to_string());
+                        true
+                    }
+                }
+            }
+            Block::Frame(n) => {
+                let frame = self.frames.entry(n).or_default();
+                match lower.as_str() {
+                    "sprite number" => {
+                        frame.sprite_number = int().and_then(|v| usize::try_from(v).ok());
+                        frame.sprite_number.is_some()
+                    }
+                    "sprite subnumber" => {
+                        frame.sprite_subnumber = int().map(|v| v as i32);
+                        frame.sprite_subnumber.is_some()
+                    }
+                    _ => {
+                        frame.fields.insert(lower, value.to_string());
+                        true
+                    }
+                }
+            }
+            Block::Strings => {
+                self.strings.insert(key.to_ascii_uppercase(), value.to_string());
+                true
+            }
+            Block::Sprites => {
+                self.sprites.push((key.to_string(), value.to_string()));
+                true
+            }
+            Block::Ignored => true,
+        };
+        if !ok {
+            self.warnings.push(format!("line {}: bad value for {:?}: {:?}", line_no, key, value));
+        }
+    }
+}
+
+/// Byte offset of the `n`th character of `text`, or its length.
+fn char_offset(text: &str, n: usize) -> usize {
+    text.char_indices().nth(n).map_or(text.len(), |(i, _)| i)
+}
+
+/// How one kind of thing looks in the editor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThingDef {
+    /// 1-based number in the thing table, as used by `Thing` blocks; 0 for
+    /// the start spots, which are not in the table.
+    pub number: usize,
+    pub name: String,
+    /// The number placed in maps, or -1 if the thing cannot be placed.
+    pub editor_number: i32,
+    pub radius: i32,
+    pub height: i32,
+    /// Index into the sprite names; `None` for things that are never drawn.
+    pub sprite: Option<usize>,
+    /// The spawn frame, once a patch has set it.
+    pub spawn_frame: Option<usize>,
+}
+
+/// Every thing the editor knows about, with any DeHackEd changes applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThingCatalog {
+    things: Vec<ThingDef>,
+    sprite_names: Vec<String>,
+    /// Sprites of the frames patches have changed; other frames keep their
+    /// stock sprite.
+    frame_sprites: HashMap<usize, usize>,
+}
+
+impl Default for ThingCatalog {
+    fn default() -> Self {
+        Self::stock()
+    }
+}
+
+impl ThingCatalog {
+    /// The unmodified Doom II things.
+    pub fn stock() -> Self {
+        let sprite_index = |name: &str| SPRITE_NAMES.iter().position(|s| *s == name);
+        let player_sprite = sprite_index("PLAY");
+        let starts = START_SPOTS.iter().map(|&(name, editor_number)| ThingDef {
+            number: 0,
+            name: name.to_string(),
+            editor_number,
+            radius: 16,
+            height: 56,
+            sprite: player_sprite,
+            spawn_frame: None,
+        });
+        let table = STOCK_THINGS
+            .iter()
+            .enumerate()
+            .map(|(i, &(name, editor_number, radius, height, sprite))| ThingDef {
+                number: i + 1,
+                name: name.to_string(),
+                editor_number,
+                radius,
+                height,
+                sprite: sprite_index(sprite),
+                spawn_frame: None,
+            });
+        Self {
+            things: starts.chain(table).collect(),
+            sprite_names: SPRITE_NAMES.iter().map(|s| s.to_string()).collect(),
+            frame_sprites: HashMap::new(),
+        }
+    }
+
+    /// Applies a patch on top of the current definitions.
+    pub fn apply(&mut self, patch: &DehPatch) {
+        // Sprite renames. A `Text` block only counts when it replaces a
+        // sprite name; the rest are messages and level names.
+        for (old, new) in &patch.texts {
+            if old.len() == 4 && new.len() == 4 {
+                self.rename_sprite(old, new);
+            }
+        }
+        for (key, name) in &patch.sprites {
+            match key.parse::<usize>() {
+                Ok(index) => {
+                    if self.sprite_names.len() <= index {
+                        self.sprite_names.resize(index + 1, String::new());
+                    }
+                    self.sprite_names[index] = name.to_ascii_uppercase();
+                }
+                Err(_) => self.rename_sprite(key, name),
+            }
+        }
+
+        for (&frame, changes) in &patch.frames {
+            if let Some(sprite) = changes.sprite_number {
+                self.frame_sprites.insert(frame, sprite);
+            }
+        }
+
+        for (&number, changes) in &patch.things {
+            if number == 0 {
+                continue;
+            }
+            let def = self.def_mut(number);
+            if let Some(name) = &changes.name {
+                def.name = name.clone();
+            }
+            if let Some(editor_number) = changes.editor_number {
+                def.editor_number = editor_number;
+            }
+            if let Some(width) = changes.width {
+                def.radius = width >> 16;
+            }
+            if let Some(height) = changes.height {
+                def.height = height >> 16;
+            }
+            if let Some(frame) = changes.initial_frame {
+                def.spawn_frame = Some(frame);
+            }
+        }
+
+        // Frames may be patched after the thing that uses them.
+        for def in &mut self.things {
+            if let Some(sprite) = def.spawn_frame.and_then(|f| frame_sprite(&self.frame_sprites, f)) {
+                def.sprite = Some(sprite);
+            }
+        }
+
+    }
+
+    fn rename_sprite(&mut self, old: &str, new: &str) {
+        for name in &mut self.sprite_names {
+            if name.eq_ignore_ascii_case(old) {
+                *name = new.to_ascii_uppercase();
+            }
+        }
+    }
+
+    /// The definition for table entry `number`, adding blank entries for
+    /// patches that extend the table.
+    fn def_mut(&mut self, number: usize) -> &mut ThingDef {
+        let index = match self.things.iter().position(|d| d.number == number) {
+            Some(index) => index,
+            None => {
+                self.things.push(ThingDef {
+                    number,
+                    name: format!("Thing {}", number),
+                    editor_number: -1,
+                    radius: 20,
+                    height: 16,
+                    sprite: None,
+                    spawn_frame: None,
+                });
+                self.things.len() - 1
+            }
+        };
+        &mut self.things[index]
+    }
+
+    pub fn things(&self) -> &[ThingDef] {
+        &self.things
+    }
+
+    /// Things that can be placed in a map, in table order.
+    pub fn placeable(&self) -> impl Iterator<Item = &ThingDef> {
+        self.things.iter().filter(|d| d.editor_number > 0)
+    }
+
+    /// The definition used for editor number `editor_number`. Like the
+    /// engine, the first match in the table wins.
+    pub fn find(&self, editor_number: i32) -> Option<&ThingDef> {
+        self.things.iter().find(|d| d.editor_number == editor_number)
+    }
+
+    /// The (possibly renamed) sprite of `def`.
+    pub fn sprite_name(&self, def: &ThingDef) -> Option<&str> {
+        let name = self.sprite_names.get(def.sprite?)?;
+        (!name.is_empty()).then_some(name.as_str())
+    }
+}
+
+/// The sprite frame `frame` shows: as `patched` sets it, or as in the stock
+/// table.
+fn frame_sprite(patched: &HashMap<usize, usize>, frame: usize) -> Option<usize> {
+    patched
+        .get(&frame)
+        .copied()
+        .or_else(|| STOCK_FRAME_SPRITES.get(frame).map(|&sprite| sprite as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATCH: &str = "Patch File for DeHackEd v3.0\r\n\
+        # A custom monster replacing the imp\r\n\
+        Doom version = 21\r\n\
+        Patch format = 6\r\n\
+        \r\n\
+        Thing 12 (Nightmare Imp)\r\n\
+        ID # = 3010\r\n\
+        Width = 1572864\r\n\
+        Initial frame = 895\r\n\
+        Bits = SOLID+SHOOTABLE\r\n\
+        \r\n\
+        Frame 895\r\n\
+        Sprite number = 140\r\n\
+        \r\n\
+        Text 4 4\r\n\
+        TROOIMPX\r\n\
+        Text 5 6\r\n\
+        E1M1\nHangar\r\n\
+        \r\n\
+        [SPRITES]\r\n\
+        140 = NIMP\r\n\
+        \r\n\
+        [STRINGS]\r\n\
+        GOTARMOR = Picked up \\\r\n\
+          the armor.\r\n\
+        [CODEPTR]\r\n\
+        FRAME 895 = Look\r\n\
+        Bogus line\r\n";
+
+    #[test]
+    fn test_parse_and_apply_patch() {
+        let patch = DehPatch::parse(PATCH);
+        assert_eq!(patch.doom_version, Some(21));
+        assert_eq!(patch.patch_format, Some(6));
+        assert_eq!(patch.texts, vec![
+            ("TROO".to_string(), "IMPX".to_string()),
+            ("E1M1\n".to_string(), "Hangar".to_string()),
+        ]);
+        assert_eq!(patch.strings["GOTARMOR"], "Picked up the armor.");
+        assert_eq!(patch.things[&12].fields["bits"], "SOLID+SHOOTABLE");
+        assert!(patch.warnings.is_empty(), "{:?}", patch.warnings);
+
+        let mut catalog = ThingCatalog::stock();
+        catalog.apply(&patch);
+        assert!(catalog.find(3001).is_none());
+        let imp = catalog.find(3010).unwrap();
+        assert_eq!(imp.name, "Nightmare Imp");
+        assert_eq!((imp.radius, imp.height), (24, 56));
+        assert_eq!(catalog.sprite_name(imp), Some("NIMP"));
+
+        // The text rename reaches every thing drawn with the old sprite.
+        let dead_imp = catalog.find(20).unwrap();
+        assert_eq!(catalog.sprite_name(dead_imp), Some("IMPX"));
+        assert_eq!(catalog.find(1).unwrap().name, "Player 1 Start");
+        assert!(catalog.sprite_name(catalog.find(14).unwrap()).is_none());
+
+        // A thing moved to an unpatched stock frame shows that frame's sprite.
+        let mut catalog = ThingCatalog::stock();
+        catalog.apply(&DehPatch::parse("Thing 12 (Imp)\nInitial frame = 502\n"));
+        assert_eq!(catalog.sprite_name(catalog.find(3001).unwrap()), Some("HEAD"));
+    }
+}
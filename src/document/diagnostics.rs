@@ -0,0 +1,125 @@
+// src/document/diagnostics.rs
+
+//! Problems found while reading a WAD.
+//!
+//! In lenient mode the loader works around damaged data and records a
+//! [`WadWarning`] for each problem; in strict mode the first one aborts the
+//! load as [`WadError::Strict`].
+
+use std::fmt;
+use std::io;
+use thiserror::Error;
+
+/// How the loader treats recoverable problems.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoadMode {
+    /// Skip or truncate bad data and report it.
+    #[default]
+    Lenient,
+    /// Refuse to load anything that is not well formed.
+    Strict,
+}
+
+/// A problem that stops a WAD from loading.
+#[derive(Debug, Error)]
+pub enum WadError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("File is too short for a WAD header ({0} bytes)")]
+    TooShort(usize),
+
+    #[error("Invalid WAD identifier: {0}")]
+    BadIdent(String),
+
+    #[error("Directory offset {offset} with {count} entries lies outside the file ({file_size} bytes)")]
+    BadDirectory { offset: i32, count: i32, file_size: usize },
+
+    #[error("Level not found: {0}")]
+    LevelNotFound(String),
+
+    #[error("Strict mode: {0}")]
+    Strict(WadWarning),
+}
+
+impl From<WadError> for io::Error {
+    fn from(e: WadError) -> Self {
+        match e {
+            WadError::Io(e) => e,
+            WadError::LevelNotFound(_) => io::Error::new(io::ErrorKind::NotFound, e.to_string()),
+            e => io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+        }
+    }
+}
+
+/// A recoverable problem; the loader kept going.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum WadWarning {
+    #[error("Directory lists {declared} lumps but only {present} fit in the file")]
+    TruncatedDirectory { declared: usize, present: usize },
+
+    #[error("Lump {index} ({name}) at {offset}+{size} lies outside the file ({file_size} bytes); skipped")]
+    LumpOutOfBounds { index: usize, name: String, offset: i32, size: i32, file_size: usize },
+
+    #[error("Lumps {first} ({first_name}) and {second} ({second_name}) overlap")]
+    OverlappingLumps { first: usize, first_name: String, second: usize, second_name: String },
+
+    #[error("Lump {index} has a non-ASCII name {raw:?}; read as {name}")]
+    BadLumpName { index: usize, raw: Vec<u8>, name: String },
+
+    #[error("{level}: {lump} is {size} bytes, not a multiple of {record}; the last {} bytes are ignored", size % record)]
+    BadLumpSize { level: String, lump: String, size: usize, record: usize },
+}
+
+impl WadWarning {
+    /// Whether the warning is about the directory (as opposed to a level's lumps).
+    pub fn is_directory_warning(&self) -> bool {
+        !matches!(self, WadWarning::BadLumpSize { .. })
+    }
+}
+
+/// Everything the loader had to work around, in the order it was found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadReport {
+    pub warnings: Vec<WadWarning>,
+}
+
+impl LoadReport {
+    pub fn is_empty(&self) -> bool {
+        self.warnings.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.warnings.len()
+    }
+
+    /// Records `warning`, or turns it into an error in strict mode.
+    pub fn push(&mut self, mode: LoadMode, warning: WadWarning) -> Result<(), WadError> {
+        match mode {
+            LoadMode::Lenient => {
+                self.warnings.push(warning);
+                Ok(())
+            }
+            LoadMode::Strict => Err(WadError::Strict(warning)),
+        }
+    }
+
+    /// Drops the warnings about a previously loaded level, keeping the
+    /// directory warnings.
+    pub fn clear_level_warnings(&mut self) {
+        self.warnings.retain(WadWarning::is_directory_warning);
+    }
+
+    pub fn extend(&mut self, other: LoadReport) {
+        self.warnings.extend(other.warnings);
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for warning in &self.warnings {
+            writeln!(f, "{}", warning)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,409 @@
+// src/document/diff.rs
+
+//! Compares two versions of a WAD.
+//!
+//! Lumps are matched by name and, for map lumps, by the level they belong
+//! to, so MAP01's THINGS is never compared with MAP02's. Matched lumps are
+//! compared by content hash and by their order relative to the other matched
+//! lumps. Levels present in both WADs whose lumps differ are then loaded and
+//! compared object by object.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::io::{self, Cursor};
+
+use crate::document::diagnostics::{LoadMode, WadError};
+use crate::document::wad::{read_directory, LumpEntry};
+use crate::document::Document;
+
+/// One difference between the lump directories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LumpChange {
+    /// Only in the new WAD, at `index`.
+    Added { name: String, index: usize },
+    /// Only in the old WAD, at `index`.
+    Removed { name: String, index: usize },
+    /// In both, with different contents. `moved` is set if it also changed
+    /// place relative to the other lumps.
+    Changed { name: String, old_index: usize, new_index: usize, old_size: usize, new_size: usize, moved: bool },
+    /// In both with the same contents, but in a different place relative to
+    /// the other lumps.
+    Moved { name: String, old_index: usize, new_index: usize },
+}
+
+/// How the objects of one kind differ between two versions of a level.
+/// Added and changed objects are indices into the new level, removed ones
+/// into the old. Objects that were only renumbered count as unchanged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ObjectDiff {
+    pub added: Vec<usize>,
+    pub removed: Vec<usize>,
+    pub changed: Vec<usize>,
+}
+
+impl ObjectDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Structural differences in a level found in both WADs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LevelDiff {
+    pub name: String,
+    pub things: ObjectDiff,
+    pub linedefs: ObjectDiff,
+    pub sectors: ObjectDiff,
+}
+
+impl LevelDiff {
+    pub fn is_empty(&self) -> bool {
+        self.things.is_empty() && self.linedefs.is_empty() && self.sectors.is_empty()
+    }
+}
+
+/// The result of [`diff_wads`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WadDiff {
+    /// Changes to the directory, added/changed/moved lumps in new directory
+    /// order followed by removed lumps in old order.
+    pub lumps: Vec<LumpChange>,
+    /// Lumps found in both WADs, identical and in the same order.
+    pub unchanged: usize,
+    /// Levels in both WADs whose map lumps differ.
+    pub levels: Vec<LevelDiff>,
+}
+
+impl WadDiff {
+    pub fn is_empty(&self) -> bool {
+        self.lumps.is_empty()
+    }
+}
+
+/// Compares the WAD files `old` and `new`. Damaged directory entries are
+/// skipped, as when opening a WAD leniently.
+pub fn diff_wads(old: &[u8], new: &[u8]) -> Result<WadDiff, WadError> {
+    let (_, old_dir, _) = read_directory(old, LoadMode::Lenient)?;
+    let (_, new_dir, _) = read_directory(new, LoadMode::Lenient)?;
+    let old_keys = lump_keys(&old_dir);
+    let new_keys = lump_keys(&new_dir);
+    let old_by_key: HashMap<&(String, usize), usize> = old_keys.iter().enumerate().map(|(i, k)| (k, i)).collect();
+    let new_by_key: HashMap<&(String, usize), usize> = new_keys.iter().enumerate().map(|(i, k)| (k, i)).collect();
+
+    // Matched lumps keep their place if they are part of the longest run
+    // that is in the same order in both directories.
+    let matched: Vec<(usize, usize)> = old_keys
+        .iter()
+        .enumerate()
+        .filter_map(|(i, key)| new_by_key.get(key).map(|&j| (i, j)))
+        .collect();
+    let in_order = longest_increasing(&matched.iter().map(|&(_, j)| j).collect::<Vec<_>>());
+    let mut stayed = vec![false; new_dir.len()];
+    for k in in_order {
+        stayed[matched[k].1] = true;
+    }
+
+    let mut diff = WadDiff::default();
+    for (j, key) in new_keys.iter().enumerate() {
+        let name = key.0.clone();
+        let i = match old_by_key.get(key) {
+            Some(&i) => i,
+            None => {
+                diff.lumps.push(LumpChange::Added { name, index: j });
+                continue;
+            }
+        };
+        let (old_entry, new_entry) = (&old_dir[i], &new_dir[j]);
+        let moved = !stayed[j];
+        if old_entry.size != new_entry.size || old_entry.hash(old) != new_entry.hash(new) {
+            diff.lumps.push(LumpChange::Changed {
+                name,
+                old_index: i,
+                new_index: j,
+                old_size: old_entry.size as usize,
+                new_size: new_entry.size as usize,
+                moved,
+            });
+        } else if moved {
+            diff.lumps.push(LumpChange::Moved { name, old_index: i, new_index: j });
+        } else {
+            diff.unchanged += 1;
+        }
+    }
+    for (i, key) in old_keys.iter().enumerate() {
+        if !new_by_key.contains_key(key) {
+            diff.lumps.push(LumpChange::Removed { name: key.0.clone(), index: i });
+        }
+    }
+
+    // Levels in both WADs with a changed, added or removed map lump.
+    let old_levels = Document::group_levels(&old_dir);
+    let new_levels = Document::group_levels(&new_dir);
+    let touched = |level: &str| {
+        let prefix = format!("{}/", level);
+        diff.lumps.iter().any(|change| match change {
+            LumpChange::Added { name, .. } | LumpChange::Removed { name, .. } | LumpChange::Changed { name, .. } => {
+                name.starts_with(&prefix)
+            }
+            LumpChange::Moved { .. } => false,
+        })
+    };
+    let compared: Vec<String> = new_levels
+        .iter()
+        .map(|level| level.name.to_ascii_uppercase())
+        .filter(|name| old_levels.iter().any(|level| level.name.eq_ignore_ascii_case(name)))
+        .filter(|name| touched(name))
+        .collect();
+    if !compared.is_empty() {
+        let runtime = tokio::runtime::Runtime::new().map_err(io::Error::other)?;
+        let mut old_doc = open_document(old)?;
+        let mut new_doc = open_document(new)?;
+        for name in compared {
+            runtime.block_on(old_doc.load_level_async(&name, &mut Cursor::new(old)))?;
+            runtime.block_on(new_doc.load_level_async(&name, &mut Cursor::new(new)))?;
+            let level = diff_levels(name, &old_doc, &new_doc);
+            if !level.is_empty() {
+                diff.levels.push(level);
+            }
+        }
+    }
+    Ok(diff)
+}
+
+fn open_document(data: &[u8]) -> Result<Document, WadError> {
+    let mut doc = Document::new();
+    doc.load_wad(&mut Cursor::new(data))?;
+    Ok(doc)
+}
+
+/// A key per directory entry: the name, prefixed with the level for map
+/// lumps, and how many earlier entries had the same name.
+fn lump_keys(directory: &[LumpEntry]) -> Vec<(String, usize)> {
+    let mut names: Vec<String> = directory.iter().map(|entry| entry.name.trim().to_ascii_uppercase()).collect();
+    for level in Document::group_levels(directory) {
+        let marker = level.name.trim().to_ascii_uppercase();
+        for &index in &level.lump_indices[1..] {
+            names[index] = format!("{}/{}", marker, names[index]);
+        }
+    }
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    names
+        .into_iter()
+        .map(|name| {
+            let count = seen.entry(name.clone()).or_insert(0);
+            *count += 1;
+            (name, *count - 1)
+        })
+        .collect()
+}
+
+/// Positions in `values` of a longest strictly increasing subsequence.
+fn longest_increasing(values: &[usize]) -> Vec<usize> {
+    // tails[k]: index of the smallest tail of an increasing run of length k + 1.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous = vec![usize::MAX; values.len()];
+    for (i, &value) in values.iter().enumerate() {
+        let k = tails.partition_point(|&t| values[t] < value);
+        if k > 0 {
+            previous[i] = tails[k - 1];
+        }
+        if k == tails.len() {
+            tails.push(i);
+        } else {
+            tails[k] = i;
+        }
+    }
+    let mut run = Vec::with_capacity(tails.len());
+    let mut current = tails.last().copied().unwrap_or(usize::MAX);
+    while current != usize::MAX {
+        run.push(current);
+        current = previous[current];
+    }
+    run.reverse();
+    run
+}
+
+type SideKey = (i32, i32, String, String, String, i32);
+
+/// Compares the loaded levels of `old` and `new`. Linedefs are compared by
+/// their end points and sidedef contents rather than by vertex and sidedef
+/// numbers, which shift whenever something earlier is deleted.
+fn diff_levels(name: String, old: &Document, new: &Document) -> LevelDiff {
+    let thing_keys = |doc: &Document| -> Vec<_> {
+        doc.things
+            .read()
+            .iter()
+            .map(|t| (t.x, t.y, t.z, t.angle, t.doom_type, t.flags, t.tid, t.special, t.args))
+            .collect()
+    };
+    let sector_keys = |doc: &Document| -> Vec<_> {
+        doc.sectors
+            .read()
+            .iter()
+            .map(|s| (s.floor_height, s.ceiling_height, s.floor_tex.clone(), s.ceiling_tex.clone(), s.light, s.r#type, s.tag))
+            .collect()
+    };
+    let linedef_keys = |doc: &Document| -> Vec<_> {
+        let vertices = doc.vertices.read();
+        let sidedefs = doc.sidedefs.read();
+        let point = |index: usize| vertices.get(index).map(|v| (v.x, v.y));
+        let side = |index: i32| -> Option<SideKey> {
+            let side = sidedefs.get(usize::try_from(index).ok()?)?;
+            Some((side.x_offset, side.y_offset, side.upper_tex.clone(), side.mid_tex.clone(), side.lower_tex.clone(), side.sector))
+        };
+        doc.linedefs
+            .read()
+            .iter()
+            .map(|l| (point(l.start), point(l.end), l.flags, l.line_type, l.tag, l.args, side(l.right), side(l.left)))
+            .collect()
+    };
+
+    LevelDiff {
+        name,
+        things: diff_objects(&thing_keys(old), &thing_keys(new)),
+        linedefs: diff_objects(&linedef_keys(old), &linedef_keys(new)),
+        sectors: diff_objects(&sector_keys(old), &sector_keys(new)),
+    }
+}
+
+/// Pairs equal objects, first at the same index and then anywhere. The
+/// objects left over are paired up in order as changed; the surplus on either
+/// side was added or removed.
+fn diff_objects<K: Eq + Hash>(old: &[K], new: &[K]) -> ObjectDiff {
+    let common = old.len().min(new.len());
+    let mut old_left = vec![true; old.len()];
+    let mut new_left = vec![true; new.len()];
+    for i in 0..common {
+        if old[i] == new[i] {
+            old_left[i] = false;
+            new_left[i] = false;
+        }
+    }
+
+    let mut pool: HashMap<&K, Vec<usize>> = HashMap::new();
+    for (i, key) in old.iter().enumerate().rev().filter(|&(i, _)| old_left[i]) {
+        pool.entry(key).or_default().push(i);
+    }
+    for (j, key) in new.iter().enumerate() {
+        if !new_left[j] {
+            continue;
+        }
+        if let Some(i) = pool.get_mut(key).and_then(Vec::pop) {
+            old_left[i] = false;
+            new_left[j] = false;
+        }
+    }
+
+    let old_rest: Vec<usize> = (0..old.len()).filter(|&i| old_left[i]).collect();
+    let new_rest: Vec<usize> = (0..new.len()).filter(|&j| new_left[j]).collect();
+    let paired = old_rest.len().min(new_rest.len());
+    ObjectDiff {
+        changed: new_rest[..paired].to_vec(),
+        added: new_rest[paired..].to_vec(),
+        removed: old_rest[paired..].to_vec(),
+    }
+}
+
+impl fmt::Display for LumpChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LumpChange::Added { name, index } => write!(f, "+ {} (#{})", name, index),
+            LumpChange::Removed { name, index } => write!(f, "- {} (was #{})", name, index),
+            LumpChange::Changed { name, old_index, new_index, old_size, new_size, moved } => {
+                write!(f, "* {} ({} -> {} bytes", name, old_size, new_size)?;
+                if *moved {
+                    write!(f, ", moved #{} -> #{}", old_index, new_index)?;
+                }
+                write!(f, ")")
+            }
+            LumpChange::Moved { name, old_index, new_index } => {
+                write!(f, "~ {} (moved #{} -> #{})", name, old_index, new_index)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ObjectDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} added, {} removed, {} changed", self.added.len(), self.removed.len(), self.changed.len())
+    }
+}
+
+impl fmt::Display for WadDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "The WADs are identical ({} lumps).", self.unchanged);
+        }
+        writeln!(f, "{} lump change(s), {} lump(s) unchanged:", self.lumps.len(), self.unchanged)?;
+        for change in &self.lumps {
+            writeln!(f, "  {}", change)?;
+        }
+        for level in &self.levels {
+            writeln!(f, "{}:", level.name)?;
+            writeln!(f, "  things: {}", level.things)?;
+            writeln!(f, "  linedefs: {}", level.linedefs)?;
+            writeln!(f, "  sectors: {}", level.sectors)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::wad::{write_wad, Lump};
+
+    fn wad(lumps: &[Lump]) -> Vec<u8> {
+        let mut cursor = Cursor::new(Vec::new());
+        write_wad(&mut cursor, b"PWAD", lumps).unwrap();
+        cursor.into_inner()
+    }
+
+    #[test]
+    fn test_diff_wads() {
+        let mut doc = Document::new();
+        doc.generate_test_map();
+        doc.add_thing(64, 64, 90, 3001, 7);
+        doc.map_name = "MAP01".into();
+        let mut old_lumps = vec![Lump::new("DEHACKED", b"a".to_vec()), Lump::marker("MAP01")];
+        old_lumps.extend(doc.level_lumps().unwrap());
+        old_lumps.push(Lump::new("PLAYPAL", vec![0; 768]));
+        old_lumps.push(Lump::new("ENDOOM", vec![1; 10]));
+
+        // Move a thing, add one, and drop the first one.
+        {
+            let mut things = doc.things.write();
+            let mut moved = (*things[1]).clone();
+            moved.x += 64;
+            things[1] = std::sync::Arc::new(moved);
+            let extra = (*things[0]).clone();
+            things.push(std::sync::Arc::new(extra));
+            things.remove(0);
+        }
+        let mut new_lumps = vec![Lump::marker("MAP01")];
+        new_lumps.extend(doc.level_lumps().unwrap());
+        new_lumps.push(Lump::new("DEHACKED", b"a".to_vec()));
+        new_lumps.push(Lump::new("PLAYPAL", vec![1; 768]));
+        new_lumps.push(Lump::new("CREDIT", vec![2; 4]));
+
+        let diff = diff_wads(&wad(&old_lumps), &wad(&new_lumps)).unwrap();
+        assert!(diff.lumps.contains(&LumpChange::Added { name: "CREDIT".into(), index: new_lumps.len() - 1 }));
+        assert!(diff.lumps.contains(&LumpChange::Removed { name: "ENDOOM".into(), index: old_lumps.len() - 1 }));
+        assert!(diff.lumps.iter().any(|c| matches!(c, LumpChange::Moved { name, .. } if name == "DEHACKED")));
+        assert!(diff.lumps.iter().any(|c| matches!(c, LumpChange::Changed { name, moved: false, .. } if name == "PLAYPAL")));
+        assert!(diff.lumps.iter().any(|c| matches!(c, LumpChange::Changed { name, .. } if name == "MAP01/THINGS")));
+
+        assert_eq!(diff.levels.len(), 1);
+        let level = &diff.levels[0];
+        assert_eq!(level.name, "MAP01");
+        // Thing 0 went to the end unchanged; thing 1 (now 0) moved.
+        assert_eq!(level.things.changed, vec![0]);
+        assert!(level.things.added.is_empty() && level.things.removed.is_empty());
+        assert!(level.linedefs.is_empty() && level.sectors.is_empty());
+
+        let same = diff_wads(&wad(&old_lumps), &wad(&old_lumps)).unwrap();
+        assert!(same.is_empty());
+        assert_eq!(same.unchanged, old_lumps.len());
+    }
+}
@@ -1,14 +1,22 @@
 // src/document/document.rs
 
-use crate::map::{LineDef, Sector, SideDef, Thing, Vertex};
-use crate::bsp::BspLevel;
+use crate::map::{LineDef, MapFormat, Sector, SideDef, Thing, Vertex};
+use crate::map::udmf::{self, UdmfLevel};
+use crate::bsp::{BspLevel, BuildOptions, NodeLumps};
+use crate::bsp::bsp_lumps::NodeData;
+use crate::document::dehconsts::{DehPatch, ThingCatalog};
+use crate::document::diagnostics::{LoadMode, LoadReport, WadError, WadWarning};
+use crate::document::palette::{PaletteError, Shading};
+use crate::document::resources::{classify_namespaces, Namespace, ResourceManager};
+use crate::document::textures::{decode_flat, TextureError, TextureSet};
+use crate::document::wad::{is_valid_lump_name, parse_directory, read_directory, write_wad, Lump, LumpEntry, WadBytes};
+use crate::utils::adler::Adler32;
+use image::RgbaImage;
 use parking_lot::RwLock;
-use rayon::prelude::*;
-use std::io::{self, Read, Seek, SeekFrom, Cursor};
+use std::io::{self, Read, Seek, SeekFrom, Cursor, Write};
 use std::str;
 use std::sync::Arc;
-use byteorder::{LE, ReadBytesExt};
-use log::{error, info};
+use log::{info, warn};
 
 #[derive(Debug, Clone, Copy)]
 pub enum ObjType {
@@ -25,14 +33,6 @@ pub enum Side {
     Right,
 }
 
-/// A single lump entry from the WAD directory.
-#[derive(Debug, Clone)]
-pub struct LumpEntry {
-    pub offset: i32,
-    pub size: i32,
-    pub name: String,
-}
-
 /// A grouping of lumps that form a level.
 #[derive(Debug, Clone)]
 pub struct LevelInfo {
@@ -61,9 +61,31 @@ pub struct Document {
     pub directory: Arc<RwLock<Vec<LumpEntry>>>,
     pub levels: Arc<RwLock<Vec<LevelInfo>>>,
     pub selected_level: Arc<RwLock<Option<String>>>,
-    pub wad_data: Arc<RwLock<Option<Vec<u8>>>>,
+    pub wad_data: Arc<RwLock<Option<WadBytes>>>,
+    /// Namespace of each `directory` entry, kept in step with it.
+    namespaces: Arc<RwLock<Vec<Namespace>>>,
+    /// PLAYPAL and COLORMAP once decoded, until the WAD or resources change.
+    shading_cache: Arc<RwLock<Option<Arc<Shading>>>>,
+    /// Bumped whenever the lumps or the resource stack change.
+    revision: Arc<RwLock<u64>>,
+
+    /// Read-only resource WADs (IWAD + PWADs), shared with the editor.
+    pub resources: Arc<RwLock<ResourceManager>>,
+    /// Thing names, sizes and sprites, with DEHACKED changes applied.
+    pub thing_catalog: Arc<RwLock<ThingCatalog>>,
+
+    /// Whether damaged data is worked around (and reported) or rejected.
+    pub load_mode: LoadMode,
+    /// Problems found in the directory and the current level.
+    pub load_report: Arc<RwLock<LoadReport>>,
 
     pub map_name: String,
+    /// Layout of the current level's THINGS/LINEDEFS, detected on load.
+    pub map_format: MapFormat,
+    /// Namespace, global fields and unknown blocks of a UDMF level.
+    pub udmf: UdmfLevel,
+    /// How the nodes, REJECT and BLOCKMAP written on save are built.
+    pub build_options: BuildOptions,
 
     pub dirty: bool,
 }
@@ -82,8 +104,6 @@ pub trait AsyncLoadable: Sized {
     async fn load_async<R: Read + Seek + Send>(reader: &mut R) -> io::Result<Self>;
 }
 
-const FILELUMP_SIZE: usize = 16; // 4 bytes (filepos) + 4 bytes (size) + 8 bytes (name)
-
 impl Document {
     /// Create a new empty Document.
     pub fn new() -> Self {
@@ -102,7 +122,17 @@ impl Document {
             levels: Arc::new(RwLock::new(Vec::new())),
             selected_level: Arc::new(RwLock::new(None)),
             wad_data: Arc::new(RwLock::new(None)),
+            namespaces: Arc::new(RwLock::new(Vec::new())),
+            shading_cache: Arc::new(RwLock::new(None)),
+            revision: Arc::new(RwLock::new(0)),
+            resources: Arc::new(RwLock::new(ResourceManager::new())),
+            thing_catalog: Arc::new(RwLock::new(ThingCatalog::stock())),
+            load_mode: LoadMode::Lenient,
+            load_report: Arc::new(RwLock::new(LoadReport::default())),
             map_name: String::new(),
+            map_format: MapFormat::Doom,
+            udmf: UdmfLevel::default(),
+            build_options: BuildOptions::default(),
             dirty: false,
         }
     }
@@ -139,7 +169,7 @@ impl Document {
     /// Adds a vertex and returns its index.
     pub fn add_vertex(&mut self, x: i32, y: i32) -> usize {
         let mut vertices = self.vertices.write();
-        let new_vertex = Arc::new(Vertex { x, y });
+        let new_vertex = Arc::new(Vertex::new(x, y));
         vertices.push(new_vertex);
         vertices.len() - 1
     }
@@ -194,6 +224,7 @@ impl Document {
             tag: 0,
             right: right_side_sector_id as i32,
             left: left_side_sector_id as i32,
+            ..Default::default()
         });
         linedefs.push(new_linedef);
         linedefs.len() - 1
@@ -220,6 +251,7 @@ impl Document {
             light: light_level as i32,
             r#type: sector_type as i32,
             tag: 0,
+            ..Default::default()
         });
         sectors.push(new_sector);
         sectors.len() - 1
@@ -244,6 +276,7 @@ impl Document {
             angle,
             doom_type: doom_type as i32,
             flags: flags as i32,
+            ..Default::default()
         });
         things.push(new_thing);
         things.len() - 1
@@ -259,49 +292,25 @@ impl Document {
         }
     }
 
-    /// Computes a checksum over all geometry.
+    /// Computes the level checksum the way Eureka does (`BA_LevelChecksum`),
+    /// so per-map data keyed on it can be shared with Eureka. It is an
+    /// Adler-32 over every thing, then every linedef with its vertices,
+    /// sidedefs and their sectors, in index order: reordering objects changes
+    /// it, while unused vertices, sidedefs and sectors do not count.
     pub fn get_level_checksum(&self) -> u32 {
-        let mut checksum = 0u32;
-        {
-            let things = self.things.read();
-            checksum = checksum.wrapping_add(
-                things.par_iter().map(|thing| {
-                    let mut crc = 0u32;
-                    checksum_thing(&mut crc, thing);
-                    crc
-                }).sum::<u32>()
-            );
+        let mut crc = Adler32::new();
+        for thing in self.things.read().iter() {
+            checksum_thing(&mut crc, thing);
         }
         {
             let vertices = self.vertices.read();
-            checksum = checksum.wrapping_add(
-                vertices.par_iter().map(|vertex| {
-                    let mut crc = 0u32;
-                    checksum_vertex(&mut crc, vertex);
-                    crc
-                }).sum::<u32>()
-            );
-        }
-        {
+            let sidedefs = self.sidedefs.read();
             let sectors = self.sectors.read();
-            checksum = checksum.wrapping_add(
-                sectors.par_iter().map(|sector| {
-                    let mut crc = 0u32;
-                    checksum_sector(&mut crc, sector);
-                    crc
-                }).sum::<u32>()
-            );
-        }
-        {
-            let linedefs = self.linedefs.read();
-            checksum = checksum.wrapping_add(
-                linedefs.par_iter().map(|line| {
-                    let mut crc = 0u32;
-                    checksum_linedef(&mut crc, line, self);
-                    crc
-                }).sum::<u32>()
-            );
+            for line in self.linedefs.read().iter() {
+                checksum_linedef(&mut crc, line, &vertices, &sidedefs, &sectors);
+            }
         }
+        let checksum = crc.value();
         *self.checksum.write() = checksum;
         checksum
     }
@@ -362,6 +371,7 @@ impl Document {
         self.behavior_data.write().clear();
         self.scripts_data.write().clear();
         *self.checksum.write() = 0;
+        *self.load_report.write() = LoadReport::default();
     }
 
     /// Returns true if the linedef is horizontal.
@@ -405,8 +415,12 @@ impl Document {
     // --- WAD Loading and Level Selection ---
 
     /// Loads a WAD file from the given reader.
-    /// Reads the entire file into memory (wad_data) so that levels can be reloaded on demand.
-    pub fn load_wad<R: Read + Seek>(&mut self, reader: &mut R) -> io::Result<()> {
+    /// Reads the entire file into memory once (wad_data). Level loads share that
+    /// buffer and only read the lumps they need, so switching levels copies nothing.
+    ///
+    /// Returns the problems worked around in `load_mode`; the same report is
+    /// kept in `load_report` for the UI.
+    pub fn load_wad<R: Read + Seek>(&mut self, reader: &mut R) -> Result<LoadReport, WadError> {
         // Create a new runtime for async operations
         let runtime = tokio::runtime::Runtime::new()
             .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
@@ -422,48 +436,15 @@ impl Document {
             // Read entire file into memory.
             let mut full_data = Vec::with_capacity(total_size as usize);
             reader.read_to_end(&mut full_data)?;
-            *self.wad_data.write() = Some(full_data.clone());
-            
-            // Create a new cursor over the full data.
-            let mut cursor = Cursor::new(full_data);
-
-            // --- Read Header ---
-            let mut header_buf = [0u8; 12];
-            cursor.read_exact(&mut header_buf)?;
-            let ident = &header_buf[0..4];
-            if ident != b"IWAD" && ident != b"PWAD" {
-                return Err(io::Error::new(io::ErrorKind::InvalidData,
-                    format!("Invalid WAD identifier: {}", String::from_utf8_lossy(ident))));
-            }
-            *self.header_data.write() = header_buf.to_vec();
-            let num_lumps = (&header_buf[4..8]).read_i32::<LE>()?;
-            let infotableofs = (&header_buf[8..12]).read_i32::<LE>()?;
-            if (infotableofs as u64) > total_size {
-                return Err(io::Error::new(io::ErrorKind::InvalidData, "Directory offset exceeds total file size"));
-            }
+            let full_data: WadBytes = full_data.into();
 
-            // --- Read Directory ---
-            let dir_size = (num_lumps as usize) * FILELUMP_SIZE;
-            cursor.seek(SeekFrom::Start(infotableofs as u64))?;
-            let mut dir_buf = vec![0u8; dir_size];
-            cursor.read_exact(&mut dir_buf)?;
-            let mut directory = Vec::with_capacity(num_lumps as usize);
-            for i in 0..(num_lumps as usize) {
-                let offset = i * FILELUMP_SIZE;
-                let lump_offset = (&dir_buf[offset..offset+4]).read_i32::<LE>()?;
-                let lump_size = (&dir_buf[offset+4..offset+8]).read_i32::<LE>()?;
-                let name_bytes = &dir_buf[offset+8..offset+16];
-                let lump_name = str::from_utf8(name_bytes).unwrap_or("").trim_end_matches('\0').to_string();
-                if lump_offset < 0 || lump_size < 0 ||
-                   (lump_offset as u64) > total_size ||
-                   (lump_offset as u64) + (lump_size as u64) > total_size {
-                    eprintln!("WARNING: Lump '{}' has invalid offset/size ({}+{} > {})",
-                              lump_name, lump_offset, lump_size, total_size);
-                    continue;
-                }
-                directory.push(LumpEntry { offset: lump_offset, size: lump_size, name: lump_name });
-            }
+            let (header, directory, report) = read_directory(&full_data, self.load_mode)?;
+            *self.wad_data.write() = Some(Arc::clone(&full_data));
+            *self.header_data.write() = header.to_vec();
             *self.directory.write() = directory;
+            self.invalidate_caches();
+            *self.selected_level.write() = None;
+            *self.load_report.write() = report;
 
             // --- Group Lumps into Levels ---
             let levels = Self::group_levels(&self.directory.read());
@@ -475,52 +456,65 @@ impl Document {
                 levels.get(0).map(|lvl| lvl.name.clone())
             };
             if let Some(level_name) = first_level_name {
-                let wad_data_clone = {
-                    let wad = self.wad_data.read();
-                    wad.as_ref().unwrap().clone()
-                };
-                self.load_level_async(&level_name, &mut Cursor::new(wad_data_clone)).await?;
+                self.load_level_async(&level_name, &mut Cursor::new(full_data)).await?;
                 *self.selected_level.write() = Some(level_name);
             }
-            Ok(())
+            for warning in &self.load_report.read().warnings {
+                warn!("{}", warning);
+            }
+            self.rebuild_thing_catalog();
+            Ok(self.load_report.read().clone())
         })
     }
 
-    /// Groups lumps from the directory into levels based on markers (e.g. "MAP01" or "E1M1").
-    fn group_levels(directory: &Vec<LumpEntry>) -> Vec<LevelInfo> {
+    /// Groups lumps from the directory into levels.
+    /// A level is its marker plus the map lumps directly following it; the first
+    /// lump that is not part of a map (graphics, music, ...) ends the level.
+    pub(crate) fn group_levels(directory: &[LumpEntry]) -> Vec<LevelInfo> {
         let mut levels = Vec::new();
-        let mut current_level: Option<LevelInfo> = None;
-        for (i, entry) in directory.iter().enumerate() {
-            if Self::is_level_marker(&entry.name) {
-                if let Some(lvl) = current_level.take() {
-                    levels.push(lvl);
-                }
-                current_level = Some(LevelInfo {
-                    name: entry.name.clone(),
-                    lump_indices: vec![i],
+        let mut i = 0;
+        while i < directory.len() {
+            if Self::is_level_marker(directory, i) {
+                let count = Self::level_lump_count(directory, i);
+                levels.push(LevelInfo {
+                    name: directory[i].name.clone(),
+                    lump_indices: (i..=i + count).collect(),
                 });
-            } else if let Some(ref mut lvl) = current_level {
-                lvl.lump_indices.push(i);
+                i += count + 1;
+            } else {
+                i += 1;
             }
         }
-        if let Some(lvl) = current_level {
-            levels.push(lvl);
-        }
         levels
     }
 
-    /// Returns true if the lump name indicates a level marker.
-    fn is_level_marker(name: &str) -> bool {
-        let name = name.trim();
-        let upper = name.to_uppercase();
-        if upper.starts_with("MAP") && upper.len() >= 5 {
-            upper.chars().skip(3).take(2).all(|c| c.is_ascii_digit())
-        } else if upper.len() == 4 && upper.starts_with('E') && upper.chars().nth(2) == Some('M') {
-            upper.chars().nth(1).map_or(false, |c| c.is_ascii_digit()) &&
-            upper.chars().nth(3).map_or(false, |c| c.is_ascii_digit())
-        } else {
-            false
+    /// Number of map lumps directly following the marker at `marker`.
+    /// For UDMF levels this is everything from TEXTMAP through ENDMAP.
+    fn level_lump_count(directory: &[LumpEntry], marker: usize) -> usize {
+        let following = &directory[marker + 1..];
+        if following.first().is_some_and(|entry| entry.name.trim().eq_ignore_ascii_case("TEXTMAP")) {
+            if let Some(end) = following.iter().position(|entry| entry.name.trim().eq_ignore_ascii_case("ENDMAP")) {
+                return end + 1;
+            }
         }
+        following
+            .iter()
+            .take_while(|entry| is_map_lump(&entry.name))
+            .count()
+    }
+
+    /// True if the lump at `index` starts a level: it is followed by THINGS or
+    /// LINEDEFS, or by TEXTMAP for UDMF. The marker's name does not matter, so
+    /// E1M10, MAP100 and TESTMAP are found like MAP01.
+    fn is_level_marker(directory: &[LumpEntry], index: usize) -> bool {
+        let name = directory[index].name.trim();
+        if is_map_lump(name) || name.eq_ignore_ascii_case("TEXTMAP") || name.eq_ignore_ascii_case("ENDMAP") {
+            return false;
+        }
+        directory.get(index + 1).is_some_and(|next| {
+            let next = next.name.trim();
+            ["THINGS", "LINEDEFS", "TEXTMAP"].iter().any(|n| next.eq_ignore_ascii_case(n))
+        })
     }
 
     /// Computes the bounding box of the level from its vertices.
@@ -552,11 +546,6 @@ impl Document {
         offset: i32,
         size: i32
     ) -> io::Result<()> {
-        if size % 4 != 0 {
-            return Err(io::Error::new(io::ErrorKind::InvalidData, 
-                "VERTEXES lump size is not a multiple of 4"));
-        }
-
         let mut buffer = vec![0u8; size as usize];
         reader.seek(SeekFrom::Start(offset as u64))?;
         reader.read_exact(&mut buffer)?;
@@ -586,11 +575,6 @@ impl Document {
         offset: i32,
         size: i32
     ) -> io::Result<()> {
-        if size % 26 != 0 {
-            return Err(io::Error::new(io::ErrorKind::InvalidData, 
-                "SECTORS lump size is not a multiple of 26"));
-        }
-
         let mut buffer = vec![0u8; size as usize];
         reader.seek(SeekFrom::Start(offset as u64))?;
         reader.read_exact(&mut buffer)?;
@@ -620,11 +604,6 @@ impl Document {
         offset: i32,
         size: i32
     ) -> io::Result<()> {
-        if size % 30 != 0 {
-            return Err(io::Error::new(io::ErrorKind::InvalidData, 
-                "SIDEDEFS lump size is not a multiple of 30"));
-        }
-
         let mut buffer = vec![0u8; size as usize];
         reader.seek(SeekFrom::Start(offset as u64))?;
         reader.read_exact(&mut buffer)?;
@@ -652,12 +631,10 @@ impl Document {
         &self,
         reader: &mut R,
         offset: i32,
-        size: i32
+        size: i32,
+        format: MapFormat,
     ) -> io::Result<()> {
-        if size % 14 != 0 {
-            return Err(io::Error::new(io::ErrorKind::InvalidData, 
-                "LINEDEFS lump size is not a multiple of 14"));
-        }
+        let record = format.linedef_size() as i32;
 
         let mut buffer = vec![0u8; size as usize];
         reader.seek(SeekFrom::Start(offset as u64))?;
@@ -666,10 +643,14 @@ impl Document {
         let linedefs_data = tokio::task::spawn_blocking(move || {
             let mut linedefs = Vec::new();
             let mut cursor = Cursor::new(buffer);
-            let num_linedefs = size / 14;
+            let num_linedefs = size / record;
             
             for _ in 0..num_linedefs {
-                if let Ok(linedef) = LineDef::from_wad(&mut cursor) {
+                let linedef = match format {
+                    MapFormat::Doom | MapFormat::Udmf => LineDef::from_wad(&mut cursor),
+                    MapFormat::Hexen => LineDef::from_wad_hexen(&mut cursor),
+                };
+                if let Ok(linedef) = linedef {
                     linedefs.push(Arc::new(linedef));
                 }
             }
@@ -686,12 +667,10 @@ impl Document {
         &self,
         reader: &mut R,
         offset: i32,
-        size: i32
+        size: i32,
+        format: MapFormat,
     ) -> io::Result<()> {
-        if size % 10 != 0 {
-            return Err(io::Error::new(io::ErrorKind::InvalidData, 
-                "THINGS lump size is not a multiple of 10"));
-        }
+        let record = format.thing_size() as i32;
 
         let mut buffer = vec![0u8; size as usize];
         reader.seek(SeekFrom::Start(offset as u64))?;
@@ -700,10 +679,14 @@ impl Document {
         let things_data = tokio::task::spawn_blocking(move || {
             let mut things = Vec::new();
             let mut cursor = Cursor::new(buffer);
-            let num_things = size / 10;
+            let num_things = size / record;
             
             for _ in 0..num_things {
-                if let Ok(thing) = Thing::from_wad(&mut cursor) {
+                let thing = match format {
+                    MapFormat::Doom | MapFormat::Udmf => Thing::from_wad(&mut cursor),
+                    MapFormat::Hexen => Thing::from_wad_hexen(&mut cursor),
+                };
+                if let Ok(thing) = thing {
                     things.push(Arc::new(thing));
                 }
             }
@@ -748,35 +731,134 @@ impl Document {
         Ok(())
     }
 
+    /// Parses a UDMF TEXTMAP lump into the geometry lists and returns the
+    /// level-wide UDMF data.
+    async fn load_textmap_async<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        offset: i32,
+        size: i32
+    ) -> io::Result<UdmfLevel> {
+        let mut buffer = vec![0u8; size as usize];
+        reader.seek(SeekFrom::Start(offset as u64))?;
+        reader.read_exact(&mut buffer)?;
+
+        let textmap = tokio::task::spawn_blocking(move || udmf::parse_textmap(&buffer))
+            .await
+            .map_err(io::Error::other)?
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+        *self.things.write() = textmap.things.into_iter().map(Arc::new).collect();
+        *self.vertices.write() = textmap.vertices.into_iter().map(Arc::new).collect();
+        *self.linedefs.write() = textmap.linedefs.into_iter().map(Arc::new).collect();
+        *self.sidedefs.write() = textmap.sidedefs.into_iter().map(Arc::new).collect();
+        *self.sectors.write() = textmap.sectors.into_iter().map(Arc::new).collect();
+
+        Ok(textmap.level)
+    }
+
     /// Loads the geometry for a level given by its marker (e.g. "MAP01").
     /// 
-    pub async fn load_level_async<R: Read + Seek>(&mut self, level_name: &str, reader: &mut R) -> io::Result<()> {
+    ///
+    /// Lumps whose size is not a whole number of records are reported in
+    /// `load_report` (lenient mode; the partial record is dropped) or rejected
+    /// (strict mode).
+    pub async fn load_level_async<R: Read + Seek>(&mut self, level_name: &str, reader: &mut R) -> Result<(), WadError> {
         self.clear_geometry();
+        self.load_report.write().clear_level_warnings();
         let level_info_opt = {
             let levels = self.levels.read();
             levels.iter().find(|lvl| lvl.name.eq_ignore_ascii_case(level_name)).cloned()
         };
         if let Some(level_info) = level_info_opt {
             let directory = self.directory.read();
+            // UDMF levels carry a TEXTMAP, Hexen-format levels a BEHAVIOR lump.
+            let has_lump = |name: &str| level_info.lump_indices.iter()
+                .any(|&index| directory[index].name.trim().eq_ignore_ascii_case(name));
+            let format = if has_lump("TEXTMAP") {
+                MapFormat::Udmf
+            } else if has_lump("BEHAVIOR") {
+                MapFormat::Hexen
+            } else {
+                MapFormat::Doom
+            };
+            let mut udmf_level = UdmfLevel::default();
+            let mut node_lumps: [Vec<u8>; 3] = Default::default();
             for &index in &level_info.lump_indices {
                 let entry = &directory[index];
                 // Normalize lump name by trimming and converting to uppercase.
                 let lump_name = entry.name.trim().to_uppercase();
+                if let Some(record) = record_size(&lump_name, format) {
+                    let size = entry.size as usize;
+                    if !size.is_multiple_of(record) {
+                        self.load_report.write().push(self.load_mode, WadWarning::BadLumpSize {
+                            level: level_info.name.clone(),
+                            lump: lump_name.clone(),
+                            size,
+                            record,
+                        })?;
+                    }
+                }
                 match lump_name.as_str() {
-                    "THINGS" => { self.load_things_async(reader, entry.offset, entry.size).await?; },
+                    "THINGS" => { self.load_things_async(reader, entry.offset, entry.size, format).await?; },
                     "VERTEXES" => { self.load_vertices_async(reader, entry.offset, entry.size).await?; },
                     "SECTORS" => { self.load_sectors_async(reader, entry.offset, entry.size).await?; },
                     "SIDEDEFS" => { self.load_sidedefs_async(reader, entry.offset, entry.size).await?; },
-                    "LINEDEFS" => { self.load_linedefs_async(reader, entry.offset, entry.size).await?; },
+                    "LINEDEFS" => { self.load_linedefs_async(reader, entry.offset, entry.size, format).await?; },
                     "BEHAVIOR" => { self.load_behavior_async(reader, entry.offset, entry.size).await?; },
                     "SCRIPTS" => { self.load_scripts_async(reader, entry.offset, entry.size).await?; },
+                    "TEXTMAP" => { udmf_level = self.load_textmap_async(reader, entry.offset, entry.size).await?; },
+                    "SEGS" => { node_lumps[0] = read_lump_data(reader, entry.offset, entry.size)?; },
+                    "SSECTORS" => { node_lumps[1] = read_lump_data(reader, entry.offset, entry.size)?; },
+                    "NODES" => { node_lumps[2] = read_lump_data(reader, entry.offset, entry.size)?; },
                     _ => { /* Ignore unknown lumps */ }
                 }
             }
+            if format != MapFormat::Udmf && (has_lump("SEGS") || has_lump("NODES")) {
+                let [segs, ssectors, nodes] = &node_lumps;
+                let vertex_count = self.vertices.read().len();
+                match NodeData::read(segs, ssectors, nodes, vertex_count) {
+                    Ok((node_format, nodes)) => {
+                        info!("Read {} nodes for {}", node_format, level_info.name);
+                        self.drop_split_vertices(&nodes);
+                    }
+                    // Without the segs there is no telling split vertices from loose ones.
+                    Err(e) => warn!("{}: {}; keeping every vertex", level_info.name, e),
+                }
+            }
+            self.map_format = format;
+            self.udmf = udmf_level;
             *self.selected_level.write() = Some(level_info.name.clone());
             Ok(())
         } else {
-            Err(io::Error::new(io::ErrorKind::NotFound, "Level not found"))
+            Err(WadError::LevelNotFound(level_name.to_string()))
+        }
+    }
+
+    /// Node builders append the vertices made by splitting segs to VERTEXES.
+    /// No linedef uses them, so they are dropped on load and written again
+    /// with the nodes on save. They start at the first vertex a seg uses but
+    /// no linedef does.
+    fn drop_split_vertices(&self, nodes: &NodeData) {
+        let linedefs = self.linedefs.read();
+        let mut on_lines = vec![false; self.vertices.read().len()];
+        for line in linedefs.iter() {
+            for index in [line.start, line.end] {
+                if let Some(used) = on_lines.get_mut(index) {
+                    *used = true;
+                }
+            }
+        }
+        let used = linedefs.iter().map(|l| l.start.max(l.end) + 1).max().unwrap_or(0);
+        let first_split = nodes.segs.iter()
+            .flat_map(|seg| [seg.v1, seg.v2])
+            .filter(|&index| !on_lines.get(index).copied().unwrap_or(false))
+            .fold(nodes.original_vertices, usize::min);
+        let mut vertices = self.vertices.write();
+        let dropped = vertices.len().saturating_sub(first_split.max(used));
+        if dropped > 0 {
+            info!("Dropped {} split vertices", dropped);
+            vertices.truncate(first_split.max(used));
         }
     }
 
@@ -785,6 +867,482 @@ impl Document {
         self.levels.read().iter().map(|lvl| lvl.name.clone()).collect()
     }
 
+    // --- WAD Saving ---
+
+    /// The marker name used when writing the current level.
+    /// Falls back to `map_name`, then to "MAP01" for brand new documents.
+    pub fn level_marker_name(&self) -> String {
+        if let Some(name) = self.selected_level.read().as_ref() {
+            return name.clone();
+        }
+        if !self.map_name.is_empty() {
+            return self.map_name.clone();
+        }
+        "MAP01".to_string()
+    }
+
+    /// Serializes the current geometry into the classic level lumps
+    /// (THINGS, LINEDEFS, SIDEDEFS, VERTEXES, SEGS, SSECTORS, NODES, SECTORS,
+    /// REJECT, BLOCKMAP), in vanilla order. The node lumps are built fresh,
+    /// and the vertices the node splits create are appended to VERTEXES.
+    /// Hexen-format levels use the Hexen layouts and also get their BEHAVIOR
+    /// (and SCRIPTS, if present) lumps. UDMF levels are written as TEXTMAP
+    /// through ENDMAP instead.
+    pub fn level_lumps(&self) -> io::Result<Vec<Lump>> {
+        let (things, linedefs) = match self.map_format {
+            MapFormat::Udmf => return Ok(self.udmf_lumps()),
+            MapFormat::Doom => (
+                encode_lump(&self.things.read(), Thing::to_wad)?,
+                encode_lump(&self.linedefs.read(), LineDef::to_wad)?,
+            ),
+            MapFormat::Hexen => (
+                encode_lump(&self.things.read(), Thing::to_wad_hexen)?,
+                encode_lump(&self.linedefs.read(), LineDef::to_wad_hexen)?,
+            ),
+        };
+        let nodes = self.node_lumps();
+        let mut vertices = Cursor::new(encode_lump(&self.vertices.read(), Vertex::to_wad)?);
+        vertices.seek(SeekFrom::End(0))?;
+        for vertex in &nodes.vertices {
+            vertex.to_wad(&mut vertices)?;
+        }
+
+        let mut lumps = vec![
+            Lump::new("THINGS", things),
+            Lump::new("LINEDEFS", linedefs),
+            Lump::new("SIDEDEFS", encode_lump(&self.sidedefs.read(), SideDef::to_wad)?),
+            Lump::new("VERTEXES", vertices.into_inner()),
+        ];
+        lumps.push(Lump::new("SECTORS", encode_lump(&self.sectors.read(), Sector::to_wad)?));
+        lumps.extend(nodes.lumps);
+        // The node lumps sit among the others: SECTORS comes before REJECT.
+        lumps.sort_by_key(|lump| map_lump_rank(&lump.name));
+        if self.map_format == MapFormat::Hexen {
+            lumps.push(Lump::new("BEHAVIOR", self.behavior_data.read().clone()));
+            let scripts = self.scripts_data.read();
+            if !scripts.is_empty() {
+                lumps.push(Lump::new("SCRIPTS", scripts.clone()));
+            }
+        }
+        Ok(lumps)
+    }
+
+    /// SEGS, SSECTORS, NODES, REJECT and BLOCKMAP for the current geometry.
+    /// A level without linedefs gets them empty, and so does one whose nodes
+    /// cannot be built, so that its geometry is still saved.
+    fn node_lumps(&self) -> NodeLumps {
+        if self.linedefs.read().is_empty() {
+            return NodeLumps::empty();
+        }
+        match self.build_bsp().and_then(|bsp| NodeLumps::new(&bsp)) {
+            Ok(nodes) => {
+                info!("Wrote {} nodes", nodes.format);
+                nodes
+            }
+            Err(e) => {
+                warn!("Could not build nodes: {}; writing empty node lumps", e);
+                NodeLumps::empty()
+            }
+        }
+    }
+
+    /// TEXTMAP, the optional BEHAVIOR/SCRIPTS lumps, and ENDMAP.
+    fn udmf_lumps(&self) -> Vec<Lump> {
+        let textmap = udmf::write_textmap(
+            &self.udmf,
+            &self.things.read(),
+            &self.vertices.read(),
+            &self.linedefs.read(),
+            &self.sidedefs.read(),
+            &self.sectors.read(),
+        );
+        let mut lumps = vec![Lump::new("TEXTMAP", textmap.into_bytes())];
+        for (name, data) in [("BEHAVIOR", &self.behavior_data), ("SCRIPTS", &self.scripts_data)] {
+            let data = data.read();
+            if !data.is_empty() {
+                lumps.push(Lump::new(name, data.clone()));
+            }
+        }
+        lumps.push(Lump::marker("ENDMAP"));
+        lumps
+    }
+
+    /// Writes the document as a WAD.
+    ///
+    /// If the level was loaded from a WAD, the file is rebuilt from `directory`
+    /// and `wad_data`: only the current level's lumps are replaced and every other
+    /// lump is copied byte for byte, in its original order. A brand new document
+    /// is written as a standalone PWAD.
+    ///
+    /// Afterwards `wad_data` and `directory` describe the saved file, so other
+    /// levels can still be loaded and the next save starts from what is on disk.
+    pub fn save_wad<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let marker = self.level_marker_name();
+        let lumps = self.rebuild_lumps(&marker)?;
+
+        let mut cursor = Cursor::new(Vec::new());
+        write_wad(&mut cursor, &self.wad_ident(), &lumps)?;
+        let data = cursor.into_inner();
+        writer.write_all(&data)?;
+        writer.flush()?;
+
+        self.set_wad_data(data)?;
+        *self.selected_level.write() = Some(marker.clone());
+
+        info!("Saved level {} ({} lumps in WAD)", marker, lumps.len());
+        Ok(())
+    }
+
+    /// The full lump list for the saved file, with the level `marker` replaced by
+    /// the current geometry. A level missing from the source WAD is appended.
+    fn rebuild_lumps(&self, marker: &str) -> io::Result<Vec<Lump>> {
+        let generated = self.level_lumps()?;
+        let mut lumps = self.lumps();
+
+        let level_start = {
+            let directory = self.directory.read();
+            directory.iter().enumerate().position(|(i, entry)| {
+                entry.name.eq_ignore_ascii_case(marker)
+                    && (entry.size == 0 || Self::level_lump_count(&directory, i) > 0)
+            }).map(|start| (start, start + 1 + Self::level_lump_count(&directory, start)))
+        };
+
+        match level_start {
+            Some((start, end)) => {
+                let original = lumps.drain(start + 1..end).collect();
+                let merged = merge_level_lumps(original, generated);
+                lumps.splice(start + 1..start + 1, merged);
+            }
+            None => {
+                lumps.push(Lump::marker(marker));
+                lumps.extend(generated);
+            }
+        }
+        Ok(lumps)
+    }
+
+    /// "IWAD" if the document was loaded from an IWAD, otherwise "PWAD".
+    fn wad_ident(&self) -> [u8; 4] {
+        match self.header_data.read().get(0..4) {
+            Some(b"IWAD") => *b"IWAD",
+            _ => *b"PWAD",
+        }
+    }
+
+    /// Makes `data` the document's WAD image: re-reads the directory and levels from it.
+    fn set_wad_data(&self, data: Vec<u8>) -> io::Result<()> {
+        let (header, directory) = parse_directory(&data)?;
+        *self.levels.write() = Self::group_levels(&directory);
+        *self.directory.write() = directory;
+        *self.header_data.write() = header.to_vec();
+        *self.wad_data.write() = Some(data.into());
+        self.invalidate_caches();
+        Ok(())
+    }
+
+    /// Re-reads what is cached from the directory after it changed.
+    fn invalidate_caches(&self) {
+        *self.namespaces.write() = classify_namespaces(&self.directory.read());
+        *self.shading_cache.write() = None;
+        *self.revision.write() += 1;
+    }
+
+    /// To be called when the resource stack changed: forgets the decoded
+    /// palette and re-applies DEHACKED patches.
+    pub fn resources_changed(&self) {
+        *self.shading_cache.write() = None;
+        *self.revision.write() += 1;
+        self.rebuild_thing_catalog();
+    }
+
+    /// Changes whenever a lump is added, replaced, renamed, moved or deleted,
+    /// another WAD is loaded, or the resource stack changes.
+    pub fn revision(&self) -> u64 {
+        *self.revision.read()
+    }
+
+    // --- Lump Management ---
+    //
+    // Edits to the directory rebuild `wad_data` in memory, so `directory`
+    // always describes it and levels keep loading as before. The file on
+    // disk only changes on save.
+
+    /// Every lump of the loaded WAD in directory order, copied out of `wad_data`.
+    pub fn lumps(&self) -> Vec<Lump> {
+        let wad_data = self.wad_data.read();
+        let data = match wad_data.as_ref() {
+            Some(data) => data,
+            None => return Vec::new(),
+        };
+        self.directory
+            .read()
+            .iter()
+            .map(|entry| {
+                let start = entry.offset as usize;
+                Lump::new(entry.name.clone(), data[start..start + entry.size as usize].to_vec())
+            })
+            .collect()
+    }
+
+    /// The contents of the lump at `index` in the directory.
+    pub fn lump_data(&self, index: usize) -> Option<Vec<u8>> {
+        let wad_data = self.wad_data.read();
+        let entry = self.directory.read().get(index)?.clone();
+        let start = entry.offset as usize;
+        Some(wad_data.as_ref()?[start..start + entry.size as usize].to_vec())
+    }
+
+    /// Inserts a lump at `index` (clamped to the end of the directory).
+    pub fn insert_lump(&mut self, index: usize, name: &str, data: Vec<u8>) -> io::Result<()> {
+        let name = checked_lump_name(name)?;
+        let mut lumps = self.lumps();
+        let index = index.min(lumps.len());
+        lumps.insert(index, Lump::new(name, data));
+        self.replace_lumps(&lumps)
+    }
+
+    /// Renames the lump at `index`. Renaming the selected level's marker
+    /// moves the selection with it.
+    pub fn rename_lump(&mut self, index: usize, name: &str) -> io::Result<()> {
+        let name = checked_lump_name(name)?;
+        let mut lumps = self.lumps();
+        let lump = lumps.get_mut(index).ok_or_else(|| lump_not_found(index))?;
+        lump.name = name.clone();
+        let selected = self.selected_marker(index);
+        self.replace_lumps(&lumps)?;
+        if selected {
+            *self.selected_level.write() = Some(name);
+        }
+        Ok(())
+    }
+
+    /// Whether the lump at `index` is the marker of the selected level.
+    fn selected_marker(&self, index: usize) -> bool {
+        let selected = self.selected_level.read();
+        self.levels.read().iter().any(|level| {
+            level.lump_indices.first() == Some(&index)
+                && selected.as_ref().is_some_and(|name| name.eq_ignore_ascii_case(&level.name))
+        })
+    }
+
+    /// Appends an empty level called `name`: a marker followed by empty
+    /// THINGS, LINEDEFS, SIDEDEFS, VERTEXES and SECTORS lumps.
+    pub fn create_level(&mut self, name: &str) -> io::Result<()> {
+        let name = checked_lump_name(name)?;
+        self.check_level_free(&name)?;
+        let mut lumps = self.lumps();
+        lumps.push(Lump::marker(name));
+        lumps.extend(["THINGS", "LINEDEFS", "SIDEDEFS", "VERTEXES", "SECTORS"].map(|n| Lump::new(n, Vec::new())));
+        self.replace_lumps(&lumps)
+    }
+
+    /// Renames the marker of level `old`. The selection follows the rename.
+    pub fn rename_level(&mut self, old: &str, new: &str) -> io::Result<()> {
+        let new = checked_lump_name(new)?;
+        if !old.eq_ignore_ascii_case(&new) {
+            self.check_level_free(&new)?;
+        }
+        let marker = self
+            .levels
+            .read()
+            .iter()
+            .find(|level| level.name.eq_ignore_ascii_case(old))
+            .map(|level| level.lump_indices[0])
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Level {} not found", old)))?;
+        self.rename_lump(marker, &new)
+    }
+
+    fn check_level_free(&self, name: &str) -> io::Result<()> {
+        if self.levels.read().iter().any(|level| level.name.eq_ignore_ascii_case(name)) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("Level {} already exists", name)));
+        }
+        Ok(())
+    }
+
+    /// Moves the lump at `from` so that it ends up at index `to`.
+    pub fn move_lump(&mut self, from: usize, to: usize) -> io::Result<()> {
+        let mut lumps = self.lumps();
+        if from >= lumps.len() || to >= lumps.len() {
+            return Err(lump_not_found(from.max(to)));
+        }
+        let lump = lumps.remove(from);
+        lumps.insert(to, lump);
+        self.replace_lumps(&lumps)
+    }
+
+    /// Deletes the lump at `index`. Deleting the selected level's marker
+    /// clears the selection, so the level is not saved back under it.
+    pub fn delete_lump(&mut self, index: usize) -> io::Result<()> {
+        let mut lumps = self.lumps();
+        if index >= lumps.len() {
+            return Err(lump_not_found(index));
+        }
+        lumps.remove(index);
+        let selected = self.selected_marker(index);
+        self.replace_lumps(&lumps)?;
+        if selected {
+            *self.selected_level.write() = None;
+        }
+        Ok(())
+    }
+
+    /// Stores `data` as `name` in the flats or patches namespace, replacing
+    /// a lump of that name already there. New lumps go after the last lump of
+    /// the namespace, or into a new FF_START/FF_END (PP_START/PP_END) range
+    /// at the end of the WAD, which the game merges with the IWAD's.
+    pub fn put_namespace_lump(&mut self, namespace: Namespace, name: &str, data: Vec<u8>) -> io::Result<()> {
+        let name = checked_lump_name(name)?;
+        let (start, end) = match namespace {
+            Namespace::Flats => ("FF_START", "FF_END"),
+            Namespace::Patches => ("PP_START", "PP_END"),
+            _ => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                    format!("Cannot add lumps to the {:?} namespace", namespace)));
+            }
+        };
+        let namespaces = self.namespaces.read().clone();
+        let mut lumps = self.lumps();
+        let existing = lumps
+            .iter()
+            .zip(&namespaces)
+            .rposition(|(lump, &ns)| ns == namespace && lump.name.eq_ignore_ascii_case(&name));
+        if let Some(index) = existing {
+            lumps[index].data = data;
+        } else if let Some(index) = namespaces.iter().rposition(|&ns| ns == namespace) {
+            lumps.insert(index + 1, Lump::new(name, data));
+        } else {
+            lumps.push(Lump::marker(start));
+            lumps.push(Lump::new(name, data));
+            lumps.push(Lump::marker(end));
+        }
+        self.replace_lumps(&lumps)
+    }
+
+    /// Looks up a lump outside any namespace, such as PLAYPAL or TEXTURE1.
+    /// The edited WAD is treated as loaded last, so its lumps override the
+    /// resource stack.
+    pub fn find_lump(&self, name: &str) -> Option<Vec<u8>> {
+        self.find_in_namespace(name, Namespace::Global)
+    }
+
+    /// Looks up a lump in a marker namespace (flats, sprites, patches), first
+    /// in the edited WAD and then in the resource stack.
+    pub fn find_in_namespace(&self, name: &str, namespace: Namespace) -> Option<Vec<u8>> {
+        let own = {
+            let directory = self.directory.read();
+            let namespaces = self.namespaces.read();
+            directory
+                .iter()
+                .zip(namespaces.iter())
+                .rposition(|(entry, &ns)| ns == namespace && entry.name.eq_ignore_ascii_case(name))
+        };
+        if let Some(index) = own {
+            return self.lump_data(index);
+        }
+        self.resources.read().find_in_namespace(name, namespace).map(<[u8]>::to_vec)
+    }
+
+    /// PLAYPAL and COLORMAP, from the edited WAD or the resource stack.
+    /// They are decoded once and kept until either changes.
+    pub fn shading(&self) -> Result<Arc<Shading>, PaletteError> {
+        if let Some(shading) = self.shading_cache.read().as_ref() {
+            return Ok(Arc::clone(shading));
+        }
+        let playpal = self.find_lump("PLAYPAL").ok_or(PaletteError::Missing("PLAYPAL"))?;
+        let colormap = self.find_lump("COLORMAP").ok_or(PaletteError::Missing("COLORMAP"))?;
+        let shading = Arc::new(Shading::from_lumps(&playpal, &colormap)?);
+        *self.shading_cache.write() = Some(Arc::clone(&shading));
+        Ok(shading)
+    }
+
+    /// PNAMES, TEXTURE1 and TEXTURE2, from the edited WAD or the resource stack.
+    pub fn texture_set(&self) -> Result<TextureSet, TextureError> {
+        let pnames = self.find_lump("PNAMES").ok_or(TextureError::Missing("PNAMES"))?;
+        let texture1 = self.find_lump("TEXTURE1").ok_or(TextureError::Missing("TEXTURE1"))?;
+        let texture2 = self.find_lump("TEXTURE2");
+        TextureSet::from_lumps(&pnames, &texture1, texture2.as_deref())
+    }
+
+    /// A patch lump. Patches are looked for between P_START and P_END first,
+    /// then anywhere, since the game accepts both.
+    pub fn find_patch(&self, name: &str) -> Option<Vec<u8>> {
+        self.find_in_namespace(name, Namespace::Patches)
+            .or_else(|| self.find_lump(name))
+    }
+
+    /// The wall texture `name` (as used by `SideDef`) as an RGBA image.
+    pub fn composite_texture(&self, name: &str) -> Result<RgbaImage, TextureError> {
+        let shading = self.shading()?;
+        self.texture_set()?
+            .composite(name, shading.palette(), |patch| self.find_patch(patch))
+    }
+
+    /// Every flat name available, from the resource stack and the edited
+    /// WAD (including nested FF_START/F1_START ranges), sorted.
+    pub fn flat_names(&self) -> Vec<String> {
+        let mut names = self.resources.read().namespace_names(Namespace::Flats);
+        {
+            let directory = self.directory.read();
+            let namespaces = self.namespaces.read();
+            for (entry, &ns) in directory.iter().zip(namespaces.iter()) {
+                if ns == Namespace::Flats && entry.size > 0 {
+                    names.push(entry.name.to_ascii_uppercase());
+                }
+            }
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// The flat `name` (as used by `Sector`) as an RGBA image.
+    pub fn flat_image(&self, name: &str) -> Result<RgbaImage, TextureError> {
+        let shading = self.shading()?;
+        let data = self
+            .find_in_namespace(name, Namespace::Flats)
+            .ok_or_else(|| TextureError::UnknownTexture(name.to_string()))?;
+        decode_flat(name, &data, shading.palette())
+    }
+
+    /// Rebuilds `thing_catalog` from the stock things and every DEHACKED
+    /// lump. Patches stack: the resource WADs bottom to top, then the edited
+    /// WAD's own.
+    pub fn rebuild_thing_catalog(&self) {
+        let mut patches: Vec<Vec<u8>> = self
+            .resources
+            .read()
+            .find_all("DEHACKED")
+            .into_iter()
+            .map(<[u8]>::to_vec)
+            .collect();
+        let own = self
+            .directory
+            .read()
+            .iter()
+            .rposition(|entry| entry.name.eq_ignore_ascii_case("DEHACKED"));
+        if let Some(data) = own.and_then(|index| self.lump_data(index)) {
+            patches.push(data);
+        }
+
+        let mut catalog = ThingCatalog::stock();
+        for data in patches {
+            let patch = DehPatch::parse(&String::from_utf8_lossy(&data));
+            for warning in &patch.warnings {
+                warn!("DEHACKED: {}", warning);
+            }
+            catalog.apply(&patch);
+        }
+        *self.thing_catalog.write() = catalog;
+    }
+
+    fn replace_lumps(&mut self, lumps: &[Lump]) -> io::Result<()> {
+        let mut cursor = Cursor::new(Vec::new());
+        write_wad(&mut cursor, &self.wad_ident(), lumps)?;
+        self.set_wad_data(cursor.into_inner())?;
+        self.dirty = true;
+        Ok(())
+    }
+
     // --- Sector relationships and geometry helper methods ---
 
     pub fn get_sector_from_side(&self, side: &SideDef) -> Option<Arc<Sector>> {
@@ -822,12 +1380,20 @@ impl Document {
         }
     }
 
-    // (Optional) For demonstration or testing
-    #[allow(unused)] // remove this if you use it
-    pub fn build_bsp(&self) -> Option<BspLevel> {
-        // This is just a stub for if you actually build a BSP.
-        // Return None or Some(BspLevel) as needed.
-        None
+    /// Builds the BSP tree of the current geometry. The builder gets a
+    /// document sharing this one's geometry, so nothing is copied.
+    pub fn build_bsp(&self) -> Result<BspLevel, String> {
+        let view = Document {
+            things: Arc::clone(&self.things),
+            vertices: Arc::clone(&self.vertices),
+            sectors: Arc::clone(&self.sectors),
+            sidedefs: Arc::clone(&self.sidedefs),
+            linedefs: Arc::clone(&self.linedefs),
+            ..Default::default()
+        };
+        let bsp = BspLevel::with_options(Arc::new(RwLock::new(view)), self.build_options.clone());
+        bsp.build()?;
+        Ok(bsp)
     }
 
     /// A small helper field for the "generate_test_map" example.
@@ -909,66 +1475,510 @@ impl Document {
 
         info!("Generated a simple test map.");
         self.map_name = "TESTMAP".into();
+    }
+}
 
-        // If you want to build a BSP right away, do it:
-        self.build_bsp();
+/// Lumps that make up a level in the classic layout, in vanilla order.
+const MAP_LUMP_NAMES: [&str; 12] = [
+    "THINGS", "LINEDEFS", "SIDEDEFS", "VERTEXES", "SEGS", "SSECTORS",
+    "NODES", "SECTORS", "REJECT", "BLOCKMAP", "BEHAVIOR", "SCRIPTS",
+];
+
+/// Record size of the fixed-layout level lumps that are loaded, if `name` is one.
+fn record_size(name: &str, format: MapFormat) -> Option<usize> {
+    match name {
+        "THINGS" if format != MapFormat::Udmf => Some(format.thing_size()),
+        "LINEDEFS" if format != MapFormat::Udmf => Some(format.linedef_size()),
+        "SIDEDEFS" => Some(30),
+        "VERTEXES" => Some(4),
+        "SECTORS" => Some(26),
+        _ => None,
     }
 }
 
+fn is_map_lump(name: &str) -> bool {
+    map_lump_rank(name).is_some()
+}
+
+fn map_lump_rank(name: &str) -> Option<usize> {
+    let name = name.trim();
+    MAP_LUMP_NAMES.iter().position(|n| name.eq_ignore_ascii_case(n))
+}
+
+fn checked_lump_name(name: &str) -> io::Result<String> {
+    let name = name.trim().to_ascii_uppercase();
+    if is_valid_lump_name(&name) {
+        Ok(name)
+    } else {
+        Err(io::Error::new(io::ErrorKind::InvalidInput,
+            format!("Invalid lump name '{}': use 1-8 printable ASCII characters", name)))
+    }
+}
+
+fn lump_not_found(index: usize) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("No lump at index {}", index))
+}
+
+fn read_lump_data<R: Read + Seek>(reader: &mut R, offset: i32, size: i32) -> io::Result<Vec<u8>> {
+    let mut buffer = vec![0u8; size as usize];
+    reader.seek(SeekFrom::Start(offset as u64))?;
+    reader.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Lumps built from the level's geometry. Once the geometry is written
+/// again, any of these that were not regenerated with it no longer match.
+const NODE_LUMP_NAMES: [&str; 6] = ["SEGS", "SSECTORS", "NODES", "REJECT", "BLOCKMAP", "ZNODES"];
+
+/// Replaces lumps in `original` with the same-named ones from `generated`.
+/// Generated lumps the level did not have yet are inserted at their vanilla
+/// position (before ENDMAP in UDMF levels). Stale node lumps that `generated`
+/// has no replacement for are dropped.
+fn merge_level_lumps(original: Vec<Lump>, generated: Vec<Lump>) -> Vec<Lump> {
+    let mut merged = original;
+    merged.retain(|l| {
+        !NODE_LUMP_NAMES.iter().any(|n| l.name.eq_ignore_ascii_case(n))
+            || generated.iter().any(|g| g.name.eq_ignore_ascii_case(&l.name))
+    });
+    for lump in generated {
+        if let Some(existing) = merged.iter_mut().find(|l| l.name.eq_ignore_ascii_case(&lump.name)) {
+            *existing = lump;
+        } else {
+            let rank = map_lump_rank(&lump.name).unwrap_or(MAP_LUMP_NAMES.len());
+            let pos = merged
+                .iter()
+                .position(|l| match map_lump_rank(&l.name) {
+                    Some(r) => r > rank,
+                    None => l.name.eq_ignore_ascii_case("ENDMAP"),
+                })
+                .unwrap_or(merged.len());
+            merged.insert(pos, lump);
+        }
+    }
+    merged
+}
+
+/// Serializes a list of map objects back-to-back using their `to_wad` writer.
+fn encode_lump<T, F>(items: &[Arc<T>], write: F) -> io::Result<Vec<u8>>
+where
+    F: Fn(&T, &mut Cursor<Vec<u8>>) -> io::Result<()>,
+{
+    let mut cursor = Cursor::new(Vec::new());
+    for item in items {
+        write(item, &mut cursor)?;
+    }
+    Ok(cursor.into_inner())
+}
+
 // --- Checksum helper functions ---
+//
+// These follow Eureka's e_basis.cc field by field. Eureka feeds 32-bit values
+// most significant byte first, texture names byte by byte, and coordinates
+// in its fixed point form with 12 fraction bits.
+
+/// Eureka's fixed point scale for map coordinates.
+const EUREKA_FRAC_UNIT: i32 = 4096;
+
+fn add_crc(crc: &mut Adler32, value: i32) {
+    crc.update(&value.to_be_bytes());
+}
+
+fn add_crc_coord(crc: &mut Adler32, value: i32) {
+    add_crc(crc, value.wrapping_mul(EUREKA_FRAC_UNIT));
+}
 
-fn add_crc(crc: &mut u32, value: i32) {
-    *crc = crc.wrapping_add(value as u32);
+fn add_crc_name(crc: &mut Adler32, name: &str) {
+    crc.update(name.as_bytes());
 }
 
-fn checksum_thing(crc: &mut u32, thing: &Thing) {
-    add_crc(crc, thing.x);
-    add_crc(crc, thing.y);
+fn checksum_thing(crc: &mut Adler32, thing: &Thing) {
+    add_crc_coord(crc, thing.x);
+    add_crc_coord(crc, thing.y);
     add_crc(crc, thing.angle);
     add_crc(crc, thing.doom_type);
     add_crc(crc, thing.flags);
 }
 
-fn checksum_vertex(crc: &mut u32, vertex: &Vertex) {
-    add_crc(crc, vertex.x);
-    add_crc(crc, vertex.y);
+fn checksum_vertex(crc: &mut Adler32, vertex: &Vertex) {
+    add_crc_coord(crc, vertex.x);
+    add_crc_coord(crc, vertex.y);
 }
 
-fn checksum_sector(crc: &mut u32, sector: &Sector) {
+fn checksum_sector(crc: &mut Adler32, sector: &Sector) {
     add_crc(crc, sector.floor_height);
     add_crc(crc, sector.ceiling_height);
     add_crc(crc, sector.light);
     add_crc(crc, sector.r#type);
     add_crc(crc, sector.tag);
-    for byte in sector.floor_tex.as_bytes() {
-        add_crc(crc, *byte as i32);
-    }
-    for byte in sector.ceiling_tex.as_bytes() {
-        add_crc(crc, *byte as i32);
-    }
+    add_crc_name(crc, &sector.floor_tex);
+    add_crc_name(crc, &sector.ceiling_tex);
 }
 
-fn checksum_sidedef(crc: &mut u32, sidedef: &SideDef) {
+fn checksum_sidedef(crc: &mut Adler32, sidedef: &SideDef, sectors: &[Arc<Sector>]) {
     add_crc(crc, sidedef.x_offset);
     add_crc(crc, sidedef.y_offset);
-    for byte in sidedef.upper_tex.as_bytes() {
-        add_crc(crc, *byte as i32);
-    }
-    for byte in sidedef.lower_tex.as_bytes() {
-        add_crc(crc, *byte as i32);
+    add_crc_name(crc, &sidedef.lower_tex);
+    add_crc_name(crc, &sidedef.mid_tex);
+    add_crc_name(crc, &sidedef.upper_tex);
+    if let Some(sector) = usize::try_from(sidedef.sector).ok().and_then(|i| sectors.get(i)) {
+        checksum_sector(crc, sector);
     }
-    for byte in sidedef.mid_tex.as_bytes() {
-        add_crc(crc, *byte as i32);
-    }
-    add_crc(crc, sidedef.sector);
 }
 
-fn checksum_linedef(crc: &mut u32, linedef: &LineDef, _doc: &Document) {
+fn checksum_linedef(
+    crc: &mut Adler32,
+    linedef: &LineDef,
+    vertices: &[Arc<Vertex>],
+    sidedefs: &[Arc<SideDef>],
+    sectors: &[Arc<Sector>],
+) {
     add_crc(crc, linedef.flags);
     add_crc(crc, linedef.line_type);
     add_crc(crc, linedef.tag);
-    add_crc(crc, linedef.start as i32);
-    add_crc(crc, linedef.end as i32);
-    add_crc(crc, linedef.right);
-    add_crc(crc, linedef.left);
+    for index in [linedef.start, linedef.end] {
+        if let Some(vertex) = vertices.get(index) {
+            checksum_vertex(crc, vertex);
+        }
+    }
+    for side in [linedef.right, linedef.left] {
+        if let Some(sidedef) = usize::try_from(side).ok().and_then(|i| sidedefs.get(i)) {
+            checksum_sidedef(crc, sidedef, sectors);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_wad_round_trip() {
+        let mut doc = Document::new();
+        doc.generate_test_map();
+        doc.map_name = "MAP01".into();
+
+        let mut cursor = Cursor::new(Vec::new());
+        doc.save_wad(&mut cursor).unwrap();
+
+        let mut loaded = Document::new();
+        cursor.set_position(0);
+        loaded.load_wad(&mut cursor).unwrap();
+
+        assert_eq!(loaded.available_levels(), vec!["MAP01".to_string()]);
+        assert_eq!(*loaded.things.read(), *doc.things.read());
+        assert_eq!(*loaded.vertices.read(), *doc.vertices.read());
+        assert_eq!(*loaded.linedefs.read(), *doc.linedefs.read());
+        assert_eq!(*loaded.sidedefs.read(), *doc.sidedefs.read());
+        assert_eq!(*loaded.sectors.read(), *doc.sectors.read());
+    }
+
+    #[test]
+    fn test_load_keeps_unused_vertices() {
+        let mut doc = Document::new();
+        doc.generate_test_map();
+        doc.map_name = "MAP01".into();
+        // Past every linedef vertex, but not made by a split.
+        doc.add_vertex(1000, 1000);
+
+        let mut cursor = Cursor::new(Vec::new());
+        doc.save_wad(&mut cursor).unwrap();
+        let mut loaded = Document::new();
+        cursor.set_position(0);
+        loaded.load_wad(&mut cursor).unwrap();
+        assert_eq!(*loaded.vertices.read(), *doc.vertices.read());
+
+        // Unreadable nodes cannot tell which vertices are split ones, so all stay.
+        let mut lumps = vec![Lump::marker("MAP01")];
+        lumps.extend(doc.level_lumps().unwrap());
+        lumps.iter_mut().find(|l| l.name == "NODES").unwrap().data = b"XNOD".to_vec();
+        let mut cursor = Cursor::new(Vec::new());
+        write_wad(&mut cursor, b"PWAD", &lumps).unwrap();
+        let mut loaded = Document::new();
+        cursor.set_position(0);
+        loaded.load_wad(&mut cursor).unwrap();
+        assert_eq!(*loaded.vertices.read(), *doc.vertices.read());
+    }
+
+    #[test]
+    fn test_bad_lump_size_report() {
+        let mut source = Document::new();
+        source.generate_test_map();
+        let mut lumps = vec![Lump::marker("MAP01")];
+        lumps.extend(source.level_lumps().unwrap());
+        let sectors = lumps.iter_mut().find(|l| l.name == "SECTORS").unwrap();
+        sectors.data.push(0);
+        let mut cursor = Cursor::new(Vec::new());
+        write_wad(&mut cursor, b"PWAD", &lumps).unwrap();
+
+        let mut lenient = Document::new();
+        cursor.set_position(0);
+        let report = lenient.load_wad(&mut cursor).unwrap();
+        assert_eq!(report.len(), 1);
+        assert!(matches!(&report.warnings[0], WadWarning::BadLumpSize { lump, record: 26, .. } if lump == "SECTORS"));
+        assert_eq!(*lenient.sectors.read(), *source.sectors.read());
+
+        let mut strict = Document::new();
+        strict.load_mode = LoadMode::Strict;
+        cursor.set_position(0);
+        assert!(matches!(strict.load_wad(&mut cursor), Err(WadError::Strict(_))));
+    }
+
+    #[test]
+    fn test_structural_level_detection() {
+        let entry = |name: &str| LumpEntry::new(0, 0, name);
+        let directory: Vec<LumpEntry> = [
+            "PLAYPAL", "TESTMAP", "THINGS", "LINEDEFS", "SIDEDEFS", "VERTEXES", "SECTORS",
+            "E1M10", "TEXTMAP", "ZNODES", "ENDMAP",
+            "MAP100", "THINGS", "LINEDEFS", "BEHAVIOR",
+            "F_START", "FLOOR0_1", "F_END",
+        ].iter().map(|n| entry(n)).collect();
+
+        let levels = Document::group_levels(&directory);
+        let names: Vec<&str> = levels.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, vec!["TESTMAP", "E1M10", "MAP100"]);
+        assert_eq!(levels[0].lump_indices, (1..=6).collect::<Vec<_>>());
+        assert_eq!(levels[1].lump_indices, (7..=10).collect::<Vec<_>>());
+        assert_eq!(levels[2].lump_indices, (11..=14).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_create_and_rename_level() {
+        let mut doc = Document::new();
+        doc.create_level("testmap").unwrap();
+        doc.create_level("E1M10").unwrap();
+        assert!(doc.create_level("TESTMAP").is_err());
+        assert_eq!(doc.available_levels(), vec!["TESTMAP", "E1M10"]);
+
+        *doc.selected_level.write() = Some("E1M10".into());
+        doc.rename_level("E1M10", "MAP100").unwrap();
+        assert!(doc.rename_level("MAP100", "TESTMAP").is_err());
+        assert_eq!(doc.available_levels(), vec!["TESTMAP", "MAP100"]);
+        assert_eq!(doc.level_marker_name(), "MAP100");
+
+        // The lump manager's edits to the marker carry over to the selection too.
+        doc.rename_lump(6, "MAP07").unwrap();
+        assert_eq!(doc.level_marker_name(), "MAP07");
+        doc.rename_lump(0, "MAP01").unwrap();
+        assert_eq!(doc.level_marker_name(), "MAP07");
+        doc.delete_lump(6).unwrap();
+        assert_eq!(*doc.selected_level.read(), None);
+    }
+
+    #[test]
+    fn test_save_keeps_other_lumps() {
+        let mut source = Document::new();
+        source.generate_test_map();
+        let level = source.level_lumps().unwrap();
+
+        let mut lumps = vec![Lump::new("PLAYPAL", vec![7; 32]), Lump::marker("MAP01")];
+        lumps.extend(level.iter().cloned());
+        // A stale table, rebuilt on save.
+        lumps.iter_mut().find(|l| l.name == "REJECT").unwrap().data = vec![0xAA; 3];
+        lumps.push(Lump::marker("MAP02"));
+        lumps.extend(level.iter().cloned());
+        lumps.push(Lump::new("D_RUNNIN", vec![1, 2, 3, 4, 5]));
+        let mut cursor = Cursor::new(Vec::new());
+        write_wad(&mut cursor, b"PWAD", &lumps).unwrap();
+
+        let mut doc = Document::new();
+        cursor.set_position(0);
+        doc.load_wad(&mut cursor).unwrap();
+        assert_eq!(doc.available_levels(), vec!["MAP01".to_string(), "MAP02".to_string()]);
+        doc.add_thing(64, 64, 90, 3001, 7);
+
+        let mut out = Vec::new();
+        doc.save_wad(&mut out).unwrap();
+        let (_, saved) = parse_directory(&out).unwrap();
+        let lump_data = |name: &str, nth: usize| {
+            let entry = saved.iter().filter(|e| e.name == name).nth(nth).unwrap();
+            out[entry.offset as usize..(entry.offset + entry.size) as usize].to_vec()
+        };
+
+        let names: Vec<&str> = saved.iter().map(|e| e.name.as_str()).collect();
+        let original: Vec<&str> = lumps.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, original);
+        assert_eq!(lump_data("PLAYPAL", 0), vec![7; 32]);
+        assert_eq!(lump_data("REJECT", 0), vec![0]);
+        assert_eq!(lump_data("REJECT", 1), vec![0]);
+        assert_eq!(lump_data("D_RUNNIN", 0), vec![1, 2, 3, 4, 5]);
+        assert_eq!(lump_data("THINGS", 0).len(), level[0].data.len() + 10);
+        assert_eq!(lump_data("THINGS", 1), level[0].data);
+
+        // The document now tracks the saved file, so MAP02 still loads.
+        let wad = doc.wad_data.read().clone().unwrap();
+        tokio::runtime::Runtime::new().unwrap()
+            .block_on(doc.load_level_async("MAP02", &mut Cursor::new(wad)))
+            .unwrap();
+        assert_eq!(*doc.things.read(), *source.things.read());
+
+        // Without lines, the old node lumps are emptied rather than kept.
+        doc.linedefs.write().clear();
+        let mut out = Vec::new();
+        doc.save_wad(&mut out).unwrap();
+        let (_, saved) = parse_directory(&out).unwrap();
+        let sizes: Vec<(&str, i32)> = saved.iter()
+            .filter(|e| ["SEGS", "NODES", "BLOCKMAP"].contains(&e.name.as_str()))
+            .map(|e| (e.name.as_str(), e.size))
+            .collect();
+        assert!(sizes[..3].iter().all(|&(_, size)| size > 0));
+        assert_eq!(sizes[3..], [("SEGS", 0), ("NODES", 0), ("BLOCKMAP", 0)]);
+
+        // Nodes that cannot be built leave them empty too, but still save the level.
+        doc.linedefs.write().extend(source.linedefs.read().iter().cloned());
+        let far = Vertex::new(40_000, 0);
+        doc.vertices.write()[0] = Arc::new(far);
+        let lumps = doc.level_lumps().unwrap();
+        let size = |name: &str| lumps.iter().find(|l| l.name == name).unwrap().data.len();
+        assert_eq!((size("LINEDEFS"), size("SEGS"), size("NODES")), (source.linedefs.read().len() * 14, 0, 0));
+    }
+
+    #[test]
+    fn test_hexen_round_trip() {
+        let mut doc = Document::new();
+        doc.generate_test_map();
+        doc.map_name = "MAP01".into();
+        doc.map_format = MapFormat::Hexen;
+        *doc.behavior_data.write() = b"ACS\0\x08\0\0\0\0\0\0\0".to_vec();
+        {
+            let mut things = doc.things.write();
+            let mut thing = (*things[0]).clone();
+            thing.tid = 42;
+            thing.z = 16;
+            thing.special = 80;
+            thing.args = [1, 2, 3, 4, 5];
+            things[0] = Arc::new(thing);
+            let mut linedefs = doc.linedefs.write();
+            let mut linedef = (*linedefs[0]).clone();
+            linedef.line_type = 12;
+            linedef.args = [9, 8, 7, 6, 5];
+            linedefs[0] = Arc::new(linedef);
+        }
+
+        let mut cursor = Cursor::new(Vec::new());
+        doc.save_wad(&mut cursor).unwrap();
+
+        let mut loaded = Document::new();
+        cursor.set_position(0);
+        loaded.load_wad(&mut cursor).unwrap();
+
+        assert_eq!(loaded.map_format, MapFormat::Hexen);
+        assert_eq!(*loaded.things.read(), *doc.things.read());
+        assert_eq!(*loaded.linedefs.read(), *doc.linedefs.read());
+        assert_eq!(*loaded.behavior_data.read(), *doc.behavior_data.read());
+    }
+
+    #[test]
+    fn test_udmf_level_round_trip() {
+        let textmap = b"namespace = \"zdoom\";\n\
+            thing { x = 16.0; y = 32.0; type = 1; skill1 = true; user_tag = 7; }\n\
+            vertex { x = 0.0; y = 0.0; }\n\
+            vertex { x = 64.0; y = 0.0; }\n\
+            linedef { v1 = 0; v2 = 1; sidefront = 0; }\n\
+            sidedef { sector = 0; texturemiddle = \"STARTAN2\"; }\n\
+            sector { texturefloor = \"FLOOR0_1\"; textureceiling = \"CEIL1_1\"; }\n";
+        let lumps = vec![
+            Lump::marker("MAP01"),
+            Lump::new("TEXTMAP", textmap.to_vec()),
+            Lump::new("ZNODES", vec![1, 2, 3]),
+            Lump::new("BLOCKMAP", vec![4, 5]),
+            Lump::marker("ENDMAP"),
+            Lump::new("DEMO1", vec![9; 4]),
+        ];
+        let mut cursor = Cursor::new(Vec::new());
+        write_wad(&mut cursor, b"PWAD", &lumps).unwrap();
+
+        let mut doc = Document::new();
+        cursor.set_position(0);
+        doc.load_wad(&mut cursor).unwrap();
+        assert_eq!(doc.map_format, MapFormat::Udmf);
+        assert_eq!(doc.levels.read()[0].lump_indices, vec![0, 1, 2, 3, 4]);
+        assert_eq!(doc.things.read()[0].x, 16);
+        assert_eq!(doc.sidedefs.read()[0].mid_tex, "STARTAN2");
+        doc.add_thing(64, 64, 0, 3001, 7);
+
+        let mut out = Vec::new();
+        doc.save_wad(&mut out).unwrap();
+        let names: Vec<String> = doc.directory.read().iter().map(|e| e.name.clone()).collect();
+        // The old nodes and blockmap no longer match the edited geometry.
+        assert_eq!(names, vec!["MAP01", "TEXTMAP", "ENDMAP", "DEMO1"]);
+
+        let mut loaded = Document::new();
+        loaded.load_wad(&mut Cursor::new(out)).unwrap();
+        assert_eq!(loaded.udmf.namespace, "zdoom");
+        assert_eq!(loaded.things.read().len(), 2);
+        assert_eq!(loaded.things.read()[0].udmf.get("user_tag"), Some(&udmf::UdmfValue::Int(7)));
+        assert_eq!(loaded.things.read()[1].doom_type, 3001);
+    }
+
+    #[test]
+    fn test_level_checksum_order() {
+        let mut doc = Document::new();
+        doc.generate_test_map();
+        doc.add_thing(32, 32, 0, 3001, 7);
+        let checksum = doc.get_level_checksum();
+        assert_eq!(*doc.checksum.read(), checksum);
+
+        // Unused vertices do not count.
+        doc.add_vertex(999, 999);
+        assert_eq!(doc.get_level_checksum(), checksum);
+
+        // The same things in another order do.
+        doc.things.write().swap(0, 1);
+        assert_ne!(doc.get_level_checksum(), checksum);
+        doc.things.write().swap(0, 1);
+        assert_eq!(doc.get_level_checksum(), checksum);
+    }
+
+    #[test]
+    fn test_level_checksum_known_value() {
+        // One thing at (1, 0), angle 0, type 1, no flags: Eureka feeds the
+        // bytes 00 00 10 00 (x = 1 * 4096), eight zeros (y, angle), 00 00 00 01
+        // and four zeros. That makes a = 1 + 0x10 + 1 = 18 and
+        // b = 2 * 1 + 13 * 17 + 5 * 18 = 313.
+        let doc = Document::new();
+        doc.things.write().push(Arc::new(Thing::new(1, 0, 0, 1, 0)));
+        assert_eq!(doc.get_level_checksum(), (313 << 16) | 18);
+    }
+
+    #[test]
+    fn test_lump_management() {
+        let mut doc = Document::new();
+        doc.generate_test_map();
+        doc.map_name = "MAP01".into();
+        doc.save_wad(&mut Vec::new()).unwrap();
+
+        doc.insert_lump(0, "mapinfo", b"map MAP01 \"Test\"".to_vec()).unwrap();
+        doc.insert_lump(1, "DEHACKED", vec![1, 2]).unwrap();
+        assert!(doc.rename_lump(1, "TOO LONG NAME").is_err());
+        doc.rename_lump(1, "DEHSTUFF").unwrap();
+        doc.move_lump(0, 1).unwrap();
+        doc.delete_lump(0).unwrap();
+        assert!(doc.dirty);
+        assert_eq!(doc.available_levels(), vec!["MAP01".to_string()]);
+
+        let mut out = Vec::new();
+        doc.save_wad(&mut out).unwrap();
+        let (_, saved) = parse_directory(&out).unwrap();
+        assert_eq!(saved[0].name, "MAPINFO");
+        assert_eq!(saved[1].name, "MAP01");
+        assert_eq!(doc.lump_data(0).unwrap(), b"map MAP01 \"Test\"".to_vec());
+
+        doc.put_namespace_lump(Namespace::Flats, "slime1", vec![1; 4096]).unwrap();
+        doc.put_namespace_lump(Namespace::Flats, "SLIME2", vec![2; 4096]).unwrap();
+        let revision = doc.revision();
+        doc.put_namespace_lump(Namespace::Flats, "SLIME1", vec![3; 4096]).unwrap();
+        // Replacing a lump in place still counts as a change.
+        assert_ne!(doc.revision(), revision);
+        assert!(doc.put_namespace_lump(Namespace::Global, "SLIME3", Vec::new()).is_err());
+        let names: Vec<String> = doc.directory.read().iter().rev().take(4).map(|e| e.name.clone()).collect();
+        assert_eq!(names, ["FF_END", "SLIME2", "SLIME1", "FF_START"]);
+        assert_eq!(doc.find_in_namespace("SLIME1", Namespace::Flats), Some(vec![3; 4096]));
+
+        // The decoded palette is kept until the directory changes.
+        doc.insert_lump(0, "PLAYPAL", vec![0; 768 * 14]).unwrap();
+        doc.insert_lump(1, "COLORMAP", vec![0; 256 * 34]).unwrap();
+        assert!(Arc::ptr_eq(&doc.shading().unwrap(), &doc.shading().unwrap()));
+        doc.delete_lump(0).unwrap();
+        assert_eq!(doc.shading(), Err(PaletteError::Missing("PLAYPAL")));
+    }
 }
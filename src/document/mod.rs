@@ -1,5 +1,15 @@
 // src/document/mod.rs
+pub mod archive;
+pub mod behavior;
+pub mod dehconsts;
+pub mod diagnostics;
+pub mod diff;
 mod document;
+pub mod overview;
+pub mod palette;
+pub mod resources;
+pub mod textures;
+pub mod wad;
 
 // Re-export everything (or selectively export only what you need).
 pub use self::document::{Document, ObjType, Side}; // Removed map-specific types
\ No newline at end of file
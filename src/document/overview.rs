@@ -0,0 +1,438 @@
+// src/document/overview.rs
+
+//! Map overviews drawn on the CPU, without a window or an egui context.
+//!
+//! An [`Overview`] collects the drawable geometry of a level once; it can
+//! then be rasterized to an RGBA image (saved as PNG or TGA) or written as an
+//! SVG document for printing. One-sided and two-sided linedefs are styled
+//! apart, things are drawn as dots sized by their radius, and sectors can be
+//! filled with a shade that follows their light level.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use image::{Rgba, RgbaImage};
+
+use crate::document::Document;
+
+/// Size and colors of an overview. Colors are RGBA.
+#[derive(Debug, Clone)]
+pub struct OverviewOptions {
+    /// Output size in pixels. The map is scaled to fit and centered.
+    pub width: u32,
+    pub height: u32,
+    /// Space kept clear around the map, in pixels.
+    pub margin: u32,
+    pub background: [u8; 4],
+    pub one_sided: [u8; 4],
+    pub two_sided: [u8; 4],
+    /// Stroke width of one-sided lines in pixels. Two-sided lines use half
+    /// of it, but at least one pixel.
+    pub line_width: f32,
+    pub things: bool,
+    pub thing_color: [u8; 4],
+    pub sector_fills: bool,
+    /// Fill color of a fully lit sector; darker sectors get darker fills.
+    pub sector_color: [u8; 4],
+}
+
+impl Default for OverviewOptions {
+    /// Black lines on white, suitable for printing.
+    fn default() -> Self {
+        Self {
+            width: 1024,
+            height: 1024,
+            margin: 16,
+            background: [255, 255, 255, 255],
+            one_sided: [0, 0, 0, 255],
+            two_sided: [150, 150, 150, 255],
+            line_width: 2.0,
+            things: true,
+            thing_color: [200, 40, 40, 255],
+            sector_fills: false,
+            sector_color: [205, 210, 225, 255],
+        }
+    }
+}
+
+/// A level's geometry, ready to draw. Coordinates are map units.
+#[derive(Debug, Clone, Default)]
+pub struct Overview {
+    vertices: Vec<[f64; 2]>,
+    /// Start vertex, end vertex and whether the line has two sides.
+    lines: Vec<(usize, usize, bool)>,
+    /// Boundary edges (vertex pairs) and light level of each sector.
+    sectors: Vec<(Vec<(usize, usize)>, i32)>,
+    /// Position and radius of each thing.
+    things: Vec<([f64; 2], f64)>,
+}
+
+/// Maps map coordinates to image pixels (y pointing down).
+struct Transform {
+    min: [f64; 2],
+    scale: f64,
+    left: f64,
+    bottom: f64,
+}
+
+impl Transform {
+    fn apply(&self, p: [f64; 2]) -> [f64; 2] {
+        [
+            self.left + (p[0] - self.min[0]) * self.scale,
+            self.bottom - (p[1] - self.min[1]) * self.scale,
+        ]
+    }
+}
+
+impl Overview {
+    /// Collects the loaded level of `doc`. Linedefs with missing vertices are
+    /// left out; thing radii come from the document's thing catalog.
+    pub fn from_document(doc: &Document) -> Self {
+        let vertices: Vec<[f64; 2]> = doc.vertices.read().iter().map(|v| [v.x as f64, v.y as f64]).collect();
+        let sidedefs = doc.sidedefs.read();
+        let side_sector = |side: i32| -> Option<usize> {
+            let side = sidedefs.get(usize::try_from(side).ok()?)?;
+            usize::try_from(side.sector).ok()
+        };
+
+        let mut sectors: Vec<(Vec<(usize, usize)>, i32)> =
+            doc.sectors.read().iter().map(|sector| (Vec::new(), sector.light)).collect();
+        let mut lines = Vec::new();
+        for line in doc.linedefs.read().iter() {
+            if line.start >= vertices.len() || line.end >= vertices.len() {
+                continue;
+            }
+            lines.push((line.start, line.end, line.right >= 0 && line.left >= 0));
+            // A line with the same sector on both sides is not a boundary.
+            let (right, left) = (side_sector(line.right), side_sector(line.left));
+            if right != left {
+                for sector in [right, left].into_iter().flatten() {
+                    if let Some((edges, _)) = sectors.get_mut(sector) {
+                        edges.push((line.start, line.end));
+                    }
+                }
+            }
+        }
+
+        let catalog = doc.thing_catalog.read();
+        let things = doc
+            .things
+            .read()
+            .iter()
+            .map(|thing| {
+                let radius = catalog.find(thing.doom_type).map_or(16, |def| def.radius);
+                ([thing.x as f64, thing.y as f64], radius as f64)
+            })
+            .collect();
+
+        Self { vertices, lines, sectors, things }
+    }
+
+    /// True if there is nothing to draw.
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty() && self.things.is_empty()
+    }
+
+    /// Draws the overview into a new image.
+    pub fn render(&self, options: &OverviewOptions) -> RgbaImage {
+        let mut image = RgbaImage::from_pixel(options.width, options.height, Rgba(options.background));
+        let transform = match self.transform(options) {
+            Some(transform) => transform,
+            None => return image,
+        };
+        let points: Vec<[f64; 2]> = self.vertices.iter().map(|&p| transform.apply(p)).collect();
+
+        if options.sector_fills {
+            for (edges, light) in &self.sectors {
+                let edges: Vec<([f64; 2], [f64; 2])> = edges.iter().map(|&(a, b)| (points[a], points[b])).collect();
+                fill_even_odd(&mut image, &edges, sector_color(options.sector_color, *light));
+            }
+        }
+        // Two-sided lines go first so walls stay on top where they meet.
+        for two_sided in [true, false] {
+            let (color, width) = line_style(options, two_sided);
+            for &(a, b, _) in self.lines.iter().filter(|line| line.2 == two_sided) {
+                draw_line(&mut image, points[a], points[b], width, color);
+            }
+        }
+        if options.things {
+            for &(position, radius) in &self.things {
+                let radius = (radius * transform.scale).max(1.5);
+                draw_disc(&mut image, transform.apply(position), radius, options.thing_color);
+            }
+        }
+        image
+    }
+
+    /// Writes the overview as an SVG document with the same layout as
+    /// [`Overview::render`]. Sectors become even-odd filled paths, so holes
+    /// and nested sectors come out right.
+    pub fn to_svg(&self, options: &OverviewOptions) -> String {
+        let (w, h) = (options.width, options.height);
+        let mut svg = String::new();
+        let _ = writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#);
+        let _ = writeln!(svg, r#"<rect width="{}" height="{}" {}/>"#, w, h, paint("fill", options.background));
+
+        if let Some(transform) = self.transform(options) {
+            let points: Vec<[f64; 2]> = self.vertices.iter().map(|&p| transform.apply(p)).collect();
+
+            if options.sector_fills {
+                svg.push_str("<g fill-rule=\"evenodd\" stroke=\"none\">\n");
+                for (edges, light) in self.sectors.iter().filter(|(edges, _)| !edges.is_empty()) {
+                    let color = sector_color(options.sector_color, *light);
+                    let _ = writeln!(svg, r#"<path d="{}" {}/>"#, sector_path(edges, &points), paint("fill", color));
+                }
+                svg.push_str("</g>\n");
+            }
+            for two_sided in [true, false] {
+                let (color, width) = line_style(options, two_sided);
+                let mut d = String::new();
+                for &(a, b, _) in self.lines.iter().filter(|line| line.2 == two_sided) {
+                    let _ = write!(d, "M{} L{} ", coord(points[a]), coord(points[b]));
+                }
+                if !d.is_empty() {
+                    let _ = writeln!(
+                        svg,
+                        r#"<path d="{}" fill="none" stroke-width="{:.2}" stroke-linecap="round" {}/>"#,
+                        d.trim_end(),
+                        width,
+                        paint("stroke", color)
+                    );
+                }
+            }
+            if options.things && !self.things.is_empty() {
+                let _ = writeln!(svg, "<g {}>", paint("fill", options.thing_color));
+                for &(position, radius) in &self.things {
+                    let [x, y] = transform.apply(position);
+                    let r = (radius * transform.scale).max(1.5);
+                    let _ = writeln!(svg, r#"<circle cx="{:.1}" cy="{:.1}" r="{:.1}"/>"#, x, y, r);
+                }
+                svg.push_str("</g>\n");
+            }
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// Fits the bounds of the lines and things into the image, or `None` if
+    /// there is nothing to fit.
+    fn transform(&self, options: &OverviewOptions) -> Option<Transform> {
+        let mut min = [f64::MAX; 2];
+        let mut max = [f64::MIN; 2];
+        let line_points = self.lines.iter().flat_map(|&(a, b, _)| [self.vertices[a], self.vertices[b]]);
+        for p in line_points.chain(self.things.iter().map(|&(p, _)| p)) {
+            for axis in 0..2 {
+                min[axis] = min[axis].min(p[axis]);
+                max[axis] = max[axis].max(p[axis]);
+            }
+        }
+        if min[0] > max[0] {
+            return None;
+        }
+
+        let size = [(max[0] - min[0]).max(1.0), (max[1] - min[1]).max(1.0)];
+        let margin = 2.0 * options.margin as f64;
+        let available = [
+            (options.width as f64 - margin).max(1.0),
+            (options.height as f64 - margin).max(1.0),
+        ];
+        let scale = (available[0] / size[0]).min(available[1] / size[1]);
+        Some(Transform {
+            min,
+            scale,
+            left: (options.width as f64 - size[0] * scale) / 2.0,
+            bottom: (options.height as f64 + size[1] * scale) / 2.0,
+        })
+    }
+}
+
+fn line_style(options: &OverviewOptions, two_sided: bool) -> ([u8; 4], f32) {
+    if two_sided {
+        (options.two_sided, (options.line_width / 2.0).max(1.0))
+    } else {
+        (options.one_sided, options.line_width)
+    }
+}
+
+/// `base` darkened for a sector of light level `light`.
+fn sector_color(base: [u8; 4], light: i32) -> [u8; 4] {
+    let factor = 0.3 + 0.7 * light.clamp(0, 255) as f64 / 255.0;
+    let [r, g, b, a] = base;
+    let shade = |c: u8| (c as f64 * factor).round() as u8;
+    [shade(r), shade(g), shade(b), a]
+}
+
+/// An SVG color attribute, with an opacity attribute for translucent colors.
+fn paint(attribute: &str, color: [u8; 4]) -> String {
+    let [r, g, b, a] = color;
+    let mut paint = format!(r##"{}="#{:02x}{:02x}{:02x}""##, attribute, r, g, b);
+    if a < 255 {
+        let _ = write!(paint, r#" {}-opacity="{:.3}""#, attribute, a as f64 / 255.0);
+    }
+    paint
+}
+
+fn coord(p: [f64; 2]) -> String {
+    format!("{:.1} {:.1}", p[0], p[1])
+}
+
+/// Chains a sector's boundary edges into closed subpaths. Edges that do not
+/// close (unclosed sectors) end their subpath early.
+fn sector_path(edges: &[(usize, usize)], points: &[[f64; 2]]) -> String {
+    let mut by_vertex: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, &(a, b)) in edges.iter().enumerate() {
+        by_vertex.entry(a).or_default().push(i);
+        by_vertex.entry(b).or_default().push(i);
+    }
+    let mut used = vec![false; edges.len()];
+    let mut d = String::new();
+    for first in 0..edges.len() {
+        if used[first] {
+            continue;
+        }
+        used[first] = true;
+        let (start, mut current) = edges[first];
+        let _ = write!(d, "M{} L{}", coord(points[start]), coord(points[current]));
+        while current != start {
+            let next = match by_vertex[&current].iter().copied().find(|&e| !used[e]) {
+                Some(next) => next,
+                None => break,
+            };
+            used[next] = true;
+            let (a, b) = edges[next];
+            current = if a == current { b } else { a };
+            let _ = write!(d, " L{}", coord(points[current]));
+        }
+        d.push_str(" Z ");
+    }
+    d.trim_end().to_string()
+}
+
+/// Blends `color` over the pixel at (`x`, `y`), scaled by `coverage`.
+/// Pixels outside the image are ignored.
+fn blend(image: &mut RgbaImage, x: i64, y: i64, color: [u8; 4], coverage: f64) {
+    if x < 0 || y < 0 || x >= image.width() as i64 || y >= image.height() as i64 {
+        return;
+    }
+    let alpha = color[3] as f64 / 255.0 * coverage;
+    let pixel = image.get_pixel_mut(x as u32, y as u32);
+    let dst_alpha = pixel[3] as f64 / 255.0;
+    for c in 0..3 {
+        pixel[c] = (color[c] as f64 * alpha + pixel[c] as f64 * (1.0 - alpha)).round() as u8;
+    }
+    pixel[3] = ((alpha + dst_alpha * (1.0 - alpha)) * 255.0).round() as u8;
+}
+
+fn segment_distance(p: [f64; 2], a: [f64; 2], b: [f64; 2]) -> f64 {
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+    let len_sq = dx * dx + dy * dy;
+    let t = if len_sq > 0.0 {
+        (((p[0] - a[0]) * dx + (p[1] - a[1]) * dy) / len_sq).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (p[0] - (a[0] + t * dx)).hypot(p[1] - (a[1] + t * dy))
+}
+
+/// Draws an antialiased line with round caps. Walks the major axis one
+/// pixel at a time and covers the few pixels across the stroke, so the cost
+/// follows the length of the line rather than its bounding box.
+fn draw_line(image: &mut RgbaImage, a: [f64; 2], b: [f64; 2], width: f32, color: [u8; 4]) {
+    let half = width as f64 / 2.0;
+    let steep = (b[1] - a[1]).abs() > (b[0] - a[0]).abs();
+    let (major, minor) = if steep { (1, 0) } else { (0, 1) };
+    let limit = image.width().max(image.height()) as f64 + half + 2.0;
+    let lo = (a[major].min(b[major]) - half - 1.0).floor().max(-1.0);
+    let hi = (a[major].max(b[major]) + half + 1.0).ceil().min(limit);
+    // Across a line at most 45 degrees off the major axis, the stroke spans
+    // up to sqrt(2) times its width.
+    let reach = half * 1.5 + 1.0;
+
+    for m in lo as i64..=hi as i64 {
+        let center = m as f64 + 0.5;
+        let span = b[major] - a[major];
+        let t = if span != 0.0 { ((center - a[major]) / span).clamp(0.0, 1.0) } else { 0.0 };
+        let c = a[minor] + t * (b[minor] - a[minor]);
+        for n in (c - reach).floor() as i64..=(c + reach).ceil() as i64 {
+            let (x, y) = if steep { (n, m) } else { (m, n) };
+            let distance = segment_distance([x as f64 + 0.5, y as f64 + 0.5], a, b);
+            let coverage = (half + 0.5 - distance).clamp(0.0, 1.0);
+            if coverage > 0.0 {
+                blend(image, x, y, color, coverage);
+            }
+        }
+    }
+}
+
+fn draw_disc(image: &mut RgbaImage, center: [f64; 2], radius: f64, color: [u8; 4]) {
+    let (x0, x1) = ((center[0] - radius - 1.0).floor() as i64, (center[0] + radius + 1.0).ceil() as i64);
+    let (y0, y1) = ((center[1] - radius - 1.0).floor() as i64, (center[1] + radius + 1.0).ceil() as i64);
+    for y in y0..=y1 {
+        for x in x0..=x1 {
+            let distance = (x as f64 + 0.5 - center[0]).hypot(y as f64 + 0.5 - center[1]);
+            let coverage = (radius + 0.5 - distance).clamp(0.0, 1.0);
+            if coverage > 0.0 {
+                blend(image, x, y, color, coverage);
+            }
+        }
+    }
+}
+
+/// Fills the inside of `edges` (in pixel coordinates) by the even-odd rule,
+/// sampling pixel centers row by row.
+fn fill_even_odd(image: &mut RgbaImage, edges: &[([f64; 2], [f64; 2])], color: [u8; 4]) {
+    let (width, height) = (image.width() as f64, image.height() as f64);
+    let min_y = edges.iter().map(|(a, b)| a[1].min(b[1])).fold(f64::MAX, f64::min);
+    let max_y = edges.iter().map(|(a, b)| a[1].max(b[1])).fold(f64::MIN, f64::max);
+    let first_row = (min_y - 0.5).ceil().max(0.0);
+    let last_row = (max_y - 0.5).floor().min(height - 1.0);
+    if first_row > last_row {
+        return;
+    }
+
+    let mut crossings = Vec::new();
+    for row in first_row as i64..=last_row as i64 {
+        let y = row as f64 + 0.5;
+        crossings.clear();
+        crossings.extend(edges.iter().filter(|(a, b)| (a[1] <= y) != (b[1] <= y)).map(|(a, b)| {
+            a[0] + (y - a[1]) * (b[0] - a[0]) / (b[1] - a[1])
+        }));
+        crossings.sort_by(f64::total_cmp);
+        for span in crossings.chunks_exact(2) {
+            let start = (span[0] - 0.5).ceil().max(0.0);
+            let end = (span[1] - 0.5).floor().min(width - 1.0);
+            if start <= end {
+                for x in start as i64..=end as i64 {
+                    blend(image, x, row, color, 1.0);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_and_svg() {
+        let mut doc = Document::new();
+        doc.generate_test_map();
+        let overview = Overview::from_document(&doc);
+        assert!(!overview.is_empty());
+
+        let options = OverviewOptions { width: 200, height: 120, sector_fills: true, ..Default::default() };
+        let image = overview.render(&options);
+        assert_eq!(image.dimensions(), (200, 120));
+        assert_eq!(image.get_pixel(0, 0).0, options.background);
+        assert!(image.pixels().any(|p| p.0 == options.one_sided));
+        assert!(image.pixels().any(|p| p.0 != options.background && p.0 != options.one_sided));
+
+        let svg = overview.to_svg(&options);
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+        assert!(svg.contains("fill-rule=\"evenodd\""));
+        assert!(svg.contains("stroke=\"#000000\""));
+
+        let empty = Overview::default().render(&options);
+        assert!(empty.pixels().all(|p| p.0 == options.background));
+    }
+}
@@ -0,0 +1,234 @@
+// src/document/palette.rs
+
+//! PLAYPAL and COLORMAP.
+//!
+//! Doom graphics are 8-bit indices into a 256-color palette. PLAYPAL holds 14
+//! palettes (the normal one, then red, yellow and green tints for pain,
+//! pickups and the radiation suit); COLORMAP holds 34 remapping tables: 32
+//! light levels from brightest to darkest, the invulnerability map, and an
+//! all-black one. Shading a pixel for a sector's light means remapping its
+//! index through the light's table and looking the result up in palette 0.
+
+use thiserror::Error;
+
+/// Bytes in one PLAYPAL palette.
+pub const PALETTE_SIZE: usize = 256 * 3;
+/// Palettes in a stock PLAYPAL.
+pub const PLAYPAL_COUNT: usize = 14;
+/// Bytes in one COLORMAP table.
+pub const COLORMAP_SIZE: usize = 256;
+/// Tables in a stock COLORMAP.
+pub const COLORMAP_COUNT: usize = 34;
+/// Light tables at the start of COLORMAP, from full bright to darkest.
+pub const LIGHT_LEVELS: usize = 32;
+/// The inverted grey table used while invulnerable.
+pub const INVULNERABILITY_MAP: usize = 32;
+
+/// A PLAYPAL or COLORMAP lump that cannot be used.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PaletteError {
+    #[error("{0} lump not found")]
+    Missing(&'static str),
+
+    #[error("{lump} is {size} bytes; at least {needed} are needed")]
+    TooShort { lump: &'static str, size: usize, needed: usize },
+}
+
+/// One 256-color palette.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    colors: [[u8; 3]; 256],
+}
+
+impl Palette {
+    /// Reads 768 bytes of RGB triples.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, PaletteError> {
+        if data.len() < PALETTE_SIZE {
+            return Err(PaletteError::TooShort { lump: "PLAYPAL", size: data.len(), needed: PALETTE_SIZE });
+        }
+        let mut colors = [[0u8; 3]; 256];
+        for (color, rgb) in colors.iter_mut().zip(data.chunks_exact(3)) {
+            color.copy_from_slice(rgb);
+        }
+        Ok(Self { colors })
+    }
+
+    pub fn rgb(&self, index: u8) -> [u8; 3] {
+        self.colors[index as usize]
+    }
+
+    /// The color as opaque RGBA.
+    pub fn rgba(&self, index: u8) -> [u8; 4] {
+        let [r, g, b] = self.colors[index as usize];
+        [r, g, b, 255]
+    }
+
+    /// Converts indexed pixels to RGBA bytes, four per pixel.
+    pub fn to_rgba(&self, pixels: &[u8]) -> Vec<u8> {
+        pixels.iter().flat_map(|&i| self.rgba(i)).collect()
+    }
+
+    /// The index of the color closest to `rgb`; the lowest index wins a tie.
+    pub fn nearest(&self, rgb: [u8; 3]) -> u8 {
+        let distance = |color: &[u8; 3]| -> i32 {
+            color.iter().zip(rgb).map(|(&a, b)| (a as i32 - b as i32).pow(2)).sum()
+        };
+        let (index, _) = self
+            .colors
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, color)| distance(color))
+            .unwrap();
+        index as u8
+    }
+}
+
+/// Every palette of a PLAYPAL lump.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Playpal {
+    palettes: Vec<Palette>,
+}
+
+impl Playpal {
+    /// Decodes as many whole palettes as the lump holds (14 in the stock
+    /// IWADs). A trailing partial palette is ignored.
+    pub fn from_lump(data: &[u8]) -> Result<Self, PaletteError> {
+        let palettes = data
+            .chunks_exact(PALETTE_SIZE)
+            .map(Palette::from_bytes)
+            .collect::<Result<Vec<_>, _>>()?;
+        if palettes.is_empty() {
+            return Err(PaletteError::TooShort { lump: "PLAYPAL", size: data.len(), needed: PALETTE_SIZE });
+        }
+        Ok(Self { palettes })
+    }
+
+    pub fn len(&self) -> usize {
+        self.palettes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.palettes.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Palette> {
+        self.palettes.get(index)
+    }
+
+    /// Palette 0, the one everything is drawn with normally.
+    pub fn base(&self) -> &Palette {
+        &self.palettes[0]
+    }
+}
+
+/// The tables of a COLORMAP lump.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColorMap {
+    maps: Vec<[u8; COLORMAP_SIZE]>,
+}
+
+impl ColorMap {
+    /// Decodes every whole table. The 32 light tables are required; the
+    /// invulnerability and black tables are optional.
+    pub fn from_lump(data: &[u8]) -> Result<Self, PaletteError> {
+        let needed = LIGHT_LEVELS * COLORMAP_SIZE;
+        if data.len() < needed {
+            return Err(PaletteError::TooShort { lump: "COLORMAP", size: data.len(), needed });
+        }
+        let maps = data
+            .chunks_exact(COLORMAP_SIZE)
+            .map(|chunk| {
+                let mut map = [0u8; COLORMAP_SIZE];
+                map.copy_from_slice(chunk);
+                map
+            })
+            .collect();
+        Ok(Self { maps })
+    }
+
+    pub fn len(&self) -> usize {
+        self.maps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.maps.is_empty()
+    }
+
+    /// Table `map` applied to `index`; indices past the last table are
+    /// clamped to it.
+    pub fn remap(&self, map: usize, index: u8) -> u8 {
+        self.maps[map.min(self.maps.len() - 1)][index as usize]
+    }
+
+    /// The light table for a sector light level.
+    pub fn light_map(light: i32) -> usize {
+        // 256 light levels over 32 tables, brightest first. This is what the
+        // game shows for a surface right in front of the player.
+        ((255 - light.clamp(0, 255)) / 8) as usize
+    }
+}
+
+/// Palette 0 and COLORMAP together: everything needed to draw a pixel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shading {
+    pub playpal: Playpal,
+    pub colormap: ColorMap,
+}
+
+impl Shading {
+    /// Decodes a PLAYPAL and a COLORMAP lump.
+    pub fn from_lumps(playpal: &[u8], colormap: &[u8]) -> Result<Self, PaletteError> {
+        Ok(Self {
+            playpal: Playpal::from_lump(playpal)?,
+            colormap: ColorMap::from_lump(colormap)?,
+        })
+    }
+
+    pub fn palette(&self) -> &Palette {
+        self.playpal.base()
+    }
+
+    /// The RGBA color of palette index `index` in a sector with light level
+    /// `light` (a `Sector::light`, 0-255).
+    pub fn shade(&self, index: u8, light: i32) -> [u8; 4] {
+        let shaded = self.colormap.remap(ColorMap::light_map(light), index);
+        self.palette().rgba(shaded)
+    }
+
+    /// Converts indexed pixels to RGBA bytes as seen under `light`.
+    pub fn shade_pixels(&self, pixels: &[u8], light: i32) -> Vec<u8> {
+        pixels.iter().flat_map(|&i| self.shade(i, light)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_light_shading() {
+        // Palette: index i is grey level i. Colormap: table m darkens by m*8.
+        let playpal: Vec<u8> = (0..PLAYPAL_COUNT)
+            .flat_map(|_| (0..=255u8).flat_map(|i| [i, i, i]))
+            .collect();
+        let colormap: Vec<u8> = (0..COLORMAP_COUNT)
+            .flat_map(|m| (0..=255u8).map(move |i| i.saturating_sub((m * 8) as u8)))
+            .collect();
+        let shading = Shading::from_lumps(&playpal, &colormap).unwrap();
+        assert_eq!(shading.playpal.len(), PLAYPAL_COUNT);
+        assert_eq!(shading.colormap.len(), COLORMAP_COUNT);
+
+        assert_eq!(ColorMap::light_map(255), 0);
+        assert_eq!(ColorMap::light_map(0), 31);
+        assert_eq!(ColorMap::light_map(-40), 31);
+        assert_eq!(shading.shade(200, 255), [200, 200, 200, 255]);
+        assert_eq!(shading.shade(200, 128), [80, 80, 80, 255]);
+        assert_eq!(shading.shade_pixels(&[10, 200], 0), vec![0, 0, 0, 255, 0, 0, 0, 255]);
+        assert_eq!(shading.palette().nearest([101, 99, 103]), 101);
+
+        assert_eq!(
+            Shading::from_lumps(&playpal[..100], &colormap),
+            Err(PaletteError::TooShort { lump: "PLAYPAL", size: 100, needed: PALETTE_SIZE })
+        );
+    }
+}
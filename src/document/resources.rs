@@ -0,0 +1,335 @@
+// src/document/resources.rs
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use log::{info, warn};
+
+use crate::document::archive::{lump_name_from_path, Pk3Archive};
+use crate::document::wad::{parse_directory, write_wad, Lump, LumpEntry, WadBytes};
+
+/// Lump namespaces delimited by marker lumps (e.g. F_START / F_END).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Namespace {
+    /// Lumps outside any marker range.
+    Global,
+    Flats,
+    Sprites,
+    Patches,
+    /// Loose textures from a PK3 `textures/` directory.
+    Textures,
+}
+
+/// A read-only WAD in the resource stack.
+#[derive(Debug, Clone)]
+pub struct ResourceWad {
+    /// Display name, usually the file name.
+    pub name: String,
+    pub is_iwad: bool,
+    pub directory: Vec<LumpEntry>,
+    /// Namespace of each directory entry.
+    namespaces: Vec<Namespace>,
+    /// Full path of each directory entry, for WADs built from an archive.
+    /// Lookups match it as well as the short name.
+    paths: Vec<String>,
+    data: WadBytes,
+}
+
+impl ResourceWad {
+    /// Parses an in-memory WAD.
+    pub fn from_bytes(name: impl Into<String>, data: Vec<u8>) -> io::Result<Self> {
+        let (header, directory) = parse_directory(&data)?;
+        let namespaces = classify_namespaces(&directory);
+        Ok(Self {
+            name: name.into(),
+            is_iwad: &header[0..4] == b"IWAD",
+            directory,
+            namespaces,
+            paths: Vec::new(),
+            data: data.into(),
+        })
+    }
+
+    /// Builds a resource WAD from loose lumps named by path, e.g. the files
+    /// of a PK3. The directory gets their short names.
+    pub fn from_lumps(name: impl Into<String>, lumps: Vec<(Lump, Namespace)>) -> io::Result<Self> {
+        let name = name.into();
+        let mut paths = Vec::with_capacity(lumps.len());
+        let mut namespaces = Vec::with_capacity(lumps.len());
+        let mut short = Vec::with_capacity(lumps.len());
+        for (lump, namespace) in lumps {
+            let lump_name = lump_name_from_path(&lump.name);
+            let clash = short.iter().zip(&namespaces)
+                .position(|(l, &ns): (&Lump, _)| ns == namespace && l.name == lump_name);
+            if let Some(index) = clash {
+                warn!("{}: {} and {} share the short name {}; look them up by path",
+                    name, paths[index], lump.name, lump_name);
+            }
+            paths.push(lump.name);
+            namespaces.push(namespace);
+            short.push(Lump::new(lump_name, lump.data));
+        }
+        let mut cursor = io::Cursor::new(Vec::new());
+        write_wad(&mut cursor, b"PWAD", &short)?;
+        let data = cursor.into_inner();
+        let (_, directory) = parse_directory(&data)?;
+        Ok(Self {
+            name,
+            is_iwad: false,
+            directory,
+            namespaces,
+            paths,
+            data: data.into(),
+        })
+    }
+
+    /// The contents of the directory entry at `index`.
+    pub fn lump_data(&self, index: usize) -> Option<&[u8]> {
+        let entry = self.directory.get(index)?;
+        let start = entry.offset as usize;
+        self.data.get(start..start + entry.size as usize)
+    }
+
+    /// Index of the last lump called `name` in `namespace`; like Doom, later
+    /// entries in the same file win. Archive files also answer to their full
+    /// path, which tells apart files with the same short name.
+    fn find(&self, name: &str, namespace: Namespace) -> Option<usize> {
+        if let Some(index) = self.paths.iter().zip(&self.namespaces)
+            .position(|(path, &ns)| ns == namespace && path.eq_ignore_ascii_case(name))
+        {
+            return Some(index);
+        }
+        self.directory
+            .iter()
+            .zip(&self.namespaces)
+            .rposition(|(entry, &ns)| ns == namespace && entry.name.eq_ignore_ascii_case(name))
+    }
+}
+
+/// The stack of read-only resource WADs: a base IWAD followed by PWADs in
+/// load order. Lookups follow Doom's rule that the last loaded lump wins.
+///
+/// The map being edited is not part of the stack; see `Document::find_lump`.
+#[derive(Debug, Default)]
+pub struct ResourceManager {
+    wads: Vec<ResourceWad>,
+}
+
+impl ResourceManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The loaded WADs, base IWAD first.
+    pub fn wads(&self) -> &[ResourceWad] {
+        &self.wads
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.wads.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.wads.clear();
+    }
+
+    /// Sets the base IWAD, replacing any previous one. It always sits at the
+    /// bottom of the stack.
+    pub fn set_iwad(&mut self, wad: ResourceWad) {
+        if self.wads.first().is_some_and(|w| w.is_iwad) {
+            self.wads.remove(0);
+        }
+        info!("Resource IWAD: {} ({} lumps)", wad.name, wad.directory.len());
+        self.wads.insert(0, wad);
+    }
+
+    /// Puts a PWAD on top of the stack. A WAD with the same name is removed
+    /// first, so reloading a file moves it to the top instead of stacking it twice.
+    pub fn add_pwad(&mut self, wad: ResourceWad) {
+        self.wads.retain(|w| w.is_iwad || w.name != wad.name);
+        info!("Resource PWAD: {} ({} lumps)", wad.name, wad.directory.len());
+        self.wads.push(wad);
+    }
+
+    /// Reads a WAD, PK3 or DeHackEd patch and adds it to the stack: as the
+    /// base if it is an IWAD, on top otherwise. Only the loose files of a PK3
+    /// are used.
+    pub fn load_file(&mut self, path: &Path) -> io::Result<()> {
+        let data = fs::read(path)?;
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.display().to_string());
+        let wad = if Pk3Archive::is_archive(&data) {
+            ResourceWad::from_lumps(name, Pk3Archive::from_bytes(&data)?.lumps())?
+        } else if is_dehacked_path(path) {
+            // A loose patch acts like a WAD holding only a DEHACKED lump.
+            ResourceWad::from_lumps(name, vec![(Lump::new("DEHACKED", data), Namespace::Global)])?
+        } else {
+            ResourceWad::from_bytes(name, data)?
+        };
+        if wad.is_iwad {
+            self.set_iwad(wad);
+        } else {
+            self.add_pwad(wad);
+        }
+        Ok(())
+    }
+
+    /// Looks up a lump outside any namespace (PLAYPAL, TEXTURE1, PNAMES, ...).
+    pub fn find_lump(&self, name: &str) -> Option<&[u8]> {
+        self.find_in_namespace(name, Namespace::Global)
+    }
+
+    /// Looks up a lump inside a marker namespace, e.g. a flat between F_START
+    /// and F_END. The topmost WAD that has it wins.
+    pub fn find_in_namespace(&self, name: &str, namespace: Namespace) -> Option<&[u8]> {
+        self.wads.iter().rev().find_map(|wad| {
+            let index = wad.find(name, namespace)?;
+            wad.lump_data(index)
+        })
+    }
+
+    /// Every lump called `name` from bottom to top, for lumps that are merged
+    /// rather than replaced.
+    pub fn find_all(&self, name: &str) -> Vec<&[u8]> {
+        self.wads
+            .iter()
+            .filter_map(|wad| {
+                let index = wad.find(name, Namespace::Global)?;
+                wad.lump_data(index)
+            })
+            .collect()
+    }
+
+    /// The names of all lumps in `namespace` after overrides, in first-seen order.
+    pub fn namespace_names(&self, namespace: Namespace) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for wad in &self.wads {
+            for (entry, &ns) in wad.directory.iter().zip(&wad.namespaces) {
+                if ns == namespace
+                    && entry.size > 0
+                    && !names.iter().any(|n| n.eq_ignore_ascii_case(&entry.name))
+                {
+                    names.push(entry.name.to_ascii_uppercase());
+                }
+            }
+        }
+        names
+    }
+}
+
+/// Which namespace a `*_START` / `*_END` marker opens or closes, if any.
+/// Nested end markers such as F1_END do not close the namespace.
+fn marker_namespace(name: &str) -> Option<(Namespace, MarkerKind)> {
+    let name = name.to_ascii_uppercase();
+    let (prefix, kind) = if let Some(prefix) = name.strip_suffix("_START") {
+        (prefix.to_string(), MarkerKind::Start)
+    } else if let Some(prefix) = name.strip_suffix("_END") {
+        (prefix.to_string(), MarkerKind::End)
+    } else {
+        return None;
+    };
+    let namespace = match prefix.as_str() {
+        "F" | "FF" | "F1" | "F2" | "F3" => Namespace::Flats,
+        "S" | "SS" => Namespace::Sprites,
+        "P" | "PP" | "P1" | "P2" | "P3" => Namespace::Patches,
+        _ => return None,
+    };
+    let kind = match (kind, prefix.len()) {
+        (MarkerKind::End, 2) if prefix.ends_with(|c: char| c.is_ascii_digit()) => MarkerKind::Inner,
+        (kind, _) => kind,
+    };
+    Some((namespace, kind))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MarkerKind {
+    Start,
+    End,
+    /// A nested end marker such as F1_END.
+    Inner,
+}
+
+/// True for `.deh` and `.bex` files.
+fn is_dehacked_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("deh") || ext.eq_ignore_ascii_case("bex"))
+}
+
+/// Assigns a namespace to each directory entry. Markers themselves are Global.
+pub fn classify_namespaces(directory: &[LumpEntry]) -> Vec<Namespace> {
+    let mut current = Namespace::Global;
+    directory
+        .iter()
+        .map(|entry| match marker_namespace(&entry.name) {
+            Some((ns, MarkerKind::Start)) => {
+                current = ns;
+                Namespace::Global
+            }
+            Some((ns, MarkerKind::End)) if ns == current => {
+                current = Namespace::Global;
+                Namespace::Global
+            }
+            Some(_) => Namespace::Global,
+            None => current,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn wad(ident: &[u8; 4], lumps: &[(&str, &[u8])]) -> Vec<u8> {
+        let lumps: Vec<Lump> = lumps.iter().map(|(n, d)| Lump::new(*n, d.to_vec())).collect();
+        let mut cursor = Cursor::new(Vec::new());
+        write_wad(&mut cursor, ident, &lumps).unwrap();
+        cursor.into_inner()
+    }
+
+    #[test]
+    fn test_last_loaded_wins() {
+        let iwad = wad(b"IWAD", &[
+            ("PLAYPAL", b"iwad"),
+            ("F_START", b""),
+            ("F1_START", b""),
+            ("FLOOR0_1", b"iflat"),
+            ("F1_END", b""),
+            ("NUKAGE1", b"nukage"),
+            ("F_END", b""),
+        ]);
+        let pwad = wad(b"PWAD", &[("FF_START", b""), ("FLOOR0_1", b"pflat"), ("FF_END", b""), ("PLAYPAL", b"pwad")]);
+
+        let mut resources = ResourceManager::new();
+        resources.add_pwad(ResourceWad::from_bytes("mod.wad", pwad).unwrap());
+        resources.set_iwad(ResourceWad::from_bytes("doom2.wad", iwad).unwrap());
+        assert_eq!(resources.wads()[0].name, "doom2.wad");
+
+        assert_eq!(resources.find_lump("PLAYPAL"), Some(&b"pwad"[..]));
+        assert_eq!(resources.find_in_namespace("FLOOR0_1", Namespace::Flats), Some(&b"pflat"[..]));
+        assert_eq!(resources.find_in_namespace("NUKAGE1", Namespace::Flats), Some(&b"nukage"[..]));
+        assert_eq!(resources.find_lump("FLOOR0_1"), None);
+        assert_eq!(resources.find_all("PLAYPAL").len(), 2);
+        assert_eq!(resources.namespace_names(Namespace::Flats), vec!["FLOOR0_1", "NUKAGE1"]);
+    }
+
+    #[test]
+    fn test_archive_lumps_by_path() {
+        let wad = ResourceWad::from_lumps("mod.pk3", vec![
+            (Lump::new("textures/wall/brick_long.png", b"wall".to_vec()), Namespace::Textures),
+            (Lump::new("textures/brick_longer.png", b"longer".to_vec()), Namespace::Textures),
+            (Lump::new("flats/brick_long.png", b"flat".to_vec()), Namespace::Flats),
+        ]).unwrap();
+        assert_eq!(wad.directory[0].name, "BRICK_LO");
+
+        let mut resources = ResourceManager::new();
+        resources.add_pwad(wad);
+        let find = |name| resources.find_in_namespace(name, Namespace::Textures);
+        assert_eq!(find("textures/wall/brick_long.png"), Some(&b"wall"[..]));
+        assert_eq!(find("TEXTURES/BRICK_LONGER.PNG"), Some(&b"longer"[..]));
+        assert_eq!(find("BRICK_LO"), Some(&b"longer"[..]));
+        assert_eq!(resources.find_in_namespace("flats/brick_long.png", Namespace::Flats), Some(&b"flat"[..]));
+    }
+}
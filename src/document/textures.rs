@@ -0,0 +1,506 @@
+// src/document/textures.rs
+
+//! Doom pictures and composite wall textures.
+//!
+//! Wall graphics are stored as patches in the column-based picture format.
+//! PNAMES lists the patch lumps by number, and TEXTURE1/TEXTURE2 build each
+//! wall texture by placing patches on a canvas. Composites come out as RGBA
+//! images; pixels no patch covers stay transparent.
+//!
+//! Flats (floors and ceilings) are simpler: raw rows of palette indices
+//! between F_START and F_END.
+
+use std::collections::HashMap;
+use image::RgbaImage;
+use log::warn;
+use thiserror::Error;
+
+use crate::document::palette::{Palette, PaletteError};
+
+/// A patch, picture or texture definition that cannot be decoded.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TextureError {
+    #[error("{0} is not a valid picture")]
+    BadPicture(String),
+
+    #[error("{lump} is truncated")]
+    Truncated { lump: String },
+
+    #[error("Texture {0} is not defined")]
+    UnknownTexture(String),
+
+    #[error("{0} lump not found")]
+    Missing(&'static str),
+
+    #[error(transparent)]
+    Palette(#[from] PaletteError),
+}
+
+/// A decoded picture: palette indices plus a mask of the pixels that are
+/// drawn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Picture {
+    pub width: usize,
+    pub height: usize,
+    pub left_offset: i32,
+    pub top_offset: i32,
+    /// Row-major palette indices.
+    pub pixels: Vec<u8>,
+    /// Row-major; false where the picture is transparent.
+    pub opaque: Vec<bool>,
+}
+
+impl Picture {
+    /// Decodes a lump in the Doom picture format. `name` is only used in
+    /// errors.
+    ///
+    /// Posts whose top delta does not increase are read as relative to the
+    /// previous post, the usual extension for pictures taller than 254 pixels.
+    pub fn decode(name: &str, data: &[u8]) -> Result<Self, TextureError> {
+        let bad = || TextureError::BadPicture(name.to_string());
+        if data.len() < 8 {
+            return Err(bad());
+        }
+        let width = u16::from_le_bytes([data[0], data[1]]) as usize;
+        let height = u16::from_le_bytes([data[2], data[3]]) as usize;
+        let left_offset = i16::from_le_bytes([data[4], data[5]]) as i32;
+        let top_offset = i16::from_le_bytes([data[6], data[7]]) as i32;
+        if width == 0 || height == 0 || data.len() < 8 + width * 4 {
+            return Err(bad());
+        }
+
+        let mut picture = Picture {
+            width,
+            height,
+            left_offset,
+            top_offset,
+            pixels: vec![0; width * height],
+            opaque: vec![false; width * height],
+        };
+        for x in 0..width {
+            let at = 8 + x * 4;
+            let mut pos = u32::from_le_bytes(data[at..at + 4].try_into().unwrap()) as usize;
+            let mut top: Option<usize> = None;
+            loop {
+                let delta = *data.get(pos).ok_or_else(bad)? as usize;
+                if delta == 0xFF {
+                    break;
+                }
+                let length = *data.get(pos + 1).ok_or_else(bad)? as usize;
+                let start = match top {
+                    Some(previous) if delta <= previous => previous + delta,
+                    _ => delta,
+                };
+                top = Some(start);
+                // Skip the top delta, length and a padding byte.
+                let post = data.get(pos + 3..pos + 3 + length).ok_or_else(bad)?;
+                for (i, &index) in post.iter().enumerate() {
+                    let y = start + i;
+                    if y < height {
+                        picture.pixels[y * width + x] = index;
+                        picture.opaque[y * width + x] = true;
+                    }
+                }
+                // The post ends with another padding byte.
+                pos += 4 + length;
+            }
+        }
+        Ok(picture)
+    }
+
+    /// Converts an RGBA image to palette colors. Pixels with alpha below 128
+    /// become transparent (index 0). The offsets start at zero.
+    pub fn from_rgba(image: &RgbaImage, palette: &Palette) -> Self {
+        let mut nearest = HashMap::new();
+        let mut pixels = Vec::with_capacity(image.len() / 4);
+        let mut opaque = Vec::with_capacity(image.len() / 4);
+        for pixel in image.pixels() {
+            let [r, g, b, a] = pixel.0;
+            let drawn = a >= 128;
+            let index = if drawn {
+                *nearest.entry([r, g, b]).or_insert_with(|| palette.nearest([r, g, b]))
+            } else {
+                0
+            };
+            pixels.push(index);
+            opaque.push(drawn);
+        }
+        Self {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            left_offset: 0,
+            top_offset: 0,
+            pixels,
+            opaque,
+        }
+    }
+
+    /// Encodes the picture in the Doom picture format. Columns taller than
+    /// 254 pixels use relative top deltas, padded with empty posts where a
+    /// jump is too long to express.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&(self.width as u16).to_le_bytes());
+        data.extend_from_slice(&(self.height as u16).to_le_bytes());
+        data.extend_from_slice(&(self.left_offset as i16).to_le_bytes());
+        data.extend_from_slice(&(self.top_offset as i16).to_le_bytes());
+        let table = data.len();
+        data.resize(table + self.width * 4, 0);
+
+        for x in 0..self.width {
+            let offset = data.len() as u32;
+            data[table + x * 4..table + x * 4 + 4].copy_from_slice(&offset.to_le_bytes());
+            let mut previous_top = None;
+            let mut y = 0;
+            while y < self.height {
+                if !self.opaque[y * self.width + x] {
+                    y += 1;
+                    continue;
+                }
+                let start = y;
+                while y < self.height && y - start < 254 && self.opaque[y * self.width + x] {
+                    y += 1;
+                }
+                let column: Vec<u8> = (start..y).map(|row| self.pixels[row * self.width + x]).collect();
+                write_post(&mut data, &mut previous_top, start, &column);
+            }
+            data.push(0xFF);
+        }
+        data
+    }
+
+    /// The picture in RGBA; transparent pixels have alpha 0.
+    pub fn to_rgba(&self, palette: &Palette) -> RgbaImage {
+        let mut image = RgbaImage::new(self.width as u32, self.height as u32);
+        for (i, pixel) in image.pixels_mut().enumerate() {
+            if self.opaque[i] {
+                pixel.0 = palette.rgba(self.pixels[i]);
+            }
+        }
+        image
+    }
+}
+
+/// Appends one post starting at row `start`. Rows past 254 are reached
+/// through top deltas relative to the previous post, which the decoder reads
+/// whenever a delta does not move down; empty posts fill in long jumps.
+fn write_post(data: &mut Vec<u8>, previous_top: &mut Option<usize>, start: usize, pixels: &[u8]) {
+    loop {
+        let delta = if start <= 254 {
+            Some(start)
+        } else {
+            match *previous_top {
+                Some(top) if top >= 254 && start - top <= 254 => Some(start - top),
+                _ => None,
+            }
+        };
+        if let Some(delta) = delta {
+            data.extend_from_slice(&[delta as u8, pixels.len() as u8, 0]);
+            data.extend_from_slice(pixels);
+            data.push(0);
+            *previous_top = Some(start);
+            return;
+        }
+        // An empty post at row 254, or 254 rows below the previous one.
+        let (delta, top) = match *previous_top {
+            Some(top) if top >= 254 => (254, top + 254),
+            _ => (254, 254),
+        };
+        data.extend_from_slice(&[delta as u8, 0, 0, 0]);
+        *previous_top = Some(top);
+    }
+}
+
+/// Reads PNAMES: the patch lump names, indexed by the numbers TEXTUREx uses.
+pub fn parse_pnames(data: &[u8]) -> Result<Vec<String>, TextureError> {
+    let truncated = || TextureError::Truncated { lump: "PNAMES".to_string() };
+    let count = data.get(..4).ok_or_else(truncated)?;
+    let count = i32::from_le_bytes(count.try_into().unwrap()).max(0) as usize;
+    let names = data.get(4..4 + count * 8).ok_or_else(truncated)?;
+    Ok(names.chunks_exact(8).map(lump_name).collect())
+}
+
+/// One patch placed on a texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TexturePatch {
+    pub origin_x: i32,
+    pub origin_y: i32,
+    /// Index into PNAMES.
+    pub patch: usize,
+}
+
+/// A wall texture from TEXTURE1 or TEXTURE2.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextureDef {
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    pub patches: Vec<TexturePatch>,
+}
+
+/// Reads a TEXTURE1 or TEXTURE2 lump in the Doom layout.
+pub fn parse_texturex(lump: &str, data: &[u8]) -> Result<Vec<TextureDef>, TextureError> {
+    let truncated = || TextureError::Truncated { lump: lump.to_string() };
+    let read_i16 = |at: usize| -> Result<i32, TextureError> {
+        let bytes = data.get(at..at + 2).ok_or_else(truncated)?;
+        Ok(i16::from_le_bytes([bytes[0], bytes[1]]) as i32)
+    };
+    let read_i32 = |at: usize| -> Result<i32, TextureError> {
+        let bytes = data.get(at..at + 4).ok_or_else(truncated)?;
+        Ok(i32::from_le_bytes(bytes.try_into().unwrap()))
+    };
+
+    let count = read_i32(0)?.max(0) as usize;
+    // Each texture needs an offset, so a larger count is a damaged lump.
+    if count > (data.len() - 4) / 4 {
+        return Err(truncated());
+    }
+    let mut textures = Vec::with_capacity(count);
+    for i in 0..count {
+        let offset = read_i32(4 + i * 4)?.max(0) as usize;
+        // name[8], masked, width, height, column directory, patch count.
+        let name = lump_name(data.get(offset..offset + 8).ok_or_else(truncated)?);
+        let width = read_i16(offset + 12)?.max(0) as usize;
+        let height = read_i16(offset + 14)?.max(0) as usize;
+        let patch_count = read_i16(offset + 20)?.max(0) as usize;
+        let mut patches = Vec::with_capacity(patch_count);
+        for p in 0..patch_count {
+            // origin x, origin y, patch, step dir, colormap.
+            let at = offset + 22 + p * 10;
+            patches.push(TexturePatch {
+                origin_x: read_i16(at)?,
+                origin_y: read_i16(at + 2)?,
+                patch: read_i16(at + 4)?.max(0) as usize,
+            });
+        }
+        textures.push(TextureDef { name, width, height, patches });
+    }
+    Ok(textures)
+}
+
+/// PNAMES plus the definitions of TEXTURE1 and TEXTURE2, in that order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TextureSet {
+    pub patch_names: Vec<String>,
+    pub textures: Vec<TextureDef>,
+}
+
+impl TextureSet {
+    /// Parses the definition lumps; TEXTURE2 is optional (the shareware and
+    /// Doom II IWADs have none).
+    pub fn from_lumps(pnames: &[u8], texture1: &[u8], texture2: Option<&[u8]>) -> Result<Self, TextureError> {
+        let mut textures = parse_texturex("TEXTURE1", texture1)?;
+        if let Some(data) = texture2 {
+            textures.extend(parse_texturex("TEXTURE2", data)?);
+        }
+        Ok(Self { patch_names: parse_pnames(pnames)?, textures })
+    }
+
+    /// The definition of `name`. As in the game, the first one wins when a
+    /// name is defined twice.
+    pub fn find(&self, name: &str) -> Option<&TextureDef> {
+        self.textures.iter().find(|t| t.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Texture names in definition order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.textures.iter().map(|t| t.name.as_str())
+    }
+
+    /// Builds texture `name` from its patches. `patch_data` looks up a patch
+    /// lump by name; patches it cannot find or decode are left out with a
+    /// warning, so a damaged texture still shows what it can.
+    pub fn composite(
+        &self,
+        name: &str,
+        palette: &Palette,
+        patch_data: impl Fn(&str) -> Option<Vec<u8>>,
+    ) -> Result<RgbaImage, TextureError> {
+        let def = self.find(name).ok_or_else(|| TextureError::UnknownTexture(name.to_string()))?;
+        let mut image = RgbaImage::new(def.width as u32, def.height as u32);
+        for placed in &def.patches {
+            let patch_name = match self.patch_names.get(placed.patch) {
+                Some(patch_name) => patch_name,
+                None => {
+                    warn!("{}: patch number {} is not in PNAMES", def.name, placed.patch);
+                    continue;
+                }
+            };
+            let picture = match patch_data(patch_name).map(|data| Picture::decode(patch_name, &data)) {
+                Some(Ok(picture)) => picture,
+                Some(Err(e)) => {
+                    warn!("{}: {}", def.name, e);
+                    continue;
+                }
+                None => {
+                    warn!("{}: patch {} not found", def.name, patch_name);
+                    continue;
+                }
+            };
+            draw_picture(&mut image, &picture, placed.origin_x, placed.origin_y, palette);
+        }
+        Ok(image)
+    }
+}
+
+/// Copies the opaque pixels of `picture` onto `image` with its top-left
+/// corner at (`x`, `y`), clipping at the edges.
+fn draw_picture(image: &mut RgbaImage, picture: &Picture, x: i32, y: i32, palette: &Palette) {
+    for py in 0..picture.height {
+        let ty = y + py as i32;
+        if ty < 0 || ty >= image.height() as i32 {
+            continue;
+        }
+        for px in 0..picture.width {
+            let tx = x + px as i32;
+            let i = py * picture.width + px;
+            if tx < 0 || tx >= image.width() as i32 || !picture.opaque[i] {
+                continue;
+            }
+            image.get_pixel_mut(tx as u32, ty as u32).0 = palette.rgba(picture.pixels[i]);
+        }
+    }
+}
+
+/// Size in pixels of a flat of `len` bytes. Flats are 64x64; some ports
+/// also accept larger square or 64x128 ones. Heretic's 64x65 flats are read
+/// as 64x64.
+pub fn flat_size(len: usize) -> Option<(usize, usize)> {
+    match len {
+        8192 => Some((64, 128)),
+        16384 => Some((128, 128)),
+        65536 => Some((256, 256)),
+        len if len >= 4096 => Some((64, 64)),
+        _ => None,
+    }
+}
+
+/// Decodes a raw flat to RGBA.
+pub fn decode_flat(name: &str, data: &[u8], palette: &Palette) -> Result<RgbaImage, TextureError> {
+    let (width, height) = flat_size(data.len()).ok_or_else(|| TextureError::BadPicture(name.to_string()))?;
+    let mut image = RgbaImage::new(width as u32, height as u32);
+    for (pixel, &index) in image.pixels_mut().zip(data) {
+        pixel.0 = palette.rgba(index);
+    }
+    Ok(image)
+}
+
+/// Encodes an RGBA image as a raw flat. The image must have one of the flat
+/// sizes `flat_size` accepts; alpha is ignored.
+pub fn encode_flat(name: &str, image: &RgbaImage, palette: &Palette) -> Result<Vec<u8>, TextureError> {
+    let size = (image.width() as usize, image.height() as usize);
+    let len = size.0 * size.1;
+    if len < 4096 || flat_size(len) != Some(size) {
+        return Err(TextureError::BadPicture(format!(
+            "{} ({}x{}; flats are 64x64, 64x128, 128x128 or 256x256)",
+            name, size.0, size.1
+        )));
+    }
+    Ok(Picture::from_rgba(image, palette).pixels)
+}
+
+/// An 8-byte, NUL-padded name, uppercased.
+fn lump_name(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .take_while(|&&b| b != 0)
+        .map(|&b| (b as char).to_ascii_uppercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 2x3 patch: column 0 has one post at row 1, column 1 is empty.
+    fn patch() -> Vec<u8> {
+        let mut data = vec![2, 0, 3, 0, 0, 0, 0, 0];
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(&23u32.to_le_bytes());
+        data.extend_from_slice(&[1, 2, 0, 7, 8, 0, 0xFF]);
+        data.push(0xFF);
+        data
+    }
+
+    fn name8(name: &str) -> Vec<u8> {
+        let mut bytes = name.as_bytes().to_vec();
+        bytes.resize(8, 0);
+        bytes
+    }
+
+    #[test]
+    fn test_composite_texture() {
+        let picture = Picture::decode("TEST", &patch()).unwrap();
+        assert_eq!((picture.width, picture.height), (2, 3));
+        assert_eq!(picture.opaque, vec![false, false, true, false, true, false]);
+        assert_eq!(picture.pixels[2], 7);
+        assert_eq!(picture.pixels[4], 8);
+        assert!(Picture::decode("BAD", &patch()[..10]).is_err());
+
+        let mut pnames = 1i32.to_le_bytes().to_vec();
+        pnames.extend(name8("wall00_1"));
+
+        // One 4x3 texture with the patch at x = 1 and at x = 3 (clipped).
+        let mut texture1 = 1i32.to_le_bytes().to_vec();
+        texture1.extend_from_slice(&8i32.to_le_bytes());
+        texture1.extend(name8("STARTAN2"));
+        texture1.extend_from_slice(&[0, 0, 0, 0, 4, 0, 3, 0, 0, 0, 0, 0, 2, 0]);
+        texture1.extend_from_slice(&[1, 0, 0, 0, 0, 0, 1, 0, 0, 0]);
+        texture1.extend_from_slice(&[3, 0, 0, 0, 0, 0, 1, 0, 0, 0]);
+
+        let set = TextureSet::from_lumps(&pnames, &texture1, None).unwrap();
+        assert_eq!(set.patch_names, vec!["WALL00_1".to_string()]);
+        assert_eq!(set.names().collect::<Vec<_>>(), vec!["STARTAN2"]);
+        let mut damaged = texture1.clone();
+        damaged[..4].copy_from_slice(&i32::MAX.to_le_bytes());
+        assert_eq!(parse_texturex("TEXTURE1", &damaged), Err(TextureError::Truncated { lump: "TEXTURE1".to_string() }));
+
+        let colors: Vec<u8> = (0..=255u8).flat_map(|i| [i, 0, 0]).collect();
+        let palette = Palette::from_bytes(&colors).unwrap();
+        let image = set
+            .composite("startan2", &palette, |name| (name == "WALL00_1").then(patch))
+            .unwrap();
+        assert_eq!(image.dimensions(), (4, 3));
+        assert_eq!(image.get_pixel(1, 1).0, [7, 0, 0, 255]);
+        assert_eq!(image.get_pixel(3, 2).0, [8, 0, 0, 255]);
+        assert_eq!(image.get_pixel(0, 1).0, [0, 0, 0, 0]);
+        assert_eq!(image.get_pixel(2, 1).0, [0, 0, 0, 0]);
+        assert_eq!(
+            set.composite("NOSUCH", &palette, |_| None),
+            Err(TextureError::UnknownTexture("NOSUCH".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_encode_picture() {
+        let colors: Vec<u8> = (0..=255u8).flat_map(|i| [i, i, i]).collect();
+        let palette = Palette::from_bytes(&colors).unwrap();
+        // A tall column: gaps at rows 2 and 280, everything else opaque.
+        let image = RgbaImage::from_fn(2, 600, |x, y| {
+            let alpha = if y == 2 || y == 280 || x == 1 && y > 500 { 0 } else { 255 };
+            image::Rgba([(y % 256) as u8, (y % 256) as u8, (y % 256) as u8, alpha])
+        });
+        let mut picture = Picture::from_rgba(&image, &palette);
+        picture.left_offset = -3;
+        picture.top_offset = 12;
+        let decoded = Picture::decode("TALL", &picture.encode()).unwrap();
+        assert_eq!(decoded, picture);
+        let rgba = decoded.to_rgba(&palette);
+        assert_eq!(rgba.get_pixel(0, 400).0, [144, 144, 144, 255]);
+        assert_eq!(rgba.get_pixel(0, 280).0, [0, 0, 0, 0]);
+        assert_eq!(rgba.get_pixel(1, 501).0, [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_decode_flat() {
+        let colors: Vec<u8> = (0..=255u8).flat_map(|i| [i, i, 0]).collect();
+        let palette = Palette::from_bytes(&colors).unwrap();
+        let data: Vec<u8> = (0..4096).map(|i| (i % 256) as u8).collect();
+        let image = decode_flat("FLOOR0_1", &data, &palette).unwrap();
+        assert_eq!(image.dimensions(), (64, 64));
+        assert_eq!(image.get_pixel(3, 1).0, [67, 67, 0, 255]);
+        assert_eq!(flat_size(4160), Some((64, 64)));
+        assert_eq!(flat_size(16384), Some((128, 128)));
+        assert!(decode_flat("SHORT", &data[..100], &palette).is_err());
+    }
+}
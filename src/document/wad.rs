@@ -0,0 +1,329 @@
+// src/document/wad.rs
+
+use std::io::{self, Seek, SeekFrom, Write};
+use std::sync::{Arc, OnceLock};
+use byteorder::{LE, ReadBytesExt, WriteBytesExt};
+use log::warn;
+
+use crate::document::diagnostics::{LoadMode, LoadReport, WadError, WadWarning};
+use crate::utils::adler::adler32;
+
+/// Size of the WAD header: 4 bytes (ident) + 4 bytes (numlumps) + 4 bytes (infotableofs).
+pub const HEADER_SIZE: usize = 12;
+
+const FILELUMP_SIZE: usize = 16; // 4 bytes (filepos) + 4 bytes (size) + 8 bytes (name)
+
+/// The contents of a whole WAD file, shared rather than copied: cloning is a
+/// reference count bump, and readers take slices of the one buffer.
+pub type WadBytes = Arc<[u8]>;
+
+/// A single lump entry from the WAD directory.
+#[derive(Debug, Clone)]
+pub struct LumpEntry {
+    pub offset: i32,
+    pub size: i32,
+    pub name: String,
+    /// Hashes of the lump's data, worked out the first time they are asked
+    /// for; see [`LumpEntry::hash`].
+    hash: OnceLock<LumpHash>,
+}
+
+impl LumpEntry {
+    pub fn new(offset: i32, size: i32, name: impl Into<String>) -> Self {
+        Self { offset, size, name: name.into(), hash: OnceLock::new() }
+    }
+
+    /// The hashes of this lump's data in `wad`, the WAD the entry was read
+    /// from. Loading does not hash anything; only comparisons need it.
+    pub fn hash(&self, wad: &[u8]) -> LumpHash {
+        *self.hash.get_or_init(|| {
+            let start = self.offset as usize;
+            LumpHash::of(&wad[start..start + self.size as usize])
+        })
+    }
+}
+
+/// Content hashes of a lump. Adler-32 is enough to spot a change; SHA-1
+/// tells apart lumps that merely collide.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct LumpHash {
+    pub adler32: u32,
+    pub sha1: [u8; 20],
+}
+
+impl LumpHash {
+    pub fn of(data: &[u8]) -> Self {
+        Self { adler32: adler32(data), sha1: sha1_smol::Sha1::from(data).digest().bytes() }
+    }
+}
+
+/// A named chunk of data ready to be written into a WAD.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lump {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+impl Lump {
+    pub fn new(name: impl Into<String>, data: Vec<u8>) -> Self {
+        Self { name: name.into(), data }
+    }
+
+    /// A zero-length lump, as used for level markers.
+    pub fn marker(name: impl Into<String>) -> Self {
+        Self::new(name, Vec::new())
+    }
+}
+
+/// Parses the header and directory of an in-memory WAD.
+/// Damaged entries are skipped and logged; use [`read_directory`] to get them
+/// as a report instead.
+pub fn parse_directory(data: &[u8]) -> io::Result<([u8; 12], Vec<LumpEntry>)> {
+    let (header, directory, report) = read_directory(data, LoadMode::Lenient)?;
+    for warning in &report.warnings {
+        warn!("{}", warning);
+    }
+    Ok((header, directory))
+}
+
+/// Parses the header and directory of an in-memory WAD, checking each entry.
+///
+/// In lenient mode a truncated directory is read as far as it goes, entries
+/// pointing outside the file are skipped, and bad names are patched up; every
+/// such fix is listed in the returned report. In strict mode the first one is
+/// an error.
+pub fn read_directory(data: &[u8], mode: LoadMode) -> Result<([u8; 12], Vec<LumpEntry>, LoadReport), WadError> {
+    if data.len() < HEADER_SIZE {
+        return Err(WadError::TooShort(data.len()));
+    }
+
+    // --- Read Header ---
+    let mut header = [0u8; HEADER_SIZE];
+    header.copy_from_slice(&data[..HEADER_SIZE]);
+    let ident = &header[0..4];
+    if ident != b"IWAD" && ident != b"PWAD" {
+        return Err(WadError::BadIdent(String::from_utf8_lossy(ident).to_string()));
+    }
+    let num_lumps = (&header[4..8]).read_i32::<LE>()?;
+    let infotableofs = (&header[8..12]).read_i32::<LE>()?;
+    if num_lumps < 0 || infotableofs < 0 || infotableofs as usize > data.len() {
+        return Err(WadError::BadDirectory { offset: infotableofs, count: num_lumps, file_size: data.len() });
+    }
+
+    // --- Read Directory ---
+    let mut report = LoadReport::default();
+    let declared = num_lumps as usize;
+    let dir_start = infotableofs as usize;
+    let present = declared.min((data.len() - dir_start) / FILELUMP_SIZE);
+    if present < declared {
+        report.push(mode, WadWarning::TruncatedDirectory { declared, present })?;
+    }
+
+    let mut directory = Vec::with_capacity(present);
+    // Original directory index of each kept entry, for messages.
+    let mut indices = Vec::with_capacity(present);
+    for index in 0..present {
+        let record = &data[dir_start + index * FILELUMP_SIZE..][..FILELUMP_SIZE];
+        let offset = (&record[0..4]).read_i32::<LE>()?;
+        let size = (&record[4..8]).read_i32::<LE>()?;
+        let name = match decode_lump_name(&record[8..16]) {
+            Ok(name) => name,
+            Err((raw, name)) => {
+                report.push(mode, WadWarning::BadLumpName { index, raw, name: name.clone() })?;
+                name
+            }
+        };
+        if offset < 0 || size < 0 || offset as u64 + size as u64 > data.len() as u64 {
+            report.push(mode, WadWarning::LumpOutOfBounds { index, name, offset, size, file_size: data.len() })?;
+            continue;
+        }
+        directory.push(LumpEntry::new(offset, size, name));
+        indices.push(index);
+    }
+
+    check_overlaps(&directory, &indices, mode, &mut report)?;
+    Ok((header, directory, report))
+}
+
+/// Decodes an 8-byte name field up to the first NUL. A name with bytes that
+/// are not printable ASCII comes back as `Err` with the raw bytes and a
+/// readable version where each bad byte is `?`.
+fn decode_lump_name(field: &[u8]) -> Result<String, (Vec<u8>, String)> {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    let raw = &field[..end];
+    let name: String = raw
+        .iter()
+        .map(|&b| if b.is_ascii_graphic() { b as char } else { '?' })
+        .collect();
+    if raw.iter().all(u8::is_ascii_graphic) {
+        Ok(name)
+    } else {
+        Err((raw.to_vec(), name))
+    }
+}
+
+/// Reports lumps whose data partly overlaps. Lumps that share exactly the same
+/// data (a common space saving) and empty lumps are fine.
+fn check_overlaps(
+    directory: &[LumpEntry],
+    indices: &[usize],
+    mode: LoadMode,
+    report: &mut LoadReport,
+) -> Result<(), WadError> {
+    let end = |i: usize| directory[i].offset as i64 + directory[i].size as i64;
+    let mut order: Vec<usize> = (0..directory.len()).filter(|&i| directory[i].size > 0).collect();
+    order.sort_by_key(|&i| (directory[i].offset, directory[i].size));
+
+    // The lump reaching furthest into the file so far.
+    let mut reach: Option<usize> = None;
+    for &i in &order {
+        if let Some(prev) = reach {
+            let same = directory[prev].offset == directory[i].offset && directory[prev].size == directory[i].size;
+            if (directory[i].offset as i64) < end(prev) && !same {
+                let (first, second) = if indices[prev] < indices[i] { (prev, i) } else { (i, prev) };
+                report.push(mode, WadWarning::OverlappingLumps {
+                    first: indices[first],
+                    first_name: directory[first].name.clone(),
+                    second: indices[second],
+                    second_name: directory[second].name.clone(),
+                })?;
+            }
+            if end(i) <= end(prev) {
+                continue;
+            }
+        }
+        reach = Some(i);
+    }
+    Ok(())
+}
+
+/// Writes a complete WAD file: header, lump data, then the directory.
+///
+/// `ident` is either `b"IWAD"` or `b"PWAD"`. Lumps are written in order and
+/// the directory is placed after the last lump, which is what most tools expect.
+pub fn write_wad<W: Write + Seek>(writer: &mut W, ident: &[u8; 4], lumps: &[Lump]) -> io::Result<()> {
+    let start = writer.stream_position()?;
+
+    // Reserve the header; the directory offset is only known at the end.
+    writer.write_all(&[0u8; HEADER_SIZE])?;
+
+    let mut entries = Vec::with_capacity(lumps.len());
+    let mut pos = HEADER_SIZE as u64;
+    for lump in lumps {
+        // Zero-size lumps conventionally point at the start of the data area.
+        let offset = if lump.data.is_empty() { HEADER_SIZE as u64 } else { pos };
+        writer.write_all(&lump.data)?;
+        pos += lump.data.len() as u64;
+        entries.push((offset, lump.data.len() as u64, lump.name.as_str()));
+    }
+
+    if pos > i32::MAX as u64 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "WAD exceeds 2 GB limit"));
+    }
+
+    let dir_offset = pos;
+    for (offset, size, name) in &entries {
+        writer.write_i32::<LE>(*offset as i32)?;
+        writer.write_i32::<LE>(*size as i32)?;
+        write_lump_name(writer, name)?;
+    }
+
+    // Go back and fill in the header.
+    let end = writer.stream_position()?;
+    writer.seek(SeekFrom::Start(start))?;
+    writer.write_all(ident)?;
+    writer.write_i32::<LE>(lumps.len() as i32)?;
+    writer.write_i32::<LE>(dir_offset as i32)?;
+    writer.seek(SeekFrom::Start(end))?;
+    writer.flush()
+}
+
+/// Writes an 8-byte lump name, uppercased and zero-padded (truncated if longer).
+pub fn write_lump_name<W: Write>(writer: &mut W, name: &str) -> io::Result<()> {
+    let mut buf = [0u8; 8];
+    for (i, b) in name.to_ascii_uppercase().bytes().take(8).enumerate() {
+        buf[i] = b;
+    }
+    writer.write_all(&buf)
+}
+
+/// True if `name` fits in a directory entry: 1 to 8 printable ASCII characters.
+pub fn is_valid_lump_name(name: &str) -> bool {
+    (1..=8).contains(&name.len()) && name.bytes().all(|b| b.is_ascii_graphic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_write_wad_layout() {
+        let lumps = vec![
+            Lump::marker("MAP01"),
+            Lump::new("things", vec![1, 2, 3, 4]),
+            Lump::new("VERTEXES", vec![5, 6]),
+        ];
+        let mut cursor = Cursor::new(Vec::new());
+        write_wad(&mut cursor, b"PWAD", &lumps).unwrap();
+        let data = cursor.into_inner();
+
+        assert_eq!(&data[0..4], b"PWAD");
+        let num_lumps = (&data[4..8]).read_i32::<LE>().unwrap();
+        let dir_ofs = (&data[8..12]).read_i32::<LE>().unwrap() as usize;
+        assert_eq!(num_lumps, 3);
+        assert_eq!(dir_ofs, HEADER_SIZE + 6);
+        assert_eq!(data.len(), dir_ofs + 3 * 16);
+
+        // Second entry: THINGS, uppercased, right after the header.
+        let entry = &data[dir_ofs + 16..dir_ofs + 32];
+        assert_eq!((&entry[0..4]).read_i32::<LE>().unwrap(), HEADER_SIZE as i32);
+        assert_eq!((&entry[4..8]).read_i32::<LE>().unwrap(), 4);
+        assert_eq!(&entry[8..16], b"THINGS\0\0");
+        assert_eq!(&data[HEADER_SIZE..HEADER_SIZE + 4], &[1, 2, 3, 4]);
+
+        let (_, directory) = parse_directory(&data).unwrap();
+        assert_eq!(directory[1].hash(&data), LumpHash::of(&[1, 2, 3, 4]));
+        assert_eq!(directory[0].hash(&data).adler32, 1);
+        assert_eq!(LumpHash::of(b"abc").sha1[..4], [0xa9, 0x99, 0x3e, 0x36]);
+    }
+
+    fn header(num_lumps: i32, dir_offset: i32) -> Vec<u8> {
+        let mut data = b"PWAD".to_vec();
+        data.write_i32::<LE>(num_lumps).unwrap();
+        data.write_i32::<LE>(dir_offset).unwrap();
+        data
+    }
+
+    fn entry(data: &mut Vec<u8>, offset: i32, size: i32, name: &[u8; 8]) {
+        data.write_i32::<LE>(offset).unwrap();
+        data.write_i32::<LE>(size).unwrap();
+        data.extend_from_slice(name);
+    }
+
+    #[test]
+    fn test_directory_report() {
+        // 16 bytes of lump data, then a directory claiming 5 entries of which 4 exist.
+        let mut data = header(5, 28);
+        data.extend_from_slice(&[0u8; 16]);
+        entry(&mut data, 12, 8, b"GOOD\0\0\0\0");
+        entry(&mut data, 16, 8, b"OVERLAP\0");
+        entry(&mut data, 12, 500, b"TOOBIG\0\0");
+        entry(&mut data, 20, 0, b"BAD\xff\0\0\0\0");
+
+        let (_, directory, report) = read_directory(&data, LoadMode::Lenient).unwrap();
+        let names: Vec<&str> = directory.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["GOOD", "OVERLAP", "BAD?"]);
+        assert_eq!(report.warnings, vec![
+            WadWarning::TruncatedDirectory { declared: 5, present: 4 },
+            WadWarning::LumpOutOfBounds { index: 2, name: "TOOBIG".into(), offset: 12, size: 500, file_size: 92 },
+            WadWarning::BadLumpName { index: 3, raw: b"BAD\xff".to_vec(), name: "BAD?".into() },
+            WadWarning::OverlappingLumps { first: 0, first_name: "GOOD".into(), second: 1, second_name: "OVERLAP".into() },
+        ]);
+
+        match read_directory(&data, LoadMode::Strict) {
+            Err(WadError::Strict(WadWarning::TruncatedDirectory { .. })) => {}
+            other => panic!("expected a strict-mode error, got {:?}", other.map(|r| r.2)),
+        }
+    }
+}
@@ -202,6 +202,7 @@ impl Command for CommandType {
                     tag: *tag,
                     right: *right,
                     left: *left,
+                    ..Default::default()
                 };
                 let linedefs_arc = document.linedefs();
                 let mut linedefs = linedefs_arc.write();
@@ -262,6 +263,7 @@ impl Command for CommandType {
                     lower_tex: lower_tex.clone(),
                     mid_tex: mid_tex.clone(),
                     sector: *sector,
+                    ..Default::default()
                 };
                 let sidedefs_arc = document.sidedefs();
                 let mut sidedefs = sidedefs_arc.write();
@@ -330,6 +332,7 @@ impl Command for CommandType {
                     light: *light,
                     r#type: *r#type,
                     tag: *tag,
+                    ..Default::default()
                 };
                 let sectors_arc = document.sectors();
                 let mut sectors = sectors_arc.write();
@@ -435,6 +438,7 @@ impl Command for CommandType {
                     angle: *angle,
                     doom_type: *doom_type,
                     flags: *flags,
+                    ..Default::default()
                 };
                 let things_arc = document.things();
                 let mut things = things_arc.write();
@@ -1,25 +1,50 @@
 // src/editor/core.rs
 
-use std::fs::File;
-use std::io::Cursor;
+use std::fs::{self, File};
+use std::io::{self, Cursor};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use futures::FutureExt;
 use log::{error, info};
 use parking_lot::RwLock;
 use rfd::FileDialog;
 
-use crate::bsp::BspLevel;
+use crate::bsp::{BspLevel, BuildOptions, NodeFormat, PartitionOptions, RejectMode};
 use crate::document::Document;
+use crate::document::archive::Pk3Archive;
+use crate::document::diagnostics::{LoadMode, LoadReport};
+use crate::document::diff::{diff_wads, WadDiff};
+use crate::document::overview::{Overview, OverviewOptions};
+use crate::document::resources::{ResourceManager, ResourceWad};
 use crate::editor::commands::{Command, CommandType};
 use crate::ui::central_panel::CentralPanel;
+use crate::utils::tga;
 use eframe::egui;
-use crate::editor::tools::{Tool, SelectTool, DrawLineTool, DrawShapeTool, ThingsTool, SectorsTool};
+use crate::editor::tools::{Tool, Selection, SelectTool, DrawLineTool, DrawShapeTool, ThingsTool, SectorsTool};
+
+/// Where the current document lives on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DocumentSource {
+    /// A plain WAD file.
+    Wad(PathBuf),
+    /// A map WAD inside a PK3/ZIP archive, e.g. `maps/map01.wad`.
+    Archive { path: PathBuf, entry: String },
+}
 
 /// The core `Editor` struct, holding geometry references, current tool, etc.
 pub struct Editor {
     /// The current document, if any.
     document: Option<Arc<RwLock<Document>>>,
 
+    /// Where the current document was loaded from / last saved to.
+    source: Option<DocumentSource>,
+
+    /// Map WADs of the open archive, if the document came from one.
+    archive_maps: Vec<String>,
+
+    /// Read-only IWAD/PWAD resources, shared with every document.
+    resources: Arc<RwLock<ResourceManager>>,
+
     /// Currently active tool and available tools
     current_tool: Box<dyn Tool>,
     tools: Vec<Box<dyn Tool>>,
@@ -35,6 +60,25 @@ pub struct Editor {
     /// Whether the side panel or BSP debug is shown.
     pub show_side_panel: bool,
     pub show_bsp_debug: bool,
+    /// Whether the lump manager window is open.
+    pub show_lump_panel: bool,
+    /// Whether the load report window is open.
+    pub show_load_report: bool,
+    /// Whether the ACS script viewer is open.
+    pub show_script_viewer: bool,
+    /// Whether the texture and flat browser is open.
+    pub show_texture_browser: bool,
+    /// The last WAD comparison and the file compared with; shown while set.
+    pub wad_diff: Option<(PathBuf, WadDiff)>,
+
+    /// How damaged WADs are handled when opening files.
+    pub load_mode: LoadMode,
+    /// Whether node builds sample partition candidates on large maps.
+    pub fast_nodes: bool,
+    /// How node builds fill the REJECT lump.
+    pub reject_mode: RejectMode,
+    /// The node format to save in; `None` picks one the level fits.
+    pub node_format: Option<NodeFormat>,
 
     /// A handle to the central panel (camera, pan/zoom) if needed.
     central_panel: Option<Arc<RwLock<CentralPanel>>>,
@@ -55,8 +99,14 @@ impl Editor {
             Box::new(SectorsTool::default()),
         ];
 
+        let resources = Arc::new(RwLock::new(ResourceManager::new()));
+        document.write().resources = Arc::clone(&resources);
+
         Self {
             document: Some(document),
+            source: None,
+            archive_maps: Vec::new(),
+            resources,
             current_tool: Box::new(SelectTool::default()),
             tools,
             command_history: Vec::new(),
@@ -65,6 +115,15 @@ impl Editor {
             error_message: None,
             show_side_panel: true,
             show_bsp_debug: false,
+            show_lump_panel: false,
+            show_load_report: false,
+            show_script_viewer: false,
+            show_texture_browser: false,
+            wad_diff: None,
+            load_mode: LoadMode::Lenient,
+            fast_nodes: false,
+            reject_mode: RejectMode::default(),
+            node_format: None,
             central_panel: None,
             bsp_level: None,
         }
@@ -82,6 +141,8 @@ impl Editor {
 
     /// Sets a new Document, discarding the old one.
     pub fn set_document(&mut self, document: Arc<RwLock<Document>>) {
+        document.write().resources = Arc::clone(&self.resources);
+        document.read().resources_changed();
         self.document = Some(document);
         self.error_message = None;
         self.command_history.clear();
@@ -168,32 +229,53 @@ impl Editor {
 
     /// Create a brand new, empty document.
     pub fn new_document(&mut self) {
-        self.document = Some(Arc::new(RwLock::new(Document::new())));
+        let mut doc = Document::new();
+        doc.resources = Arc::clone(&self.resources);
+        doc.rebuild_thing_catalog();
+        self.document = Some(Arc::new(RwLock::new(doc)));
+        self.source = None;
+        self.archive_maps.clear();
         self.command_history.clear();
         self.redo_stack.clear();
         self.status_message = "Created new document.".to_string();
         self.error_message = None;
     }
 
-    /// Opens a file dialog to pick a WAD, and loads it.
+    /// Opens a file dialog to pick a WAD or PK3, and loads it.
     pub fn show_open_dialog(&mut self) {
         if let Some(path) = FileDialog::new()
             .add_filter("WAD Files", &["wad"])
+            .add_filter("PK3/ZIP Archives", &["pk3", "zip"])
             .pick_file() 
         {
+            if is_archive_path(&path) {
+                if let Err(e) = self.open_archive(path) {
+                    error!("Archive load error: {}", e);
+                    self.error_message = Some(e);
+                }
+                return;
+            }
             let path_str = path.to_string_lossy().to_string();
             match File::open(&path) {
                 Ok(mut file) => {
                     let mut new_doc = Document::new();
-                    if let Err(e) = new_doc.load_wad(&mut file) {
-                        error!("WAD load error: {}", e);
-                        self.error_message = Some(format!("Failed to load WAD: {}", e));
-                    } else {
-                        self.document = Some(Arc::new(RwLock::new(new_doc)));
-                        self.command_history.clear();
-                        self.redo_stack.clear();
-                        self.status_message = format!("Loaded WAD file: {}", path_str);
-                        self.error_message = None;
+                    new_doc.resources = Arc::clone(&self.resources);
+                    new_doc.load_mode = self.load_mode;
+                    match new_doc.load_wad(&mut file) {
+                        Err(e) => {
+                            error!("WAD load error: {}", e);
+                            self.error_message = Some(format!("Failed to load WAD: {}", e));
+                        }
+                        Ok(report) => {
+                            self.document = Some(Arc::new(RwLock::new(new_doc)));
+                            self.source = Some(DocumentSource::Wad(path.clone()));
+                            self.archive_maps.clear();
+                            self.command_history.clear();
+                            self.redo_stack.clear();
+                            self.status_message = format!("Loaded WAD file: {}", path_str);
+                            self.error_message = None;
+                            self.note_load_report(&report);
+                        }
                     }
                 }
                 Err(e) => {
@@ -204,18 +286,158 @@ impl Editor {
         }
     }
 
-    /// Save the current document.
-    pub fn save_document(&self) -> Result<(), String> {
-        if let Some(doc_arc) = &self.document {
-            let doc = doc_arc.read();
-            // Implement actual save logic here
-            info!("Document saved.");
-            Ok(())
-        } else {
-            Err("No document available to save.".into())
+    /// Opens a PK3/ZIP archive. Its loose files go on top of the resource
+    /// stack and the first map WAD under `maps/` is loaded for editing.
+    pub fn open_archive(&mut self, path: PathBuf) -> Result<(), String> {
+        let archive = Pk3Archive::load(&path)
+            .map_err(|e| format!("Failed to read archive {}: {}", path.display(), e))?;
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.display().to_string());
+        let wad = ResourceWad::from_lumps(name, archive.lumps())
+            .map_err(|e| format!("Failed to index archive {}: {}", path.display(), e))?;
+        self.resources.write().add_pwad(wad);
+
+        let maps = archive.map_wads();
+        let entry = match maps.first() {
+            Some(entry) => entry.clone(),
+            None => {
+                self.refresh_thing_catalog();
+                self.status_message = format!("No maps in {}; loaded its resources.", path.display());
+                return Ok(());
+            }
+        };
+        self.archive_maps = maps;
+        self.load_archive_entry(path, &archive, &entry)
+    }
+
+    /// Switches to another map WAD of the open archive.
+    pub fn open_archive_map(&mut self, entry: &str) -> Result<(), String> {
+        let path = match &self.source {
+            Some(DocumentSource::Archive { path, .. }) => path.clone(),
+            _ => return Err("No archive is open.".to_string()),
+        };
+        let archive = Pk3Archive::load(&path)
+            .map_err(|e| format!("Failed to read archive {}: {}", path.display(), e))?;
+        self.load_archive_entry(path, &archive, entry)
+    }
+
+    fn load_archive_entry(&mut self, path: PathBuf, archive: &Pk3Archive, entry: &str) -> Result<(), String> {
+        let data = archive
+            .get(entry)
+            .ok_or_else(|| format!("{} not found in {}", entry, path.display()))?;
+        let mut new_doc = Document::new();
+        new_doc.resources = Arc::clone(&self.resources);
+        new_doc.load_mode = self.load_mode;
+        let report = new_doc
+            .load_wad(&mut Cursor::new(data))
+            .map_err(|e| format!("Failed to load {}: {}", entry, e))?;
+        self.set_document(Arc::new(RwLock::new(new_doc)));
+        self.status_message = format!("Loaded {} from {}", entry, path.display());
+        self.note_load_report(&report);
+        self.source = Some(DocumentSource::Archive { path, entry: entry.to_string() });
+        Ok(())
+    }
+
+    /// Mentions load problems in the status line and opens the report window.
+    fn note_load_report(&mut self, report: &LoadReport) {
+        if !report.is_empty() {
+            self.status_message = format!("{} ({} problem(s), see View > Load Report)", self.status_message, report.len());
+            self.show_load_report = true;
         }
     }
 
+    /// Where the current document was loaded from, if anywhere.
+    pub fn source(&self) -> Option<&DocumentSource> {
+        self.source.as_ref()
+    }
+
+    /// Map WADs of the open archive; empty when editing a plain WAD.
+    pub fn archive_maps(&self) -> &[String] {
+        &self.archive_maps
+    }
+
+    /// The resource stack (IWAD plus PWADs) used for textures, flats and palettes.
+    pub fn resources(&self) -> Arc<RwLock<ResourceManager>> {
+        Arc::clone(&self.resources)
+    }
+
+    /// Asks for one or more WAD, PK3 or DeHackEd files and adds them to the
+    /// resource stack. An IWAD becomes the base; the rest go on top in the order picked.
+    pub fn load_resources_dialog(&mut self) {
+        let paths = match FileDialog::new()
+            .add_filter("WAD Files", &["wad"])
+            .add_filter("PK3/ZIP Archives", &["pk3", "zip"])
+            .add_filter("DeHackEd Patches", &["deh", "bex"])
+            .pick_files()
+        {
+            Some(paths) => paths,
+            None => return,
+        };
+        for path in paths {
+            if let Err(e) = self.resources.write().load_file(&path) {
+                error!("Resource load error: {}", e);
+                self.error_message = Some(format!("Failed to load resource {}: {}", path.display(), e));
+                self.refresh_thing_catalog();
+                return;
+            }
+        }
+        self.refresh_thing_catalog();
+        let count = self.resources.read().wads().len();
+        self.status_message = format!("{} resource WAD(s) loaded.", count);
+        self.error_message = None;
+    }
+
+    pub fn clear_resources(&mut self) {
+        self.resources.write().clear();
+        self.refresh_thing_catalog();
+        self.status_message = "Resources cleared.".to_string();
+    }
+
+    /// Re-applies DEHACKED patches and drops the cached palette after the
+    /// resource stack changed.
+    fn refresh_thing_catalog(&self) {
+        if let Some(doc) = &self.document {
+            doc.read().resources_changed();
+        }
+    }
+
+    /// Save the current document.
+    ///
+    /// Writes to the file the document was opened from; new documents
+    /// prompt for a destination first. Maps from an archive are written back
+    /// into their entry, leaving the other files of the archive as they are.
+    pub fn save_document(&mut self) -> Result<(), String> {
+        let doc_arc = self.document.clone()
+            .ok_or_else(|| "No document available to save.".to_string())?;
+        doc_arc.write().build_options = self.build_options();
+
+        let path = match &self.source {
+            Some(DocumentSource::Archive { path, entry }) => {
+                return save_into_archive(&doc_arc.read(), path, entry);
+            }
+            Some(DocumentSource::Wad(path)) => path.clone(),
+            None => FileDialog::new()
+                .add_filter("WAD Files", &["wad"])
+                .set_file_name("untitled.wad")
+                .save_file()
+                .ok_or_else(|| "Save cancelled.".to_string())?,
+        };
+
+        // Build the whole WAD before touching the file, so a failed node
+        // build or limit check leaves the old one as it was.
+        let mut wad = Vec::new();
+        doc_arc.read().save_wad(&mut wad)
+            .map_err(|e| format!("Failed to build {}: {}", path.display(), e))?;
+        replace_file(&path, &wad)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+        info!("Document saved to {}.", path.display());
+        self.source = Some(DocumentSource::Wad(path));
+        Ok(())
+    }
+
     /// Wrapper for save_document that handles errors
     pub fn save_document_wrapper(&mut self) {
         match self.save_document() {
@@ -229,17 +451,103 @@ impl Editor {
         }
     }
 
+    /// Asks for a PNG, TGA or SVG file and writes an overview of the current
+    /// level to it, with sectors shaded by light level.
+    pub fn export_overview_dialog(&mut self) {
+        let doc_arc = match &self.document {
+            Some(doc) => Arc::clone(doc),
+            None => {
+                self.error_message = Some("No document loaded!".to_string());
+                return;
+            }
+        };
+        let (overview, map_name) = {
+            let doc = doc_arc.read();
+            (Overview::from_document(&doc), doc.map_name.clone())
+        };
+        let path = match FileDialog::new()
+            .add_filter("PNG Images", &["png"])
+            .add_filter("SVG Drawings", &["svg"])
+            .add_filter("TGA Images", &["tga"])
+            .set_file_name(format!("{}.png", if map_name.is_empty() { "overview" } else { &map_name }))
+            .save_file()
+        {
+            Some(path) => path,
+            None => return,
+        };
+
+        let options = OverviewOptions { sector_fills: true, ..Default::default() };
+        let extension = path.extension().map(|e| e.to_string_lossy().to_ascii_lowercase());
+        let result = match extension.as_deref() {
+            Some("svg") => std::fs::write(&path, overview.to_svg(&options)).map_err(|e| e.to_string()),
+            Some("tga") => tga::save(&overview.render(&options), &path, true).map_err(|e| e.to_string()),
+            _ => overview.render(&options).save(&path).map_err(|e| e.to_string()),
+        };
+        match result {
+            Ok(()) => {
+                self.status_message = format!("Overview written to {}.", path.display());
+                self.error_message = None;
+            }
+            Err(e) => {
+                error!("Overview export failed: {}", e);
+                self.error_message = Some(format!("Failed to write {}: {}", path.display(), e));
+            }
+        }
+    }
+
+    /// Asks for another WAD and compares the current one with it, treating
+    /// the picked file as the newer version. The result opens in the
+    /// comparison window. Unsaved geometry edits are not part of the current
+    /// WAD until saved.
+    pub fn compare_with_dialog(&mut self) {
+        let wad_data = self.document.as_ref().and_then(|doc| doc.read().wad_data.read().clone());
+        let wad_data = match wad_data {
+            Some(data) => data,
+            None => {
+                self.error_message = Some("Save or open a WAD before comparing.".to_string());
+                return;
+            }
+        };
+        let path = match FileDialog::new().add_filter("WAD Files", &["wad"]).pick_file() {
+            Some(path) => path,
+            None => return,
+        };
+        let result = std::fs::read(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|other| diff_wads(&wad_data, &other).map_err(|e| e.to_string()));
+        match result {
+            Ok(diff) => {
+                self.status_message = format!("{} lump change(s) against {}.", diff.lumps.len(), path.display());
+                self.error_message = None;
+                self.wad_diff = Some((path, diff));
+            }
+            Err(e) => {
+                error!("WAD comparison failed: {}", e);
+                self.error_message = Some(format!("Failed to compare with {}: {}", path.display(), e));
+            }
+        }
+    }
+
     /// Build the BSP tree from the current document
     pub fn build_nodes(&mut self) -> Result<(), String> {
         let doc_arc = self.document.as_ref()
             .ok_or_else(|| "No document loaded!".to_string())?;
 
-        let bsp = BspLevel::new(doc_arc.clone());
+        let bsp = BspLevel::with_options(doc_arc.clone(), self.build_options());
         bsp.build()?;
         self.bsp_level = Some(Arc::new(bsp));
         Ok(())
     }
 
+    /// The node builder settings chosen in the Tools menu.
+    pub fn build_options(&self) -> BuildOptions {
+        BuildOptions {
+            partition: if self.fast_nodes { PartitionOptions::fast() } else { PartitionOptions::default() },
+            reject: self.reject_mode,
+            node_format: self.node_format,
+        }
+    }
+
     /// Wrapper for build_nodes that handles errors
     pub fn build_nodes_wrapper(&mut self) {
         match self.build_nodes() {
@@ -270,6 +578,7 @@ impl Editor {
             let doc_arc = self.document.as_ref()
                 .ok_or_else(|| "No document present".to_string())?;
 
+            // Shares the document's buffer; no copy of the WAD is made.
             let wad_data = {
                 let doc_read = doc_arc.read();
                 let wad_data_read = doc_read.wad_data.read();
@@ -279,7 +588,7 @@ impl Editor {
 
             let mut cursor = Cursor::new(wad_data);
 
-            {
+            let report = {
                 let mut doc_write = doc_arc.write();
                 doc_write.load_level_async(&level, &mut cursor)
                     .await
@@ -293,13 +602,16 @@ impl Editor {
                         cp.set_pan(egui::vec2(-center.x, -center.y));
                     }
                 }
-            }
+                let report = doc_write.load_report.read().clone();
+                report
+            };
 
-            Ok::<(), String>(())
+            Ok::<LoadReport, String>(report)
         }) {
-            Ok(_) => {
+            Ok(report) => {
                 self.status_message = format!("Loaded level: {}", level);
                 self.error_message = None;
+                self.note_load_report(&report);
                 info!("Loaded level {} successfully.", level);
             }
             Err(e) => {
@@ -373,8 +685,47 @@ impl Editor {
         self.bsp_level.clone()
     }
 
+    /// What the current tool has selected.
+    pub fn selection(&self) -> Selection {
+        self.current_tool.selection()
+    }
+
     pub fn current_tool(&self) -> &dyn Tool {
         self.current_tool.as_ref()
     }
 
-}
\ No newline at end of file
+}
+
+fn is_archive_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("pk3") || ext.eq_ignore_ascii_case("zip"))
+}
+
+/// Writes `data` to a temporary file beside `path` and renames it over
+/// `path`, so the old file stays whole until the new one is complete.
+fn replace_file(path: &Path, data: &[u8]) -> io::Result<()> {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let temp = path.with_file_name(format!(".{}.tmp", name));
+    let result = fs::write(&temp, data).and_then(|_| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Saves `doc` as a WAD and stores it at `entry` in the archive at `path`.
+/// The archive is re-read first so files changed elsewhere are kept.
+fn save_into_archive(doc: &Document, path: &Path, entry: &str) -> Result<(), String> {
+    let mut archive = Pk3Archive::load(path)
+        .map_err(|e| format!("Failed to read archive {}: {}", path.display(), e))?;
+    let mut wad = Vec::new();
+    doc.save_wad(&mut wad)
+        .map_err(|e| format!("Failed to build {}: {}", entry, e))?;
+    archive.set(entry, wad);
+    let mut data = Cursor::new(Vec::new());
+    archive.write(&mut data)
+        .and_then(|_| replace_file(path, data.get_ref()))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    info!("Saved {} into {}.", entry, path.display());
+    Ok(())
+}
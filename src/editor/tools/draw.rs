@@ -118,6 +118,7 @@ impl DrawLineTool {
                 lower_tex: String::new(),
                 mid_tex: self.default_wall_tex.clone(),
                 sector: 0,
+                ..Default::default()
             };
 
             // Add end vertex
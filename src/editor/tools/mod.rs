@@ -16,6 +16,13 @@ use crate::document::Document;
 use std::sync::Arc;
 use parking_lot::RwLock;
 
+/// Map objects a tool has selected, by index.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Selection {
+    pub linedefs: Vec<usize>,
+    pub sectors: Vec<usize>,
+}
+
 pub trait Tool {
     fn name(&self) -> &'static str;
     fn handle_input(
@@ -30,6 +37,11 @@ pub trait Tool {
     );
     fn draw(&mut self, ui: &mut egui::Ui, doc: &Arc<RwLock<Document>>);
     fn cleanup(&mut self);
+    /// What the tool has selected; textures and flats picked elsewhere are
+    /// applied to it.
+    fn selection(&self) -> Selection {
+        Selection::default()
+    }
 }
 
 // Grid settings struct
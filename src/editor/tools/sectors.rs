@@ -1,7 +1,8 @@
-use super::{Tool, GridSettings};
+use super::{Tool, GridSettings, Selection};
 use crate::document::Document;
 use crate::editor::commands::{Command, CommandType, SectorProperties};
 use crate::map::{Sector, LineDef};
+use crate::ui::previews::{PreviewCache, PreviewKind};
 use eframe::egui;
 use std::sync::Arc;
 use parking_lot::RwLock;
@@ -52,23 +53,9 @@ impl CommonSectorType {
     }
 }
 
-const COMMON_FLATS: &[(&str, &str)] = &[
-    ("FLOOR0_1", "Basic Floor"),
-    ("FLOOR0_3", "Carpet"),
-    ("FLOOR1_1", "Metal"),
-    ("FLOOR4_1", "Blue Tiles"),
-    ("FLOOR4_5", "Green Tiles"),
-    ("FLOOR4_8", "Red Tiles"),
-    ("CEIL1_1", "Basic Ceiling"),
-    ("CEIL3_1", "Brown Ceiling"),
-    ("CEIL3_2", "Metal Ceiling"),
-    ("FLAT1", "Light Panel"),
-    ("FLAT14", "Water"),
-    ("FLAT5_4", "Blue Panel"),
-    ("NUKAGE1", "Nukage"),
-    ("BLOOD1", "Blood"),
-    ("LAVA1", "Lava"),
-];
+/// Size of the flat thumbnails in the properties panel.
+const THUMBNAIL_SIZE: f32 = 48.0;
+const THUMBNAILS_PER_ROW: usize = 4;
 
 pub struct SectorsTool {
     grid_settings: GridSettings,
@@ -82,6 +69,9 @@ pub struct SectorsTool {
     tag: i32,
     show_heights: bool,
     show_light_levels: bool,
+    flat_previews: PreviewCache,
+    /// Filter typed into the flat pickers.
+    flat_filter: String,
 }
 
 impl Default for SectorsTool {
@@ -98,6 +88,8 @@ impl Default for SectorsTool {
             tag: 0,
             show_heights: true,
             show_light_levels: true,
+            flat_previews: PreviewCache::default(),
+            flat_filter: String::new(),
         }
     }
 }
@@ -158,12 +150,20 @@ impl Tool for SectorsTool {
             }
         }
 
-        self.draw_properties_panel(ui, doc, &sectors);
+        self.flat_previews.refresh(doc, &doc_read);
+        self.draw_properties_panel(ui, doc, &doc_read, &sectors);
     }
 
     fn cleanup(&mut self) {
         self.selected_sector = None;
     }
+
+    fn selection(&self) -> Selection {
+        Selection {
+            linedefs: Vec::new(),
+            sectors: self.selected_sector.into_iter().collect(),
+        }
+    }
 }
 
 impl SectorsTool {
@@ -297,7 +297,7 @@ impl SectorsTool {
         }
     }
 
-    fn draw_properties_panel(&mut self, ui: &mut egui::Ui, doc: &Arc<RwLock<Document>>, sectors: &[Arc<Sector>]) {
+    fn draw_properties_panel(&mut self, ui: &mut egui::Ui, doc: &Arc<RwLock<Document>>, doc_read: &Document, sectors: &[Arc<Sector>]) {
         egui::Window::new("Sector Properties")
             .default_pos([10.0, 30.0])
             .show(ui.ctx(), |ui| {
@@ -329,33 +329,8 @@ impl SectorsTool {
                             .text("Light Level")
                             .clamp_to_range(true));
 
-                        ui.label("Floor Texture:");
-                        egui::ComboBox::from_label("")
-                            .selected_text(&self.floor_tex)
-                            .show_ui(ui, |ui| {
-                                for &(flat, name) in COMMON_FLATS {
-                                    if ui.selectable_label(
-                                        self.floor_tex == flat,
-                                        name
-                                    ).clicked() {
-                                        self.floor_tex = flat.to_string();
-                                    }
-                                }
-                            });
-
-                        ui.label("Ceiling Texture:");
-                        egui::ComboBox::from_label("")
-                            .selected_text(&self.ceiling_tex)
-                            .show_ui(ui, |ui| {
-                                for &(flat, name) in COMMON_FLATS {
-                                    if ui.selectable_label(
-                                        self.ceiling_tex == flat,
-                                        name
-                                    ).clicked() {
-                                        self.ceiling_tex = flat.to_string();
-                                    }
-                                }
-                            });
+                        flat_picker(ui, "Floor", &mut self.floor_tex, &mut self.flat_previews, &mut self.flat_filter, doc_read);
+                        flat_picker(ui, "Ceiling", &mut self.ceiling_tex, &mut self.flat_previews, &mut self.flat_filter, doc_read);
 
                         ui.add(egui::Slider::new(&mut self.tag, 0..=255)
                             .text("Tag")
@@ -557,4 +532,74 @@ impl SectorsTool {
             (line.right == sector_b as i32 && line.left == sector_a as i32)
         })
     }
-}
\ No newline at end of file
+}
+
+/// The current flat of a sector with its thumbnail, and a searchable grid of
+/// every flat in the loaded WADs to pick another.
+fn flat_picker(
+    ui: &mut egui::Ui,
+    title: &str,
+    current: &mut String,
+    previews: &mut PreviewCache,
+    filter: &mut String,
+    doc: &Document,
+) {
+    let ctx = ui.ctx().clone();
+    ui.horizontal(|ui| {
+        match previews.get(&ctx, doc, PreviewKind::Flat, current) {
+            Some(texture) => {
+                ui.image(texture.id(), [THUMBNAIL_SIZE, THUMBNAIL_SIZE]);
+            }
+            None => {
+                ui.add_sized([THUMBNAIL_SIZE, THUMBNAIL_SIZE], egui::Label::new("?"));
+            }
+        }
+        ui.vertical(|ui| {
+            ui.label(format!("{} Texture:", title));
+            ui.text_edit_singleline(current);
+        });
+    });
+
+    egui::CollapsingHeader::new(format!("Choose {} Flat", title))
+        .id_source(("flat_picker", title))
+        .show(ui, |ui| {
+            ui.add(egui::TextEdit::singleline(filter).hint_text("Search"));
+            let needle = filter.to_ascii_uppercase();
+            let names: Vec<String> = previews
+                .flat_names()
+                .iter()
+                .filter(|name| name.contains(&needle))
+                .cloned()
+                .collect();
+            if names.is_empty() {
+                ui.label("No flats found. Load an IWAD with File > Load Resource WADs.");
+                return;
+            }
+            let rows = names.len().div_ceil(THUMBNAILS_PER_ROW);
+            egui::ScrollArea::vertical()
+                .id_source(("flat_grid", title))
+                .max_height(THUMBNAIL_SIZE * 4.5)
+                .show_rows(ui, THUMBNAIL_SIZE + 8.0, rows, |ui, range| {
+                    for row in range {
+                        ui.horizontal(|ui| {
+                            for name in names.iter().skip(row * THUMBNAILS_PER_ROW).take(THUMBNAILS_PER_ROW) {
+                                let selected = current.eq_ignore_ascii_case(name);
+                                let response = match previews.get(&ctx, doc, PreviewKind::Flat, name) {
+                                    Some(texture) => ui.add(
+                                        egui::ImageButton::new(texture.id(), [THUMBNAIL_SIZE, THUMBNAIL_SIZE])
+                                            .selected(selected),
+                                    ),
+                                    None => ui.add_sized(
+                                        [THUMBNAIL_SIZE, THUMBNAIL_SIZE],
+                                        egui::SelectableLabel::new(selected, name.as_str()),
+                                    ),
+                                };
+                                if response.on_hover_text(name.as_str()).clicked() {
+                                    *current = name.clone();
+                                }
+                            }
+                        });
+                    }
+                });
+        });
+}
@@ -1,6 +1,6 @@
 // src/editor/tools/select.rs
 
-use super::{Tool, GridSettings};
+use super::{Tool, GridSettings, Selection};
 use crate::bsp::BoundingBox;
 use crate::document::Document;
 use crate::map::{Vertex, LineDef, Sector, Thing};
@@ -160,6 +160,13 @@ impl Tool for SelectTool {
         self.drag_offset = None;
         self.initial_positions = None;
     }
+
+    fn selection(&self) -> Selection {
+        Selection {
+            linedefs: self.selection.linedefs.clone(),
+            sectors: self.selection.sectors.clone(),
+        }
+    }
 }
 
 impl SelectTool {
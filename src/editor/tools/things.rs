@@ -2,6 +2,7 @@
 
 use super::{Tool, GridSettings};
 use crate::document::Document;
+use crate::document::dehconsts::ThingCatalog;
 use crate::editor::commands::{Command, CommandType};
 use crate::map::Thing;
 use eframe::egui;
@@ -9,101 +10,15 @@ use std::sync::Arc;
 use parking_lot::RwLock;
 use std::f32::consts::PI;
 
-// Common DOOM thing types
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum CommonThingType {
-    Player1Start = 1,
-    Player2Start = 2,
-    Player3Start = 3,
-    Player4Start = 4,
-    DeathmatchStart = 11,
-    TeleportDest = 14,
-    GreenArmor = 2018,
-    BlueArmor = 2019,
-    HealthBonus = 2014,
-    ArmorBonus = 2015,
-    Medikit = 2012,
-    Stimpack = 2011,
-    Shotgun = 2001,
-    Chaingun = 2002,
-    RocketLauncher = 2003,
-    PlasmaRifle = 2004,
-    BFG9000 = 2006,
-    Imp = 3001,
-    Demon = 3002,
-    Baron = 3003,
-    Zombieman = 3004,
-    Cacodemon = 3005,
-    LostSoul = 3006,
-}
-
-impl CommonThingType {
-    fn name(&self) -> &'static str {
-        match self {
-            Self::Player1Start => "Player 1 Start",
-            Self::Player2Start => "Player 2 Start",
-            Self::Player3Start => "Player 3 Start",
-            Self::Player4Start => "Player 4 Start",
-            Self::DeathmatchStart => "Deathmatch Start",
-            Self::TeleportDest => "Teleport Destination",
-            Self::GreenArmor => "Green Armor",
-            Self::BlueArmor => "Blue Armor",
-            Self::HealthBonus => "Health Bonus",
-            Self::ArmorBonus => "Armor Bonus",
-            Self::Medikit => "Medikit",
-            Self::Stimpack => "Stimpack",
-            Self::Shotgun => "Shotgun",
-            Self::Chaingun => "Chaingun",
-            Self::RocketLauncher => "Rocket Launcher",
-            Self::PlasmaRifle => "Plasma Rifle",
-            Self::BFG9000 => "BFG9000",
-            Self::Imp => "Imp",
-            Self::Demon => "Demon",
-            Self::Baron => "Baron of Hell",
-            Self::Zombieman => "Zombieman",
-            Self::Cacodemon => "Cacodemon",
-            Self::LostSoul => "Lost Soul",
-        }
-    }
-
-    fn all() -> Vec<CommonThingType> {
-        vec![
-            Self::Player1Start,
-            Self::Player2Start,
-            Self::Player3Start,
-            Self::Player4Start,
-            Self::DeathmatchStart,
-            Self::TeleportDest,
-            Self::GreenArmor,
-            Self::BlueArmor,
-            Self::HealthBonus,
-            Self::ArmorBonus,
-            Self::Medikit,
-            Self::Stimpack,
-            Self::Shotgun,
-            Self::Chaingun,
-            Self::RocketLauncher,
-            Self::PlasmaRifle,
-            Self::BFG9000,
-            Self::Imp,
-            Self::Demon,
-            Self::Baron,
-            Self::Zombieman,
-            Self::Cacodemon,
-            Self::LostSoul,
-        ]
-    }
-}
-
 pub struct ThingsTool {
     grid_settings: GridSettings,
     selected_thing: Option<usize>,
     dragging_thing: Option<usize>,
     drag_start: Option<egui::Pos2>,
-    current_type: CommonThingType,
+    /// Editor number of the thing placed with shift-click.
+    current_type: i32,
     current_angle: i32,
     show_angles: bool,
-    custom_type: Option<i32>,
 }
 
 impl Default for ThingsTool {
@@ -113,10 +28,9 @@ impl Default for ThingsTool {
             selected_thing: None,
             dragging_thing: None,
             drag_start: None,
-            current_type: CommonThingType::Player1Start,
+            current_type: 1,
             current_angle: 0,
             show_angles: true,
-            custom_type: None,
         }
     }
 }
@@ -177,6 +91,7 @@ impl Tool for ThingsTool {
     fn draw(&mut self, ui: &mut egui::Ui, doc: &Arc<RwLock<Document>>) {
         let doc_read = doc.read();
         let things = doc_read.things.read();
+        let catalog = doc_read.thing_catalog.read();
 
         // Draw all things
         for (idx, thing) in things.iter().enumerate() {
@@ -189,9 +104,10 @@ impl Tool for ThingsTool {
             };
 
             // Draw thing circle
+            let radius = thing_radius(&catalog, thing.doom_type);
             ui.painter().circle_stroke(
                 pos,
-                if is_selected { 12.0 } else { 8.0 },
+                if is_selected { radius + 4.0 } else { radius },
                 egui::Stroke::new(if is_selected { 2.0 } else { 1.0 }, color),
             );
 
@@ -213,17 +129,35 @@ impl Tool for ThingsTool {
             .default_pos([10.0, 30.0])
             .show(ui.ctx(), |ui| {
                 ui.label("Thing Type:");
+                let current = catalog.find(self.current_type);
                 egui::ComboBox::from_label("")
-                    .selected_text(self.current_type.name())
+                    .selected_text(current.map_or_else(
+                        || format!("Type {}", self.current_type),
+                        |def| def.name.clone(),
+                    ))
                     .show_ui(ui, |ui| {
-                        for thing_type in CommonThingType::all() {
+                        for def in catalog.placeable() {
                             ui.selectable_value(
                                 &mut self.current_type,
-                                thing_type,
-                                thing_type.name()
+                                def.editor_number,
+                                format!("{} ({})", def.name, def.editor_number),
                             );
                         }
                     });
+                ui.add(egui::DragValue::new(&mut self.current_type).prefix("Number: "));
+                match catalog.find(self.current_type) {
+                    Some(def) => {
+                        ui.label(format!(
+                            "Radius: {}  Height: {}  Sprite: {}",
+                            def.radius,
+                            def.height,
+                            catalog.sprite_name(def).unwrap_or("none"),
+                        ));
+                    }
+                    None => {
+                        ui.label("Not defined by the game or its DEHACKED patches.");
+                    }
+                }
 
                 ui.add(egui::Slider::new(&mut self.current_angle, 0..=359)
                     .text("Angle")
@@ -236,7 +170,8 @@ impl Tool for ThingsTool {
                         ui.separator();
                         ui.label(format!("Selected Thing ID: {}", thing_id));
                         ui.label(format!("Position: ({}, {})", thing.x, thing.y));
-                        ui.label(format!("Type: {}", thing.doom_type));
+                        let name = catalog.find(thing.doom_type).map_or("Unknown", |def| def.name.as_str());
+                        ui.label(format!("Type: {} ({})", thing.doom_type, name));
                         ui.label(format!("Angle: {}", thing.angle));
                         ui.label(format!("Flags: {:#04x}", thing.flags));
                     }
@@ -263,12 +198,11 @@ impl ThingsTool {
     }
 
     fn place_thing(&mut self, doc: &Arc<RwLock<Document>>, pos: egui::Pos2) {
-        let doom_type = self.custom_type.unwrap_or(self.current_type as i32);
         let mut cmd = CommandType::AddThing {
             x: pos.x as i32,
             y: pos.y as i32,
             angle: self.current_angle,
-            doom_type,
+            doom_type: self.current_type,
             flags: 0x0007, // Default flags - Easy, Medium, Hard difficulties
             thing_id: None,
         };
@@ -282,11 +216,12 @@ impl ThingsTool {
     fn select_thing_at(&mut self, doc: &Arc<RwLock<Document>>, pos: egui::Pos2) {
         let doc_read = doc.read();
         let things = doc_read.things.read();
+        let catalog = doc_read.thing_catalog.read();
 
         self.selected_thing = things.iter().enumerate()
             .find(|(_, thing)| {
                 let thing_pos = egui::pos2(thing.x as f32, thing.y as f32);
-                thing_pos.distance(pos) < 12.0
+                thing_pos.distance(pos) < thing_radius(&catalog, thing.doom_type).max(12.0)
             })
             .map(|(idx, _)| idx);
     }
@@ -328,4 +263,10 @@ impl ThingsTool {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// The radius a thing is drawn with: its catalog size, or 8 for types the
+/// catalog does not know.
+fn thing_radius(catalog: &ThingCatalog, doom_type: i32) -> f32 {
+    catalog.find(doom_type).map_or(8.0, |def| def.radius as f32)
+}
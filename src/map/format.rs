@@ -0,0 +1,43 @@
+// src/map/format.rs
+
+/// The binary layout used for a level's THINGS and LINEDEFS lumps.
+///
+/// Hexen-format levels (also used by ZDoom's "Doom in Hexen" maps) are
+/// recognised by the presence of a BEHAVIOR lump, UDMF levels by a TEXTMAP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MapFormat {
+    /// Vanilla Doom: 10-byte things, 14-byte linedefs.
+    #[default]
+    Doom,
+    /// Hexen: 20-byte things, 16-byte linedefs, plus a BEHAVIOR lump.
+    Hexen,
+    /// UDMF: a text TEXTMAP lump between the marker and ENDMAP.
+    Udmf,
+}
+
+impl MapFormat {
+    /// Size in bytes of one entry in the THINGS lump.
+    /// UDMF levels have no binary lumps; stray ones are read as Doom.
+    pub fn thing_size(self) -> usize {
+        match self {
+            MapFormat::Doom | MapFormat::Udmf => 10,
+            MapFormat::Hexen => 20,
+        }
+    }
+
+    /// Size in bytes of one entry in the LINEDEFS lump.
+    pub fn linedef_size(self) -> usize {
+        match self {
+            MapFormat::Doom | MapFormat::Udmf => 14,
+            MapFormat::Hexen => 16,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MapFormat::Doom => "Doom",
+            MapFormat::Hexen => "Hexen",
+            MapFormat::Udmf => "UDMF",
+        }
+    }
+}
@@ -3,6 +3,8 @@
 use std::io::{self, Read, Seek, Write};
 use byteorder::{LE, ReadBytesExt, WriteBytesExt};
 
+use super::udmf::UdmfFields;
+
 /// A linedef in classic DOOM format.
 /// 
 /// Fields:
@@ -11,7 +13,9 @@ use byteorder::{LE, ReadBytesExt, WriteBytesExt};
 ///  - `line_type` is the "special type" or action (i16 in WAD).
 ///  - `tag` is the sector tag used by many actions (i16 in WAD).
 ///  - `right` and `left` are indexes into the sidedef array (i16 in WAD, -1 for "none").
-#[derive(Debug, Clone, PartialEq)]
+///  - `args` are the five special arguments of Hexen-format maps. In that format
+///    `line_type` holds the (8-bit) special and `tag` is unused.
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct LineDef {
     pub start: usize,   // DOOM stores this as unsigned 16-bit
     pub end: usize,     // DOOM stores this as unsigned 16-bit
@@ -20,6 +24,8 @@ pub struct LineDef {
     pub tag: i32,       // DOOM stores this as signed 16-bit
     pub right: i32,     // DOOM stores this as signed 16-bit
     pub left: i32,      // DOOM stores this as signed 16-bit
+    pub args: [u8; 5],  // Hexen only
+    pub udmf: UdmfFields, // original UDMF fields, empty for binary maps
 }
 
 impl LineDef {
@@ -46,6 +52,8 @@ impl LineDef {
             tag,
             right,
             left,
+            args: [0; 5],
+            udmf: UdmfFields::new(),
         }
     }
 
@@ -75,6 +83,8 @@ impl LineDef {
             tag: reader.read_i16::<LE>()? as i32,
             right: reader.read_i16::<LE>()? as i32,
             left: reader.read_i16::<LE>()? as i32,
+            args: [0; 5],
+            udmf: UdmfFields::new(),
         })
     }
 
@@ -106,6 +116,58 @@ impl LineDef {
         Ok(())
     }
 
+    /// Reads a linedef in the 16-byte Hexen format.
+    ///
+    /// **Layout**:
+    /// ```text
+    /// offset  field       type
+    /// ------  ----------  -----------
+    /// 0-1     start       u16
+    /// 2-3     end         u16
+    /// 4-5     flags       i16
+    /// 6       special     u8   (stored in `line_type`)
+    /// 7-11    args        5 x u8
+    /// 12-13   right_side  i16
+    /// 14-15   left_side   i16
+    /// ```
+    ///
+    /// # Errors
+    /// Returns any `io::Error` that happens while reading.
+    pub fn from_wad_hexen<R: Read + Seek>(reader: &mut R) -> io::Result<Self> {
+        let start = reader.read_u16::<LE>()? as usize;
+        let end = reader.read_u16::<LE>()? as usize;
+        let flags = reader.read_i16::<LE>()? as i32;
+        let line_type = reader.read_u8()? as i32;
+        let mut args = [0u8; 5];
+        reader.read_exact(&mut args)?;
+        Ok(Self {
+            start,
+            end,
+            flags,
+            line_type,
+            tag: 0,
+            right: reader.read_i16::<LE>()? as i32,
+            left: reader.read_i16::<LE>()? as i32,
+            args,
+            udmf: UdmfFields::new(),
+        })
+    }
+
+    /// Writes this linedef in the 16-byte Hexen format (see [`LineDef::from_wad_hexen`]).
+    ///
+    /// # Errors
+    /// Returns any `io::Error` that happens while writing.
+    pub fn to_wad_hexen<W: Write + Seek>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_u16::<LE>(self.start as u16)?;
+        writer.write_u16::<LE>(self.end as u16)?;
+        writer.write_i16::<LE>(self.flags as i16)?;
+        writer.write_u8(self.line_type as u8)?;
+        writer.write_all(&self.args)?;
+        writer.write_i16::<LE>(self.right as i16)?;
+        writer.write_i16::<LE>(self.left as i16)?;
+        Ok(())
+    }
+
     /// Returns `true` if the "Two-Sided" bit is set in `flags`.
     ///
     /// In classic DOOM, that bit is typically `0x0004`.
@@ -4,9 +4,12 @@ pub mod linedef;
 pub mod sidedef;
 pub mod sector;
 pub mod thing;
+pub mod format;
+pub mod udmf;
 
 pub use vertex::Vertex;
 pub use linedef::LineDef;
 pub use sidedef::SideDef;
 pub use sector::Sector;
-pub use thing::Thing;
\ No newline at end of file
+pub use thing::Thing;
+pub use format::MapFormat;
@@ -3,6 +3,8 @@
 use std::io::{self, Read, Seek, Write};
 use byteorder::{LE, ReadBytesExt, WriteBytesExt};
 
+use super::udmf::UdmfFields;
+
 /// A sector in classic DOOM format (26 bytes).
 ///
 /// Layout (all little-endian):
@@ -18,7 +20,7 @@ use byteorder::{LE, ReadBytesExt, WriteBytesExt};
 /// 22-23   special_type   i16
 /// 24-25   tag            i16
 /// ```
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct Sector {
     /// The floor height (in map units).
     pub floor_height: i32,
@@ -41,6 +43,9 @@ pub struct Sector {
 
     /// Sector tag, used to link linedefs, etc.
     pub tag: i32,
+
+    /// Original UDMF fields (empty for binary maps).
+    pub udmf: UdmfFields,
 }
 
 impl Sector {
@@ -75,6 +80,7 @@ impl Sector {
             light,
             r#type,
             tag,
+            udmf: UdmfFields::new(),
         }
     }
 
@@ -109,6 +115,7 @@ impl Sector {
             light,
             r#type,
             tag,
+            udmf: UdmfFields::new(),
         })
     }
 
@@ -3,6 +3,8 @@
 use std::io::{self, Read, Seek, Write};
 use byteorder::{LE, ReadBytesExt, WriteBytesExt};
 
+use super::udmf::UdmfFields;
+
 /// A sidedef in classic DOOM format (30 bytes total).
 ///
 /// Layout (all little-endian):
@@ -17,7 +19,7 @@ use byteorder::{LE, ReadBytesExt, WriteBytesExt};
 /// 20-27   mid_tex     [u8; 8]
 /// 28-29   sector      i16  (index into sector list)
 /// ```
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct SideDef {
     /// Horizontal texture offset (signed 16-bit in a WAD).
     pub x_offset: i32,
@@ -36,6 +38,9 @@ pub struct SideDef {
 
     /// Sector index for this sidedef (in DOOM, stored as i16).
     pub sector: i32,
+
+    /// Original UDMF fields (empty for binary maps).
+    pub udmf: UdmfFields,
 }
 
 impl SideDef {
@@ -70,6 +75,7 @@ impl SideDef {
             lower_tex,
             mid_tex,
             sector,
+            udmf: UdmfFields::new(),
         }
     }
 
@@ -103,6 +109,7 @@ impl SideDef {
             lower_tex: lower_tex_raw,
             mid_tex:   mid_tex_raw,
             sector,
+            udmf: UdmfFields::new(),
         })
     }
 
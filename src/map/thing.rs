@@ -3,6 +3,8 @@
 use std::io::{self, Read, Seek, Write};
 use byteorder::{LE, ReadBytesExt, WriteBytesExt};
 
+use super::udmf::UdmfFields;
+
 /// A classic DOOM "thing" (map object) in the vanilla 10-byte format.
 ///
 /// Layout (all little-endian):
@@ -16,7 +18,10 @@ use byteorder::{LE, ReadBytesExt, WriteBytesExt};
 /// 6-7     doom_type   i16  (thing type number)
 /// 8-9     flags       i16  (bitmask)
 /// ```
-#[derive(Debug, Clone, PartialEq)]
+///
+/// Hexen-format things carry a few extra fields (`tid`, `z`, `special`, `args`);
+/// see [`Thing::from_wad_hexen`]. They stay zero for Doom-format maps.
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct Thing {
     /// X coordinate in map units (signed 16-bit in WAD).
     pub x: i32,
@@ -32,6 +37,22 @@ pub struct Thing {
 
     /// Flags bitmask (e.g., 0x0007 for easy/medium/hard).
     pub flags: i32,
+
+    /// Thing ID, referenced by scripts and specials (Hexen only).
+    pub tid: i32,
+
+    /// Height above the floor in map units (Hexen only).
+    pub z: i32,
+
+    /// Action special triggered when the thing dies or is picked up (Hexen only).
+    pub special: i32,
+
+    /// The five special arguments (Hexen only).
+    pub args: [u8; 5],
+
+    /// Fields read from a UDMF TEXTMAP, including keys the editor does not
+    /// understand, so they can be written back. Empty for binary maps.
+    pub udmf: UdmfFields,
 }
 
 impl Thing {
@@ -48,6 +69,7 @@ impl Thing {
             angle,
             doom_type,
             flags,
+            ..Default::default()
         }
     }
 
@@ -77,6 +99,7 @@ impl Thing {
             angle,
             doom_type,
             flags,
+            ..Default::default()
         })
     }
 
@@ -103,6 +126,66 @@ impl Thing {
         Ok(())
     }
 
+    /// Reads a `Thing` in the 20-byte Hexen format.
+    ///
+    /// # Format
+    /// ```text
+    /// 0-1   : tid (i16)
+    /// 2-3   : x (i16)
+    /// 4-5   : y (i16)
+    /// 6-7   : z (i16)
+    /// 8-9   : angle (i16)
+    /// 10-11 : doom_type (i16)
+    /// 12-13 : flags (i16)
+    /// 14    : special (u8)
+    /// 15-19 : args (5 x u8)
+    /// ```
+    ///
+    /// # Errors
+    /// Returns any I/O error encountered.
+    pub fn from_wad_hexen<R: Read + Seek>(reader: &mut R) -> io::Result<Self> {
+        let tid = reader.read_i16::<LE>()? as i32;
+        let x = reader.read_i16::<LE>()? as i32;
+        let y = reader.read_i16::<LE>()? as i32;
+        let z = reader.read_i16::<LE>()? as i32;
+        let angle = reader.read_i16::<LE>()? as i32;
+        let doom_type = reader.read_i16::<LE>()? as i32;
+        let flags = reader.read_i16::<LE>()? as i32;
+        let special = reader.read_u8()? as i32;
+        let mut args = [0u8; 5];
+        reader.read_exact(&mut args)?;
+
+        Ok(Thing {
+            x,
+            y,
+            angle,
+            doom_type,
+            flags,
+            tid,
+            z,
+            special,
+            args,
+            udmf: UdmfFields::new(),
+        })
+    }
+
+    /// Writes this thing in the 20-byte Hexen format (see [`Thing::from_wad_hexen`]).
+    ///
+    /// # Errors
+    /// Returns any I/O error encountered.
+    pub fn to_wad_hexen<W: Write + Seek>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_i16::<LE>(self.tid as i16)?;
+        writer.write_i16::<LE>(self.x as i16)?;
+        writer.write_i16::<LE>(self.y as i16)?;
+        writer.write_i16::<LE>(self.z as i16)?;
+        writer.write_i16::<LE>(self.angle as i16)?;
+        writer.write_i16::<LE>(self.doom_type as i16)?;
+        writer.write_i16::<LE>(self.flags as i16)?;
+        writer.write_u8(self.special as u8)?;
+        writer.write_all(&self.args)?;
+        Ok(())
+    }
+
     /// A quick helper to set default fields for a newly created Thing.
     /// 
     /// For instance, you could make Player 1 starts or a certain monster.
@@ -0,0 +1,949 @@
+// src/map/udmf.rs
+
+//! UDMF ("Universal Doom Map Format") support.
+//!
+//! A UDMF level stores its geometry as text in a TEXTMAP lump, between the
+//! level marker and ENDMAP. Each block is converted into the regular `map`
+//! structs; every field read from the file is also kept on the object (see
+//! [`UdmfFields`]), so keys the editor does not understand survive a
+//! load-save round trip.
+
+use std::fmt::{self, Write as _};
+use std::sync::Arc;
+use thiserror::Error;
+
+use super::{LineDef, Sector, SideDef, Thing, Vertex};
+
+/// A single UDMF value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UdmfValue {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+}
+
+impl UdmfValue {
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            UdmfValue::Int(i) => Some(*i),
+            UdmfValue::Float(f) => Some(f.round() as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            UdmfValue::Int(i) => Some(*i as f64),
+            UdmfValue::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            UdmfValue::Bool(b) => Some(*b),
+            UdmfValue::Int(i) => Some(*i != 0),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            UdmfValue::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for UdmfValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UdmfValue::Int(i) => write!(f, "{}", i),
+            UdmfValue::Float(v) => {
+                // Floats must keep a decimal point or they read back as integers.
+                let s = v.to_string();
+                if s.contains(['.', 'e', 'E']) || !v.is_finite() {
+                    f.write_str(&s)
+                } else {
+                    write!(f, "{}.0", s)
+                }
+            }
+            UdmfValue::Bool(b) => write!(f, "{}", b),
+            UdmfValue::Str(s) => {
+                f.write_char('"')?;
+                for c in s.chars() {
+                    if c == '"' || c == '\\' {
+                        f.write_char('\\')?;
+                    }
+                    f.write_char(c)?;
+                }
+                f.write_char('"')
+            }
+        }
+    }
+}
+
+/// The key/value pairs of one UDMF block, in file order.
+/// Keys are case-insensitive and stored lowercased.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UdmfFields {
+    entries: Vec<(String, UdmfValue)>,
+}
+
+impl UdmfFields {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&UdmfValue> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Sets `key`, replacing an existing value in place or appending a new one.
+    pub fn set(&mut self, key: &str, value: UdmfValue) {
+        match self.entries.iter_mut().find(|(k, _)| k.eq_ignore_ascii_case(key)) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((key.to_ascii_lowercase(), value)),
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<UdmfValue> {
+        let index = self.entries.iter().position(|(k, _)| k.eq_ignore_ascii_case(key))?;
+        Some(self.entries.remove(index).1)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &UdmfValue)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn int(&self, key: &str, default: i64) -> i64 {
+        self.get(key).and_then(UdmfValue::as_int).unwrap_or(default)
+    }
+
+    fn float(&self, key: &str, default: f64) -> f64 {
+        self.get(key).and_then(UdmfValue::as_float).unwrap_or(default)
+    }
+
+    fn flag(&self, key: &str) -> bool {
+        self.get(key).and_then(UdmfValue::as_bool).unwrap_or(false)
+    }
+
+    fn string(&self, key: &str, default: &str) -> String {
+        self.get(key).and_then(UdmfValue::as_str).unwrap_or(default).to_string()
+    }
+
+    fn set_int(&mut self, key: &str, value: i64) {
+        self.set(key, UdmfValue::Int(value));
+    }
+
+    fn set_float(&mut self, key: &str, value: f64) {
+        self.set(key, UdmfValue::Float(value));
+    }
+
+    fn set_flag(&mut self, key: &str) {
+        self.set(key, UdmfValue::Bool(true));
+    }
+
+    fn set_string(&mut self, key: &str, value: &str) {
+        self.set(key, UdmfValue::Str(value.to_string()));
+    }
+}
+
+/// Level-wide UDMF data that has no place in the map structs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UdmfLevel {
+    /// The declared namespace, e.g. "doom", "heretic", "hexen" or "zdoom".
+    pub namespace: String,
+    /// Top-level assignments other than `namespace`.
+    pub globals: UdmfFields,
+    /// Blocks other than thing/vertex/linedef/sidedef/sector, kept verbatim.
+    pub blocks: Vec<(String, UdmfFields)>,
+}
+
+impl UdmfLevel {
+    /// Hexen-style namespaces (hexen, zdoom and its relatives) describe
+    /// specials with five arguments and activation flags; the Doom-style
+    /// ones (doom, heretic, strife) keep the sector tag in `arg0`.
+    pub fn is_hexen_like(&self) -> bool {
+        !matches!(
+            self.namespace.to_ascii_lowercase().as_str(),
+            "" | "doom" | "heretic" | "strife"
+        )
+    }
+}
+
+/// The contents of a parsed TEXTMAP lump.
+#[derive(Debug, Clone, Default)]
+pub struct TextMap {
+    pub level: UdmfLevel,
+    pub things: Vec<Thing>,
+    pub vertices: Vec<Vertex>,
+    pub linedefs: Vec<LineDef>,
+    pub sidedefs: Vec<SideDef>,
+    pub sectors: Vec<Sector>,
+}
+
+#[derive(Debug, Error)]
+#[error("TEXTMAP line {line}: {message}")]
+pub struct UdmfError {
+    pub line: usize,
+    pub message: String,
+}
+
+// --- Parsing ---
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Value(UdmfValue),
+    Symbol(char),
+}
+
+struct Lexer<'a> {
+    src: &'a [u8],
+    pos: usize,
+    line: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(src: &'a [u8]) -> Self {
+        Self { src, pos: 0, line: 1 }
+    }
+
+    fn error(&self, message: impl Into<String>) -> UdmfError {
+        UdmfError { line: self.line, message: message.into() }
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<u8> {
+        self.src.get(self.pos + offset).copied()
+    }
+
+    fn bump(&mut self) -> Option<u8> {
+        let c = self.peek_at(0)?;
+        self.pos += 1;
+        if c == b'\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn skip_whitespace_and_comments(&mut self) -> Result<(), UdmfError> {
+        loop {
+            match (self.peek_at(0), self.peek_at(1)) {
+                (Some(c), _) if c.is_ascii_whitespace() => {
+                    self.bump();
+                }
+                (Some(b'/'), Some(b'/')) => {
+                    while !matches!(self.peek_at(0), None | Some(b'\n')) {
+                        self.bump();
+                    }
+                }
+                (Some(b'/'), Some(b'*')) => {
+                    self.pos += 2;
+                    loop {
+                        match (self.peek_at(0), self.peek_at(1)) {
+                            (Some(b'*'), Some(b'/')) => {
+                                self.pos += 2;
+                                break;
+                            }
+                            (Some(_), _) => {
+                                self.bump();
+                            }
+                            (None, _) => return Err(self.error("unterminated block comment")),
+                        }
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn next(&mut self) -> Result<Option<Token>, UdmfError> {
+        self.skip_whitespace_and_comments()?;
+        let c = match self.peek_at(0) {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        let token = match c {
+            b'{' | b'}' | b'=' | b';' => {
+                self.bump();
+                Token::Symbol(c as char)
+            }
+            b'"' => Token::Value(UdmfValue::Str(self.string()?)),
+            b'0'..=b'9' | b'+' | b'-' | b'.' => Token::Value(self.number()?),
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                let start = self.pos;
+                while matches!(self.peek_at(0), Some(c) if c.is_ascii_alphanumeric() || c == b'_') {
+                    self.bump();
+                }
+                let ident = String::from_utf8_lossy(&self.src[start..self.pos]).to_ascii_lowercase();
+                match ident.as_str() {
+                    "true" => Token::Value(UdmfValue::Bool(true)),
+                    "false" => Token::Value(UdmfValue::Bool(false)),
+                    _ => Token::Ident(ident),
+                }
+            }
+            other => return Err(self.error(format!("unexpected character '{}'", other as char))),
+        };
+        Ok(Some(token))
+    }
+
+    fn expect_next(&mut self) -> Result<Token, UdmfError> {
+        self.next()?.ok_or_else(|| self.error("unexpected end of TEXTMAP"))
+    }
+
+    fn expect_symbol(&mut self, symbol: char) -> Result<(), UdmfError> {
+        match self.expect_next()? {
+            Token::Symbol(c) if c == symbol => Ok(()),
+            other => Err(self.error(format!("expected '{}', found {:?}", symbol, other))),
+        }
+    }
+
+    fn value(&mut self) -> Result<UdmfValue, UdmfError> {
+        match self.expect_next()? {
+            Token::Value(value) => Ok(value),
+            other => Err(self.error(format!("expected a value, found {:?}", other))),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, UdmfError> {
+        self.bump(); // opening quote
+        let mut bytes = Vec::new();
+        loop {
+            match self.bump() {
+                Some(b'"') => break,
+                Some(b'\\') => match self.bump() {
+                    Some(c) => bytes.push(c),
+                    None => return Err(self.error("unterminated string")),
+                },
+                Some(c) => bytes.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    fn number(&mut self) -> Result<UdmfValue, UdmfError> {
+        let start = self.pos;
+        if matches!(self.peek_at(0), Some(b'+') | Some(b'-')) {
+            self.bump();
+        }
+        let digits_start = self.pos;
+
+        if self.peek_at(0) == Some(b'0') && matches!(self.peek_at(1), Some(b'x') | Some(b'X')) {
+            self.pos += 2;
+            while matches!(self.peek_at(0), Some(c) if c.is_ascii_hexdigit()) {
+                self.bump();
+            }
+            let hex = std::str::from_utf8(&self.src[digits_start + 2..self.pos]).unwrap_or("");
+            let value = i64::from_str_radix(hex, 16)
+                .map_err(|_| self.error("invalid hexadecimal number"))?;
+            let negative = self.src[start] == b'-';
+            return Ok(UdmfValue::Int(if negative { -value } else { value }));
+        }
+
+        let mut is_float = false;
+        while let Some(c) = self.peek_at(0) {
+            match c {
+                b'0'..=b'9' => {}
+                b'.' => is_float = true,
+                b'e' | b'E' => {
+                    is_float = true;
+                    if matches!(self.peek_at(1), Some(b'+') | Some(b'-')) {
+                        self.bump();
+                    }
+                }
+                _ => break,
+            }
+            self.bump();
+        }
+
+        let text = std::str::from_utf8(&self.src[start..self.pos]).unwrap_or("");
+        if is_float {
+            return text
+                .parse::<f64>()
+                .map(UdmfValue::Float)
+                .map_err(|_| self.error(format!("invalid number '{}'", text)));
+        }
+
+        let digits = &self.src[digits_start..self.pos];
+        let value = if digits.len() > 1 && digits[0] == b'0' {
+            let octal = std::str::from_utf8(digits).unwrap_or("");
+            i64::from_str_radix(octal, 8).ok().map(|v| if self.src[start] == b'-' { -v } else { v })
+        } else {
+            text.trim_start_matches('+').parse::<i64>().ok()
+        };
+        value
+            .map(UdmfValue::Int)
+            .ok_or_else(|| self.error(format!("invalid number '{}'", text)))
+    }
+}
+
+/// Parses a TEXTMAP lump.
+pub fn parse_textmap(data: &[u8]) -> Result<TextMap, UdmfError> {
+    let mut lexer = Lexer::new(data);
+    let mut level = UdmfLevel::default();
+    let mut blocks = Vec::new();
+
+    while let Some(token) = lexer.next()? {
+        let name = match token {
+            Token::Ident(name) => name,
+            other => return Err(lexer.error(format!("expected an identifier, found {:?}", other))),
+        };
+        match lexer.expect_next()? {
+            Token::Symbol('=') => {
+                let value = lexer.value()?;
+                lexer.expect_symbol(';')?;
+                if name == "namespace" {
+                    level.namespace = value.as_str().unwrap_or_default().to_string();
+                } else {
+                    level.globals.set(&name, value);
+                }
+            }
+            Token::Symbol('{') => blocks.push((name, parse_block(&mut lexer)?)),
+            other => return Err(lexer.error(format!("expected '=' or '{{', found {:?}", other))),
+        }
+    }
+
+    // The namespace decides how fields map, so convert once everything is read.
+    let hexen = level.is_hexen_like();
+    let mut map = TextMap::default();
+    for (kind, fields) in blocks {
+        match kind.as_str() {
+            "thing" => map.things.push(Thing { udmf: fields.clone(), ..decode_thing(&fields, hexen) }),
+            "vertex" => map.vertices.push(Vertex { udmf: fields.clone(), ..decode_vertex(&fields) }),
+            "linedef" => map.linedefs.push(LineDef { udmf: fields.clone(), ..decode_linedef(&fields, hexen) }),
+            "sidedef" => map.sidedefs.push(SideDef { udmf: fields.clone(), ..decode_sidedef(&fields) }),
+            "sector" => map.sectors.push(Sector { udmf: fields.clone(), ..decode_sector(&fields) }),
+            _ => level.blocks.push((kind, fields)),
+        }
+    }
+    map.level = level;
+    Ok(map)
+}
+
+fn parse_block(lexer: &mut Lexer) -> Result<UdmfFields, UdmfError> {
+    let mut fields = UdmfFields::new();
+    loop {
+        match lexer.expect_next()? {
+            Token::Symbol('}') => return Ok(fields),
+            Token::Ident(key) => {
+                lexer.expect_symbol('=')?;
+                let value = lexer.value()?;
+                lexer.expect_symbol(';')?;
+                fields.set(&key, value);
+            }
+            other => return Err(lexer.error(format!("expected a field or '}}', found {:?}", other))),
+        }
+    }
+}
+
+// --- Writing ---
+
+/// Writes a complete TEXTMAP lump.
+///
+/// Objects are written from their struct fields. Fields whose value did not
+/// change since loading keep their original spelling, and unknown keys are
+/// written back untouched.
+pub fn write_textmap(
+    level: &UdmfLevel,
+    things: &[Arc<Thing>],
+    vertices: &[Arc<Vertex>],
+    linedefs: &[Arc<LineDef>],
+    sidedefs: &[Arc<SideDef>],
+    sectors: &[Arc<Sector>],
+) -> String {
+    let hexen = level.is_hexen_like();
+    let namespace = if level.namespace.is_empty() { "doom" } else { &level.namespace };
+
+    let mut out = String::new();
+    let _ = writeln!(out, "namespace = {};", UdmfValue::Str(namespace.to_string()));
+    for (key, value) in level.globals.iter() {
+        let _ = writeln!(out, "{} = {};", key, value);
+    }
+    out.push('\n');
+
+    for (i, thing) in things.iter().enumerate() {
+        let fields = merge_fields(
+            &thing.udmf,
+            &encode_thing(&decode_thing(&thing.udmf, hexen), hexen),
+            &encode_thing(thing, hexen),
+            &["x", "y", "type"],
+        );
+        write_block(&mut out, "thing", i, &fields);
+    }
+    for (i, vertex) in vertices.iter().enumerate() {
+        let fields = merge_fields(
+            &vertex.udmf,
+            &encode_vertex(&decode_vertex(&vertex.udmf)),
+            &encode_vertex(vertex),
+            &["x", "y"],
+        );
+        write_block(&mut out, "vertex", i, &fields);
+    }
+    for (i, linedef) in linedefs.iter().enumerate() {
+        let fields = merge_fields(
+            &linedef.udmf,
+            &encode_linedef(&decode_linedef(&linedef.udmf, hexen), hexen),
+            &encode_linedef(linedef, hexen),
+            &["v1", "v2", "sidefront"],
+        );
+        write_block(&mut out, "linedef", i, &fields);
+    }
+    for (i, sidedef) in sidedefs.iter().enumerate() {
+        let fields = merge_fields(
+            &sidedef.udmf,
+            &encode_sidedef(&decode_sidedef(&sidedef.udmf)),
+            &encode_sidedef(sidedef),
+            &["sector"],
+        );
+        write_block(&mut out, "sidedef", i, &fields);
+    }
+    for (i, sector) in sectors.iter().enumerate() {
+        let fields = merge_fields(
+            &sector.udmf,
+            &encode_sector(&decode_sector(&sector.udmf)),
+            &encode_sector(sector),
+            &["texturefloor", "textureceiling"],
+        );
+        write_block(&mut out, "sector", i, &fields);
+    }
+    for (i, (kind, fields)) in level.blocks.iter().enumerate() {
+        write_block(&mut out, kind, i, fields);
+    }
+    out
+}
+
+fn write_block(out: &mut String, kind: &str, index: usize, fields: &UdmfFields) {
+    let _ = writeln!(out, "{} // {}", kind, index);
+    out.push_str("{\n");
+    for (key, value) in fields.iter() {
+        let _ = writeln!(out, "{} = {};", key, value);
+    }
+    out.push_str("}\n\n");
+}
+
+/// Combines the fields read from the file (`original`) with the object's
+/// current state. `before` is what the original fields encode to and `after`
+/// what the edited object encodes to; only keys whose encoding changed are
+/// rewritten, so untouched values keep their exact original form.
+fn merge_fields(original: &UdmfFields, before: &UdmfFields, after: &UdmfFields, required: &[&str]) -> UdmfFields {
+    let mut out = original.clone();
+    for (key, _) in before.iter().chain(after.iter()) {
+        if before.get(key) != after.get(key) {
+            match after.get(key) {
+                Some(value) => out.set(key, value.clone()),
+                None => {
+                    out.remove(key);
+                }
+            }
+        }
+    }
+    for key in required {
+        if !out.contains_key(key) {
+            if let Some(value) = after.get(key) {
+                out.set(key, value.clone());
+            }
+        }
+    }
+    out
+}
+
+// --- Namespace mapping ---
+
+const SPAC_KEYS: [&str; 6] = ["playercross", "playeruse", "monstercross", "impact", "playerpush", "missilecross"];
+
+const LINE_FLAGS: [(&str, i32); 9] = [
+    ("blocking", 0x0001),
+    ("blockmonsters", 0x0002),
+    ("twosided", 0x0004),
+    ("dontpegtop", 0x0008),
+    ("dontpegbottom", 0x0010),
+    ("secret", 0x0020),
+    ("blocksound", 0x0040),
+    ("dontdraw", 0x0080),
+    ("mapped", 0x0100),
+];
+
+const DOOM_LINE_FLAGS: [(&str, i32); 1] = [("passuse", 0x0200)];
+
+const HEXEN_LINE_FLAGS: [(&str, i32); 4] = [
+    ("repeatspecial", 0x0200),
+    ("monsteractivate", 0x2000),
+    ("blockplayers", 0x4000),
+    ("blockeverything", 0x8000),
+];
+
+/// Doom-format thing flags that are set when the UDMF key is *false*.
+const DOOM_THING_EXCLUDE_FLAGS: [(&str, i32); 3] = [("single", 0x0010), ("dm", 0x0020), ("coop", 0x0040)];
+
+const DOOM_THING_FLAGS: [(&str, i32); 1] = [("friend", 0x0080)];
+
+const HEXEN_THING_FLAGS: [(&str, i32); 11] = [
+    ("dormant", 0x0010),
+    ("class1", 0x0020),
+    ("class2", 0x0040),
+    ("class3", 0x0080),
+    ("single", 0x0100),
+    ("coop", 0x0200),
+    ("dm", 0x0400),
+    ("translucent", 0x0800),
+    ("invisible", 0x1000),
+    ("friend", 0x2000),
+    ("standing", 0x4000),
+];
+
+fn decode_bits(fields: &UdmfFields, table: &[(&str, i32)]) -> i32 {
+    table.iter().filter(|(key, _)| fields.flag(key)).fold(0, |acc, (_, bit)| acc | bit)
+}
+
+fn encode_bits(fields: &mut UdmfFields, flags: i32, table: &[(&str, i32)]) {
+    for (key, bit) in table {
+        if flags & bit != 0 {
+            fields.set_flag(key);
+        }
+    }
+}
+
+fn decode_thing(f: &UdmfFields, hexen: bool) -> Thing {
+    let mut flags = 0;
+    if f.flag("skill1") || f.flag("skill2") {
+        flags |= 0x0001;
+    }
+    if f.flag("skill3") {
+        flags |= 0x0002;
+    }
+    if f.flag("skill4") || f.flag("skill5") {
+        flags |= 0x0004;
+    }
+    if f.flag("ambush") {
+        flags |= 0x0008;
+    }
+    if hexen {
+        flags |= decode_bits(f, &HEXEN_THING_FLAGS);
+    } else {
+        flags |= decode_bits(f, &DOOM_THING_FLAGS);
+        for (key, bit) in DOOM_THING_EXCLUDE_FLAGS {
+            if !f.flag(key) {
+                flags |= bit;
+            }
+        }
+    }
+
+    let mut args = [0u8; 5];
+    for (i, arg) in args.iter_mut().enumerate() {
+        *arg = f.int(&format!("arg{}", i), 0) as u8;
+    }
+
+    Thing {
+        x: f.float("x", 0.0).round() as i32,
+        y: f.float("y", 0.0).round() as i32,
+        angle: f.int("angle", 0) as i32,
+        doom_type: f.int("type", 0) as i32,
+        flags,
+        tid: f.int("id", 0) as i32,
+        z: f.float("height", 0.0).round() as i32,
+        special: f.int("special", 0) as i32,
+        args,
+        udmf: UdmfFields::new(),
+    }
+}
+
+fn encode_thing(thing: &Thing, hexen: bool) -> UdmfFields {
+    let mut f = UdmfFields::new();
+    if thing.tid != 0 {
+        f.set_int("id", thing.tid as i64);
+    }
+    f.set_float("x", thing.x as f64);
+    f.set_float("y", thing.y as f64);
+    if thing.z != 0 {
+        f.set_float("height", thing.z as f64);
+    }
+    if thing.angle != 0 {
+        f.set_int("angle", thing.angle as i64);
+    }
+    f.set_int("type", thing.doom_type as i64);
+    if thing.special != 0 {
+        f.set_int("special", thing.special as i64);
+    }
+    for (i, &arg) in thing.args.iter().enumerate() {
+        if arg != 0 {
+            f.set_int(&format!("arg{}", i), arg as i64);
+        }
+    }
+
+    let flags = thing.flags;
+    if flags & 0x0001 != 0 {
+        f.set_flag("skill1");
+        f.set_flag("skill2");
+    }
+    if flags & 0x0002 != 0 {
+        f.set_flag("skill3");
+    }
+    if flags & 0x0004 != 0 {
+        f.set_flag("skill4");
+        f.set_flag("skill5");
+    }
+    if flags & 0x0008 != 0 {
+        f.set_flag("ambush");
+    }
+    if hexen {
+        encode_bits(&mut f, flags, &HEXEN_THING_FLAGS);
+    } else {
+        for (key, bit) in DOOM_THING_EXCLUDE_FLAGS {
+            if flags & bit == 0 {
+                f.set_flag(key);
+            }
+        }
+        encode_bits(&mut f, flags, &DOOM_THING_FLAGS);
+    }
+    f
+}
+
+fn decode_vertex(f: &UdmfFields) -> Vertex {
+    Vertex {
+        x: f.float("x", 0.0).round() as i32,
+        y: f.float("y", 0.0).round() as i32,
+        udmf: UdmfFields::new(),
+    }
+}
+
+fn encode_vertex(vertex: &Vertex) -> UdmfFields {
+    let mut f = UdmfFields::new();
+    f.set_float("x", vertex.x as f64);
+    f.set_float("y", vertex.y as f64);
+    f
+}
+
+fn decode_linedef(f: &UdmfFields, hexen: bool) -> LineDef {
+    let mut flags = decode_bits(f, &LINE_FLAGS);
+    let mut args = [0u8; 5];
+    for (i, arg) in args.iter_mut().enumerate() {
+        *arg = f.int(&format!("arg{}", i), 0) as u8;
+    }
+
+    let tag = if hexen {
+        flags |= decode_bits(f, &HEXEN_LINE_FLAGS);
+        // Prefer the specific activations; plain crossing is the default.
+        let spac = (1..SPAC_KEYS.len()).find(|&i| f.flag(SPAC_KEYS[i])).unwrap_or(0);
+        flags |= (spac as i32) << 10;
+        f.int("id", -1).max(0) as i32
+    } else {
+        flags |= decode_bits(f, &DOOM_LINE_FLAGS);
+        f.int("arg0", 0) as i32
+    };
+
+    LineDef {
+        start: f.int("v1", 0).max(0) as usize,
+        end: f.int("v2", 0).max(0) as usize,
+        flags,
+        line_type: f.int("special", 0) as i32,
+        tag,
+        right: f.int("sidefront", -1) as i32,
+        left: f.int("sideback", -1) as i32,
+        args,
+        udmf: UdmfFields::new(),
+    }
+}
+
+fn encode_linedef(linedef: &LineDef, hexen: bool) -> UdmfFields {
+    let mut f = UdmfFields::new();
+    f.set_int("v1", linedef.start as i64);
+    f.set_int("v2", linedef.end as i64);
+    f.set_int("sidefront", linedef.right as i64);
+    if linedef.left != -1 {
+        f.set_int("sideback", linedef.left as i64);
+    }
+    if linedef.line_type != 0 {
+        f.set_int("special", linedef.line_type as i64);
+    }
+
+    encode_bits(&mut f, linedef.flags, &LINE_FLAGS);
+    if hexen {
+        if linedef.tag != 0 {
+            f.set_int("id", linedef.tag as i64);
+        }
+        for (i, &arg) in linedef.args.iter().enumerate() {
+            if arg != 0 {
+                f.set_int(&format!("arg{}", i), arg as i64);
+            }
+        }
+        encode_bits(&mut f, linedef.flags, &HEXEN_LINE_FLAGS);
+        let spac = ((linedef.flags >> 10) & 7) as usize;
+        if (linedef.line_type != 0 || spac != 0) && spac < SPAC_KEYS.len() {
+            f.set_flag(SPAC_KEYS[spac]);
+        }
+    } else {
+        if linedef.tag != 0 {
+            f.set_int("arg0", linedef.tag as i64);
+        }
+        for (i, &arg) in linedef.args.iter().enumerate().skip(1) {
+            if arg != 0 {
+                f.set_int(&format!("arg{}", i), arg as i64);
+            }
+        }
+        encode_bits(&mut f, linedef.flags, &DOOM_LINE_FLAGS);
+    }
+    f
+}
+
+fn decode_sidedef(f: &UdmfFields) -> SideDef {
+    SideDef {
+        x_offset: f.int("offsetx", 0) as i32,
+        y_offset: f.int("offsety", 0) as i32,
+        upper_tex: f.string("texturetop", "-"),
+        lower_tex: f.string("texturebottom", "-"),
+        mid_tex: f.string("texturemiddle", "-"),
+        sector: f.int("sector", 0) as i32,
+        udmf: UdmfFields::new(),
+    }
+}
+
+fn encode_sidedef(sidedef: &SideDef) -> UdmfFields {
+    let mut f = UdmfFields::new();
+    f.set_int("sector", sidedef.sector as i64);
+    if sidedef.x_offset != 0 {
+        f.set_int("offsetx", sidedef.x_offset as i64);
+    }
+    if sidedef.y_offset != 0 {
+        f.set_int("offsety", sidedef.y_offset as i64);
+    }
+    for (key, tex) in [
+        ("texturetop", &sidedef.upper_tex),
+        ("texturebottom", &sidedef.lower_tex),
+        ("texturemiddle", &sidedef.mid_tex),
+    ] {
+        if tex.as_str() != "-" && !tex.is_empty() {
+            f.set_string(key, tex);
+        }
+    }
+    f
+}
+
+fn decode_sector(f: &UdmfFields) -> Sector {
+    Sector {
+        floor_height: f.int("heightfloor", 0) as i32,
+        ceiling_height: f.int("heightceiling", 0) as i32,
+        floor_tex: f.string("texturefloor", ""),
+        ceiling_tex: f.string("textureceiling", ""),
+        light: f.int("lightlevel", 160) as i32,
+        r#type: f.int("special", 0) as i32,
+        tag: f.int("id", 0) as i32,
+        udmf: UdmfFields::new(),
+    }
+}
+
+fn encode_sector(sector: &Sector) -> UdmfFields {
+    let mut f = UdmfFields::new();
+    if sector.floor_height != 0 {
+        f.set_int("heightfloor", sector.floor_height as i64);
+    }
+    if sector.ceiling_height != 0 {
+        f.set_int("heightceiling", sector.ceiling_height as i64);
+    }
+    f.set_string("texturefloor", &sector.floor_tex);
+    f.set_string("textureceiling", &sector.ceiling_tex);
+    if sector.light != 160 {
+        f.set_int("lightlevel", sector.light as i64);
+    }
+    if sector.r#type != 0 {
+        f.set_int("special", sector.r#type as i64);
+    }
+    if sector.tag != 0 {
+        f.set_int("id", sector.tag as i64);
+    }
+    f
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"
+// Written by some editor
+namespace = "zdoom";
+comment = "keep me";
+
+thing { x = 32.5; y = -64.0; type = 1; angle = 90; skill1 = true; single = true; user_score = 10; }
+vertex { x = 0.0; y = 0.0; }
+vertex { x = 64.0; y = 0.0; }
+/* a block
+   comment */
+linedef { v1 = 0; v2 = 1; sidefront = 0; blocking = true; special = 80; arg0 = 0x10; playeruse = true; alpha = 0.5; }
+sidedef { sector = 0; texturemiddle = "STARTAN2"; offsetx_mid = 4.0; }
+sector { texturefloor = "FLOOR0_1"; textureceiling = "CEIL1_1"; heightceiling = 128; lightlevel = 192; }
+somethingelse { foo = "bar\"baz"; }
+"#;
+
+    #[test]
+    fn test_parse_textmap() {
+        let map = parse_textmap(SAMPLE.as_bytes()).unwrap();
+        assert_eq!(map.level.namespace, "zdoom");
+        assert_eq!(map.level.globals.get("comment"), Some(&UdmfValue::Str("keep me".into())));
+        assert_eq!(map.level.blocks.len(), 1);
+
+        let thing = &map.things[0];
+        assert_eq!((thing.x, thing.y, thing.angle, thing.doom_type), (33, -64, 90, 1));
+        assert_eq!(thing.flags & 0x0001, 0x0001);
+        assert_eq!(thing.udmf.get("user_score"), Some(&UdmfValue::Int(10)));
+
+        let linedef = &map.linedefs[0];
+        assert_eq!((linedef.start, linedef.end, linedef.right, linedef.left), (0, 1, 0, -1));
+        assert_eq!(linedef.line_type, 80);
+        assert_eq!(linedef.args[0], 16);
+        assert_eq!(linedef.flags, 0x0001 | (1 << 10));
+        assert_eq!(map.sidedefs[0].mid_tex, "STARTAN2");
+        assert_eq!(map.sectors[0].ceiling_height, 128);
+        assert_eq!(map.sectors[0].light, 192);
+    }
+
+    #[test]
+    fn test_round_trip_keeps_unknown_keys() {
+        let map = parse_textmap(SAMPLE.as_bytes()).unwrap();
+        let arcs = |v: &[Thing]| v.iter().cloned().map(Arc::new).collect::<Vec<_>>();
+        let mut things = arcs(&map.things);
+        let text = write_textmap(
+            &map.level,
+            &things,
+            &map.vertices.iter().cloned().map(Arc::new).collect::<Vec<_>>(),
+            &map.linedefs.iter().cloned().map(Arc::new).collect::<Vec<_>>(),
+            &map.sidedefs.iter().cloned().map(Arc::new).collect::<Vec<_>>(),
+            &map.sectors.iter().cloned().map(Arc::new).collect::<Vec<_>>(),
+        );
+        let again = parse_textmap(text.as_bytes()).unwrap();
+        assert_eq!(again.level, map.level);
+        assert_eq!(again.things, map.things);
+        assert_eq!(again.vertices, map.vertices);
+        assert_eq!(again.linedefs, map.linedefs);
+        assert_eq!(again.sidedefs, map.sidedefs);
+        assert_eq!(again.sectors, map.sectors);
+
+        // Editing a thing rewrites only what changed.
+        let mut moved = (*things[0]).clone();
+        moved.x = 128;
+        things[0] = Arc::new(moved);
+        let text = write_textmap(&map.level, &things, &[], &[], &[], &[]);
+        let again = parse_textmap(text.as_bytes()).unwrap();
+        let thing = &again.things[0];
+        assert_eq!(thing.x, 128);
+        assert_eq!(thing.udmf.get("y"), Some(&UdmfValue::Float(-64.0)));
+        assert_eq!(thing.udmf.get("user_score"), Some(&UdmfValue::Int(10)));
+        assert!(!thing.udmf.contains_key("skill2"));
+    }
+}
@@ -3,6 +3,8 @@
 use std::io::{self, Read, Seek, Write};
 use byteorder::{LE, ReadBytesExt, WriteBytesExt};
 
+use super::udmf::UdmfFields;
+
 /// A vertex in classic DOOM format: 4 bytes total.
 ///
 /// In a WAD's `VERTEXES` lump, each vertex is:
@@ -15,13 +17,16 @@ use byteorder::{LE, ReadBytesExt, WriteBytesExt};
 /// ```
 /// 
 /// Each coordinate is in map units.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct Vertex {
     /// X coordinate in the map (signed 16-bit in the WAD).
     pub x: i32,
 
     /// Y coordinate in the map (signed 16-bit in the WAD).
     pub y: i32,
+
+    /// Original UDMF fields (empty for binary maps).
+    pub udmf: UdmfFields,
 }
 
 impl Vertex {
@@ -32,7 +37,7 @@ impl Vertex {
     /// let v = Vertex::new(128, -64);
     /// ```
     pub fn new(x: i32, y: i32) -> Self {
-        Self { x, y }
+        Self { x, y, udmf: UdmfFields::new() }
     }
 
     /// Reads a single vertex from a DOOM WAD vertex lump (4 bytes):
@@ -47,7 +52,7 @@ impl Vertex {
     pub fn from_wad<R: Read + Seek>(reader: &mut R) -> io::Result<Self> {
         let x = reader.read_i16::<LE>()? as i32;
         let y = reader.read_i16::<LE>()? as i32;
-        Ok(Vertex { x, y })
+        Ok(Vertex::new(x, y))
     }
 
     /// Writes this vertex to a DOOM WAD vertex lump (4 bytes).
@@ -0,0 +1,88 @@
+// src/ui/diff_window.rs
+
+use std::sync::Arc;
+use eframe::egui::{self, Color32, Context, ScrollArea, Ui};
+use parking_lot::RwLock;
+
+use crate::document::diff::{LumpChange, ObjectDiff};
+use crate::editor::core::Editor;
+
+/// How many object numbers to list per kind before cutting the list short.
+const MAX_LISTED: usize = 64;
+
+/// A window showing the result of File > Compare With WAD: the lumps that
+/// were added, removed, changed or moved, and what changed inside levels.
+pub struct DiffWindow {
+    editor: Arc<RwLock<Editor>>,
+}
+
+impl DiffWindow {
+    pub fn new(editor: Arc<RwLock<Editor>>) -> Self {
+        Self { editor }
+    }
+
+    /// Called every frame. Draws the window while the editor holds a diff.
+    pub fn update(&mut self, ctx: &Context) {
+        let (path, diff) = match self.editor.read().wad_diff.clone() {
+            Some(diff) => diff,
+            None => return,
+        };
+
+        let mut open = true;
+        egui::Window::new("WAD Comparison")
+            .open(&mut open)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                ui.label(format!("Compared with {}", path.display()));
+                ui.separator();
+                if diff.is_empty() {
+                    ui.label(format!("The WADs are identical ({} lumps).", diff.unchanged));
+                    return;
+                }
+                ui.label(format!("{} lump change(s), {} lump(s) unchanged.", diff.lumps.len(), diff.unchanged));
+                ScrollArea::vertical().max_height(420.0).show(ui, |ui| {
+                    for change in &diff.lumps {
+                        let color = match change {
+                            LumpChange::Added { .. } => Color32::LIGHT_GREEN,
+                            LumpChange::Removed { .. } => Color32::LIGHT_RED,
+                            LumpChange::Changed { .. } => Color32::YELLOW,
+                            LumpChange::Moved { .. } => Color32::LIGHT_BLUE,
+                        };
+                        ui.colored_label(color, change.to_string());
+                    }
+                    for level in &diff.levels {
+                        ui.separator();
+                        ui.strong(&level.name);
+                        object_section(ui, &level.name, "Things", &level.things);
+                        object_section(ui, &level.name, "Linedefs", &level.linedefs);
+                        object_section(ui, &level.name, "Sectors", &level.sectors);
+                    }
+                });
+            });
+        if !open {
+            self.editor.write().wad_diff = None;
+        }
+    }
+}
+
+/// One collapsible line per object kind, listing the object numbers.
+fn object_section(ui: &mut Ui, level: &str, kind: &str, diff: &ObjectDiff) {
+    if diff.is_empty() {
+        ui.label(format!("{}: unchanged", kind));
+        return;
+    }
+    egui::CollapsingHeader::new(format!("{}: {}", kind, diff))
+        .id_source((level, kind))
+        .show(ui, |ui| {
+            for (label, indices) in [("Added", &diff.added), ("Removed", &diff.removed), ("Changed", &diff.changed)] {
+                if indices.is_empty() {
+                    continue;
+                }
+                let mut list: Vec<String> = indices.iter().take(MAX_LISTED).map(|i| i.to_string()).collect();
+                if indices.len() > MAX_LISTED {
+                    list.push(format!("... {} more", indices.len() - MAX_LISTED));
+                }
+                ui.label(format!("{}: {}", label, list.join(", ")));
+            }
+        });
+}
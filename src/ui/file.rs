@@ -7,6 +7,7 @@ use std::path::PathBuf;
 use log::{error, info};
 use rfd::FileDialog;
 
+use crate::document::archive::Pk3Archive;
 use crate::document::Document;
 use crate::ui::dialog::{DialogResult, DialogManager};
 
@@ -56,6 +57,7 @@ pub fn open_map(
     // No unsaved changes or user resolved the dialog—proceed with file selection.
     let file_path: Option<PathBuf> = FileDialog::new()
         .add_filter("WAD Files", &["wad"])
+        .add_filter("PK3/ZIP Archives", &["pk3", "zip"])
         .add_filter("Map Files", &["map"])
         .pick_file();
 
@@ -81,6 +83,7 @@ pub fn open_map(
         // Dispatch to the appropriate parser.
         match ext.as_str() {
             "wad" => parse_wad(&buffer),
+            "pk3" | "zip" => parse_pk3(&buffer),
             "map" => parse_map(&buffer),
             other => {
                 error!("Unsupported file format: {}", other);
@@ -107,6 +110,26 @@ fn parse_wad(buffer: &[u8]) -> Option<Document> {
     }
 }
 
+/// Loads the first map WAD under `maps/` of a PK3 archive.
+fn parse_pk3(buffer: &[u8]) -> Option<Document> {
+    info!("Parsing PK3 archive ({} bytes)", buffer.len());
+    let archive = match Pk3Archive::from_bytes(buffer) {
+        Ok(archive) => archive,
+        Err(e) => {
+            error!("Error reading PK3 archive: {}", e);
+            return None;
+        }
+    };
+    let entry = match archive.map_wads().into_iter().next() {
+        Some(entry) => entry,
+        None => {
+            error!("PK3 archive has no maps/*.wad entries");
+            return None;
+        }
+    };
+    parse_wad(archive.get(&entry)?)
+}
+
 /// Parses a custom MAP file buffer into a Document.
 /// Currently a stub; replace with your custom map parsing logic.
 fn parse_map(buffer: &[u8]) -> Option<Document> {
@@ -0,0 +1,69 @@
+// src/ui/load_report.rs
+
+use std::sync::Arc;
+use eframe::egui::{self, Color32, Context, ScrollArea};
+use parking_lot::RwLock;
+
+use crate::document::diagnostics::LoadMode;
+use crate::editor::core::Editor;
+
+/// A window listing what the loader had to work around in the current WAD:
+/// damaged directory entries first, then problems in the loaded level.
+pub struct LoadReportWindow {
+    editor: Arc<RwLock<Editor>>,
+}
+
+impl LoadReportWindow {
+    pub fn new(editor: Arc<RwLock<Editor>>) -> Self {
+        Self { editor }
+    }
+
+    /// Called every frame. Draws the window if the editor has it enabled.
+    pub fn update(&mut self, ctx: &Context) {
+        let (mut open, doc_arc) = {
+            let editor = self.editor.read();
+            (editor.show_load_report, editor.document())
+        };
+        if !open {
+            return;
+        }
+        let report = doc_arc
+            .map(|doc| doc.read().load_report.read().clone())
+            .unwrap_or_default();
+
+        let mut strict = false;
+        egui::Window::new("Load Report")
+            .open(&mut open)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                strict = self.editor.read().load_mode == LoadMode::Strict;
+                if ui.checkbox(&mut strict, "Strict loading (reject damaged WADs)").changed() {
+                    self.editor.write().load_mode =
+                        if strict { LoadMode::Strict } else { LoadMode::Lenient };
+                }
+                ui.separator();
+
+                if report.is_empty() {
+                    ui.label("No problems found.");
+                    return;
+                }
+                ui.label(format!("{} problem(s) worked around:", report.len()));
+                ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+                    let (directory, level): (Vec<_>, Vec<_>) = report
+                        .warnings
+                        .iter()
+                        .partition(|w| w.is_directory_warning());
+                    for (heading, warnings) in [("Directory", directory), ("Level", level)] {
+                        if warnings.is_empty() {
+                            continue;
+                        }
+                        ui.strong(heading);
+                        for warning in warnings {
+                            ui.colored_label(Color32::YELLOW, warning.to_string());
+                        }
+                    }
+                });
+            });
+        self.editor.write().show_load_report = open;
+    }
+}
@@ -0,0 +1,287 @@
+// src/ui/lump_panel.rs
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use eframe::egui::{self, Context, ScrollArea, Ui};
+use log::error;
+use parking_lot::RwLock;
+use rfd::FileDialog;
+
+use crate::document::resources::Namespace;
+use crate::document::textures::{encode_flat, Picture};
+use crate::document::Document;
+use crate::editor::core::Editor;
+use crate::utils::tga;
+
+/// Operations offered by the lump manager toolbar.
+#[derive(Debug, Clone, Copy)]
+enum LumpAction {
+    Import,
+    /// Converts a TGA image to a patch or flat in the given namespace.
+    ImportTga(Namespace),
+    Export,
+    Rename,
+    MoveUp,
+    MoveDown,
+    Delete,
+}
+
+/// A window listing every lump of the WAD directory, with tools to import,
+/// export, rename, reorder and delete lumps. Changes are written on save.
+pub struct LumpPanel {
+    editor: Arc<RwLock<Editor>>,
+    /// Directory index of the selected lump.
+    selected: Option<usize>,
+    /// Edit buffer for the rename field.
+    rename_buffer: String,
+}
+
+impl LumpPanel {
+    pub fn new(editor: Arc<RwLock<Editor>>) -> Self {
+        Self {
+            editor,
+            selected: None,
+            rename_buffer: String::new(),
+        }
+    }
+
+    /// Called every frame. Draws the window if the editor has it enabled.
+    pub fn update(&mut self, ctx: &Context) {
+        let (mut open, doc_arc) = {
+            let editor = self.editor.read();
+            (editor.show_lump_panel, editor.document())
+        };
+        let doc_arc = match doc_arc {
+            Some(doc) if open => doc,
+            _ => return,
+        };
+
+        let mut action = None;
+        egui::Window::new("Lump Manager")
+            .open(&mut open)
+            .default_width(380.0)
+            .show(ctx, |ui| {
+                action = self.show_toolbar(ui);
+                ui.separator();
+                self.show_directory(ui, &doc_arc);
+            });
+        self.editor.write().show_lump_panel = open;
+
+        if let Some(action) = action {
+            self.apply(action, &doc_arc);
+        }
+    }
+
+    fn show_toolbar(&mut self, ui: &mut Ui) -> Option<LumpAction> {
+        let mut action = None;
+        let has_selection = self.selected.is_some();
+
+        ui.horizontal(|ui| {
+            if ui.button("Import...").clicked() {
+                action = Some(LumpAction::Import);
+            }
+            ui.menu_button("Import TGA", |ui| {
+                if ui.button("As Patch...").clicked() {
+                    action = Some(LumpAction::ImportTga(Namespace::Patches));
+                    ui.close_menu();
+                }
+                if ui.button("As Flat...").clicked() {
+                    action = Some(LumpAction::ImportTga(Namespace::Flats));
+                    ui.close_menu();
+                }
+            });
+            if ui.add_enabled(has_selection, egui::Button::new("Export...")).clicked() {
+                action = Some(LumpAction::Export);
+            }
+            if ui.add_enabled(has_selection, egui::Button::new("Up")).clicked() {
+                action = Some(LumpAction::MoveUp);
+            }
+            if ui.add_enabled(has_selection, egui::Button::new("Down")).clicked() {
+                action = Some(LumpAction::MoveDown);
+            }
+            if ui.add_enabled(has_selection, egui::Button::new("Delete")).clicked() {
+                action = Some(LumpAction::Delete);
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.add_enabled(
+                has_selection,
+                egui::TextEdit::singleline(&mut self.rename_buffer).desired_width(90.0),
+            );
+            if ui.add_enabled(has_selection, egui::Button::new("Rename")).clicked() {
+                action = Some(LumpAction::Rename);
+            }
+        });
+        action
+    }
+
+    /// Lists the directory: index, name, size and offset of every lump.
+    fn show_directory(&mut self, ui: &mut Ui, doc_arc: &Arc<RwLock<Document>>) {
+        let directory = doc_arc.read().directory.read().clone();
+        ui.label(format!("{} lumps", directory.len()));
+
+        ScrollArea::vertical().max_height(420.0).show(ui, |ui| {
+            egui::Grid::new("lump_grid")
+                .num_columns(4)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.strong("#");
+                    ui.strong("Name");
+                    ui.strong("Size");
+                    ui.strong("Offset");
+                    ui.end_row();
+
+                    for (i, entry) in directory.iter().enumerate() {
+                        ui.label(i.to_string());
+                        if ui.selectable_label(self.selected == Some(i), &entry.name).clicked() {
+                            self.selected = Some(i);
+                            self.rename_buffer = entry.name.clone();
+                        }
+                        ui.label(entry.size.to_string());
+                        ui.label(entry.offset.to_string());
+                        ui.end_row();
+                    }
+                });
+        });
+    }
+
+    fn apply(&mut self, action: LumpAction, doc_arc: &Arc<RwLock<Document>>) {
+        let result = match action {
+            LumpAction::Import => self.import(doc_arc),
+            LumpAction::ImportTga(namespace) => self.import_tga(doc_arc, namespace),
+            LumpAction::Export => self.export(doc_arc),
+            LumpAction::Rename => self.with_selection(|index| {
+                doc_arc.write().rename_lump(index, &self.rename_buffer)?;
+                Ok(format!("Renamed lump {} to {}.", index, self.rename_buffer.trim().to_ascii_uppercase()))
+            }),
+            LumpAction::MoveUp => self.move_selected(doc_arc, -1),
+            LumpAction::MoveDown => self.move_selected(doc_arc, 1),
+            LumpAction::Delete => {
+                let result = self.with_selection(|index| {
+                    doc_arc.write().delete_lump(index)?;
+                    Ok(format!("Deleted lump {}.", index))
+                });
+                self.selected = None;
+                result
+            }
+        };
+
+        let mut editor = self.editor.write();
+        match result {
+            Ok(message) if !message.is_empty() => {
+                editor.status_message = message;
+                editor.error_message = None;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                error!("Lump operation failed: {}", e);
+                editor.error_message = Some(format!("Lump operation failed: {}", e));
+            }
+        }
+    }
+
+    fn with_selection<F>(&self, f: F) -> io::Result<String>
+    where
+        F: FnOnce(usize) -> io::Result<String>,
+    {
+        match self.selected {
+            Some(index) => f(index),
+            None => Ok(String::new()),
+        }
+    }
+
+    /// Imports a file as a new lump after the selection (or at the end).
+    /// The lump is named after the file, uppercased and cut to 8 characters.
+    fn import(&mut self, doc_arc: &Arc<RwLock<Document>>) -> io::Result<String> {
+        let path = match FileDialog::new().pick_file() {
+            Some(path) => path,
+            None => return Ok(String::new()),
+        };
+        let data = fs::read(&path)?;
+        let name = lump_name_for(&path);
+
+        let mut doc = doc_arc.write();
+        let index = self
+            .selected
+            .map(|i| i + 1)
+            .unwrap_or_else(|| doc.directory.read().len());
+        doc.insert_lump(index, &name, data)?;
+        self.selected = Some(index);
+        self.rename_buffer = name.clone();
+        Ok(format!("Imported {} as lump {}.", path.display(), name))
+    }
+
+    /// Converts a TGA file to the game's palette and stores it as a patch or
+    /// flat named after the file. A lump of that name in the namespace is
+    /// replaced.
+    fn import_tga(&mut self, doc_arc: &Arc<RwLock<Document>>, namespace: Namespace) -> io::Result<String> {
+        let path = match FileDialog::new().add_filter("TGA Images", &["tga"]).pick_file() {
+            Some(path) => path,
+            None => return Ok(String::new()),
+        };
+        let image = tga::load(&path).map_err(|e| io::Error::other(e.to_string()))?;
+        let name = lump_name_for(&path);
+
+        let mut doc = doc_arc.write();
+        let shading = doc.shading().map_err(|e| io::Error::other(e.to_string()))?;
+        let data = match namespace {
+            Namespace::Flats => encode_flat(&name, &image, shading.palette())
+                .map_err(|e| io::Error::other(e.to_string()))?,
+            _ => Picture::from_rgba(&image, shading.palette()).encode(),
+        };
+        doc.put_namespace_lump(namespace, &name, data)?;
+        self.selected = None;
+        let kind = if namespace == Namespace::Flats { "flat" } else { "patch" };
+        Ok(format!("Imported {} as {} {}.", path.display(), kind, name))
+    }
+
+    fn export(&self, doc_arc: &Arc<RwLock<Document>>) -> io::Result<String> {
+        let index = match self.selected {
+            Some(index) => index,
+            None => return Ok(String::new()),
+        };
+        let (name, data) = {
+            let doc = doc_arc.read();
+            let name = doc.directory.read().get(index).map(|e| e.name.clone());
+            match (name, doc.lump_data(index)) {
+                (Some(name), Some(data)) => (name, data),
+                _ => return Err(io::Error::new(io::ErrorKind::NotFound, "Lump not found")),
+            }
+        };
+        let path = match FileDialog::new().set_file_name(format!("{}.lmp", name)).save_file() {
+            Some(path) => path,
+            None => return Ok(String::new()),
+        };
+        fs::write(&path, data)?;
+        Ok(format!("Exported {} to {}.", name, path.display()))
+    }
+
+    fn move_selected(&mut self, doc_arc: &Arc<RwLock<Document>>, delta: isize) -> io::Result<String> {
+        let index = match self.selected {
+            Some(index) => index,
+            None => return Ok(String::new()),
+        };
+        let len = doc_arc.read().directory.read().len();
+        let target = index as isize + delta;
+        if target < 0 || target as usize >= len {
+            return Ok(String::new());
+        }
+        doc_arc.write().move_lump(index, target as usize)?;
+        self.selected = Some(target as usize);
+        Ok(format!("Moved lump to position {}.", target))
+    }
+}
+
+/// A lump name from a file name: the stem, uppercased and cut to 8
+/// characters.
+fn lump_name_for(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_ascii_graphic() { c.to_ascii_uppercase() } else { '_' })
+        .take(8)
+        .collect()
+}
@@ -10,6 +10,11 @@ use crate::ui::{
     side_panel::SidePanel,
     central_panel::CentralPanel,
     status_bar::StatusBar,
+    lump_panel::LumpPanel,
+    load_report::LoadReportWindow,
+    script_viewer::ScriptViewer,
+    texture_browser::TextureBrowser,
+    diff_window::DiffWindow,
     dialog::DialogManager, // Keep DialogManager
     Theme,
 };
@@ -42,6 +47,11 @@ pub struct MainWindow {
     side_panel: SidePanel,
     central_panel: CentralPanel,
     status_bar: StatusBar,
+    lump_panel: LumpPanel,
+    load_report: LoadReportWindow,
+    script_viewer: ScriptViewer,
+    texture_browser: TextureBrowser,
+    diff_window: DiffWindow,
 }
 
 impl MainWindow {
@@ -57,6 +67,11 @@ impl MainWindow {
             side_panel: SidePanel::new(editor.clone()),
             central_panel: CentralPanel::new(editor.clone()),
             status_bar: StatusBar::new(editor.clone()),
+            lump_panel: LumpPanel::new(editor.clone()),
+            load_report: LoadReportWindow::new(editor.clone()),
+            script_viewer: ScriptViewer::new(editor.clone()),
+            texture_browser: TextureBrowser::new(editor.clone()),
+            diff_window: DiffWindow::new(editor.clone()),
         }
     }
 
@@ -67,8 +82,13 @@ impl MainWindow {
         // Update UI elements.  These now call into the separate components.
         self.menu_bar.update(ctx);
         self.side_panel.update(ctx);
+        self.texture_browser.update(ctx);
         self.central_panel.update(ctx);
         self.status_bar.update(ctx);
+        self.lump_panel.update(ctx);
+        self.load_report.update(ctx);
+        self.script_viewer.update(ctx);
+        self.diff_window.update(ctx);
         self.dialog_manager.update(ctx); // Update dialogs
 
     }
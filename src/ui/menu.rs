@@ -3,6 +3,7 @@
 use std::sync::Arc;
 use eframe::egui::{self, Context};
 use parking_lot::RwLock;
+use crate::bsp::{NodeFormat, RejectMode};
 use crate::editor::Editor;
 
 pub struct MenuBar {
@@ -32,6 +33,23 @@ impl MenuBar {
                         self.editor.write().save_document_wrapper();
                         ui.close_menu();
                     }
+                    if ui.button("Export Overview...").clicked() {
+                        self.editor.write().export_overview_dialog();
+                        ui.close_menu();
+                    }
+                    if ui.button("Compare With WAD...").clicked() {
+                        self.editor.write().compare_with_dialog();
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    if ui.button("Load Resource WADs...").clicked() {
+                        self.editor.write().load_resources_dialog();
+                        ui.close_menu();
+                    }
+                    if ui.button("Clear Resources").clicked() {
+                        self.editor.write().clear_resources();
+                        ui.close_menu();
+                    }
                     ui.separator();
                     if ui.button("Exit").clicked() {
                         // Handle exit at a higher level (e.g., eframe integration)
@@ -60,6 +78,18 @@ impl MenuBar {
                     if ui.checkbox(&mut editor.show_bsp_debug, "BSP Debug").clicked() {
                         ui.close_menu();
                     }
+                    if ui.checkbox(&mut editor.show_lump_panel, "Lump Manager").clicked() {
+                        ui.close_menu();
+                    }
+                    if ui.checkbox(&mut editor.show_load_report, "Load Report").clicked() {
+                        ui.close_menu();
+                    }
+                    if ui.checkbox(&mut editor.show_script_viewer, "ACS Scripts").clicked() {
+                        ui.close_menu();
+                    }
+                    if ui.checkbox(&mut editor.show_texture_browser, "Texture Browser").clicked() {
+                        ui.close_menu();
+                    }
                 });
 
                 ui.menu_button("Tools", |ui| {
@@ -68,6 +98,19 @@ impl MenuBar {
                         self.editor.write().build_nodes_wrapper();
                         ui.close_menu();
                     }
+                    ui.checkbox(&mut self.editor.write().fast_nodes, "Fast Node Building");
+                    ui.menu_button("REJECT", |ui| {
+                        let mut editor = self.editor.write();
+                        ui.radio_value(&mut editor.reject_mode, RejectMode::Zeroed, "Zeroed");
+                        ui.radio_value(&mut editor.reject_mode, RejectMode::Full, "Full Visibility (slower saves)");
+                    });
+                    ui.menu_button("Node Format", |ui| {
+                        let mut editor = self.editor.write();
+                        ui.radio_value(&mut editor.node_format, None, "Automatic");
+                        for format in [NodeFormat::Vanilla, NodeFormat::DeepBsp, NodeFormat::Xnod, NodeFormat::Znod] {
+                            ui.radio_value(&mut editor.node_format, Some(format), format.to_string());
+                        }
+                    });
                     if ui.button("Generate Test Map").clicked() {
                         // self.editor.write().generate_test_map();
                         ui.close_menu();
@@ -7,6 +7,12 @@ pub mod theme;
 pub mod central_panel;
 pub mod side_panel;
 pub mod status_bar;
+pub mod lump_panel;
+pub mod load_report;
+pub mod script_viewer;
+pub mod previews;
+pub mod texture_browser;
+pub mod diff_window;
 pub use dialog::DialogManager;
 pub use theme::Theme;
 mod tool_window_manager; 
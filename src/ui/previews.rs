@@ -0,0 +1,101 @@
+// src/ui/previews.rs
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use eframe::egui::{self, ColorImage, Context, TextureHandle, TextureOptions};
+use image::RgbaImage;
+use parking_lot::RwLock;
+
+use crate::document::palette::Palette;
+use crate::document::resources::Namespace;
+use crate::document::textures::{decode_flat, TextureSet};
+use crate::document::Document;
+
+/// Which kind of graphic a preview shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PreviewKind {
+    /// A composite wall texture from TEXTUREx.
+    Texture,
+    Flat,
+}
+
+/// Texture and flat thumbnails for the current document, uploaded to the
+/// GPU on first use. Everything is dropped when the document, its lumps or
+/// the resource stack change.
+#[derive(Default)]
+pub struct PreviewCache {
+    key: Option<(usize, u64)>,
+    palette: Option<Palette>,
+    texture_set: Option<TextureSet>,
+    flat_names: Vec<String>,
+    /// `None` for graphics that could not be found or decoded.
+    previews: HashMap<(PreviewKind, String), Option<TextureHandle>>,
+}
+
+impl PreviewCache {
+    /// Re-reads the palette and the texture and flat lists if the document
+    /// or the resources changed since the last call.
+    pub fn refresh(&mut self, doc_arc: &Arc<RwLock<Document>>, doc: &Document) {
+        let key = (Arc::as_ptr(doc_arc) as usize, doc.revision());
+        if self.key == Some(key) {
+            return;
+        }
+        self.key = Some(key);
+        self.palette = doc.shading().ok().map(|shading| shading.palette().clone());
+        self.texture_set = doc.texture_set().ok();
+        self.flat_names = doc.flat_names();
+        self.previews.clear();
+    }
+
+    /// PNAMES and TEXTUREx, if the document or its resources have them.
+    pub fn texture_set(&self) -> Option<&TextureSet> {
+        self.texture_set.as_ref()
+    }
+
+    /// Every flat name, sorted.
+    pub fn flat_names(&self) -> &[String] {
+        &self.flat_names
+    }
+
+    /// The thumbnail of `name`, decoding it on first use.
+    pub fn get(&mut self, ctx: &Context, doc: &Document, kind: PreviewKind, name: &str) -> Option<TextureHandle> {
+        let key = (kind, name.to_ascii_uppercase());
+        if let Some(preview) = self.previews.get(&key) {
+            return preview.clone();
+        }
+        let preview = self.image(doc, kind, name).map(|image| {
+            let size = [image.width() as usize, image.height() as usize];
+            let image = ColorImage::from_rgba_unmultiplied(size, image.as_raw());
+            let id = match kind {
+                PreviewKind::Texture => format!("texture:{}", key.1),
+                PreviewKind::Flat => format!("flat:{}", key.1),
+            };
+            ctx.load_texture(id, image, TextureOptions::NEAREST)
+        });
+        self.previews.insert(key, preview.clone());
+        preview
+    }
+
+    /// Decodes `name` at full size, without caching it.
+    pub fn image(&self, doc: &Document, kind: PreviewKind, name: &str) -> Option<RgbaImage> {
+        let palette = self.palette.as_ref()?;
+        match kind {
+            PreviewKind::Texture => self
+                .texture_set
+                .as_ref()?
+                .composite(name, palette, |patch| doc.find_patch(patch))
+                .ok(),
+            PreviewKind::Flat => {
+                let data = doc.find_in_namespace(name, Namespace::Flats)?;
+                decode_flat(name, &data, palette).ok()
+            }
+        }
+    }
+}
+
+/// Draws `texture` scaled to fit a `size` square, keeping its aspect ratio.
+pub fn thumbnail(ui: &mut egui::Ui, texture: &TextureHandle, size: f32) -> egui::Response {
+    let [w, h] = texture.size();
+    let scale = size / w.max(h).max(1) as f32;
+    ui.add_sized([size, size], egui::Image::new(texture.id(), [w as f32 * scale, h as f32 * scale]))
+}
@@ -0,0 +1,172 @@
+// src/ui/script_viewer.rs
+
+use std::sync::Arc;
+use eframe::egui::{self, Context, RichText, ScrollArea, Ui};
+use parking_lot::RwLock;
+
+use crate::document::behavior::BehaviorLump;
+use crate::editor::core::Editor;
+
+/// What the listing pane shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Selected {
+    Script(usize),
+    Function(usize),
+    /// The whole lump.
+    All,
+}
+
+/// A read-only window over the current level's BEHAVIOR lump: scripts,
+/// functions, map variables and strings, with a p-code listing.
+pub struct ScriptViewer {
+    editor: Arc<RwLock<Editor>>,
+    selected: Selected,
+    /// The lump bytes last parsed and the result, so parsing happens only
+    /// when the level changes.
+    cache: Option<(Vec<u8>, Result<BehaviorLump, String>)>,
+    /// Listing text for `selected`, rebuilt when the selection changes.
+    listing: Option<(Selected, String)>,
+}
+
+impl ScriptViewer {
+    pub fn new(editor: Arc<RwLock<Editor>>) -> Self {
+        Self {
+            editor,
+            selected: Selected::All,
+            cache: None,
+            listing: None,
+        }
+    }
+
+    /// Called every frame. Draws the window if the editor has it enabled.
+    pub fn update(&mut self, ctx: &Context) {
+        let (mut open, doc_arc) = {
+            let editor = self.editor.read();
+            (editor.show_script_viewer, editor.document())
+        };
+        if !open {
+            return;
+        }
+
+        let data = doc_arc.map(|doc| doc.read().behavior_data.read().clone()).unwrap_or_default();
+        if self.cache.as_ref().is_none_or(|(cached, _)| *cached != data) {
+            let parsed = BehaviorLump::parse(&data).map_err(|e| e.to_string());
+            self.cache = Some((data, parsed));
+            self.selected = Selected::All;
+            self.listing = None;
+        }
+
+        egui::Window::new("ACS Scripts")
+            .open(&mut open)
+            .default_width(640.0)
+            .default_height(480.0)
+            .show(ctx, |ui| match &self.cache {
+                Some((data, _)) if data.is_empty() => {
+                    ui.label("The current level has no BEHAVIOR lump.");
+                }
+                Some((_, Err(e))) => {
+                    ui.colored_label(egui::Color32::RED, format!("Cannot read BEHAVIOR: {}", e));
+                }
+                Some((_, Ok(lump))) => {
+                    let mut selected = self.selected;
+                    if self.listing.as_ref().is_none_or(|(shown, _)| *shown != selected) {
+                        self.listing = Some((selected, listing_text(lump, selected)));
+                    }
+                    let text = self.listing.as_ref().map_or("", |(_, text)| text.as_str());
+                    ui.columns(2, |columns| {
+                        show_contents(&mut columns[0], lump, &mut selected);
+                        ScrollArea::both().id_source("acs_listing").show(&mut columns[1], |ui| {
+                            ui.label(RichText::new(text).monospace());
+                        });
+                    });
+                    self.selected = selected;
+                }
+                None => {}
+            });
+        self.editor.write().show_script_viewer = open;
+    }
+}
+
+/// Scripts, functions, variables, strings and imports.
+fn show_contents(ui: &mut Ui, lump: &BehaviorLump, selected: &mut Selected) {
+    ui.label(format!("{} object", lump.format.name()));
+    ScrollArea::vertical().id_source("acs_contents").show(ui, |ui| {
+        if ui.selectable_label(*selected == Selected::All, "Full listing").clicked() {
+            *selected = Selected::All;
+        }
+
+        egui::CollapsingHeader::new(format!("Scripts ({})", lump.scripts.len()))
+            .default_open(true)
+            .show(ui, |ui| {
+                for (i, script) in lump.scripts.iter().enumerate() {
+                    let label = lump.script_header(script);
+                    if ui.selectable_label(*selected == Selected::Script(i), label).clicked() {
+                        *selected = Selected::Script(i);
+                    }
+                }
+            });
+
+        egui::CollapsingHeader::new(format!("Functions ({})", lump.functions.len())).show(ui, |ui| {
+            for (i, function) in lump.functions.iter().enumerate() {
+                let label = lump.function_header(i, function);
+                if ui.selectable_label(*selected == Selected::Function(i), label).clicked() {
+                    *selected = Selected::Function(i);
+                }
+            }
+        });
+
+        egui::CollapsingHeader::new(format!("Map Variables ({})", lump.map_vars.len())).show(ui, |ui| {
+            for var in &lump.map_vars {
+                let mut text = format!("{}: {}", var.index, var.name.as_deref().unwrap_or("-"));
+                match var.array_size {
+                    Some(size) => text.push_str(&format!(" [{}]", size)),
+                    None => text.push_str(&format!(" = {}", var.initial)),
+                }
+                ui.label(text);
+            }
+            for (index, name) in &lump.imported_vars {
+                ui.label(format!("{}: {} (imported)", index, name));
+            }
+        });
+
+        egui::CollapsingHeader::new(format!("Strings ({})", lump.strings.len())).show(ui, |ui| {
+            for (i, string) in lump.strings.iter().enumerate() {
+                ui.label(format!("{}: {:?}", i, string));
+            }
+        });
+
+        if !lump.libraries.is_empty() {
+            egui::CollapsingHeader::new("Imports").show(ui, |ui| {
+                for library in &lump.libraries {
+                    ui.label(library);
+                }
+            });
+        }
+    });
+}
+
+fn listing_text(lump: &BehaviorLump, selected: Selected) -> String {
+    match selected {
+        Selected::All => lump.listing(),
+        Selected::Script(i) => {
+            let script = &lump.scripts[i];
+            listing_of(lump.script_header(script), lump, script.address)
+        }
+        Selected::Function(i) => {
+            let function = &lump.functions[i];
+            if function.is_imported() {
+                lump.function_header(i, function)
+            } else {
+                listing_of(lump.function_header(i, function), lump, function.address)
+            }
+        }
+    }
+}
+
+fn listing_of(header: String, lump: &BehaviorLump, address: u32) -> String {
+    let mut text = header;
+    for instruction in lump.disassemble(address) {
+        text.push_str(&format!("\n    {}", instruction));
+    }
+    text
+}
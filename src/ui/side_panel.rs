@@ -3,7 +3,8 @@ use eframe::egui::{self, Context, Ui, ScrollArea};
 use parking_lot::RwLock;
 use log::{error, info};
 
-use crate::editor::core::Editor;
+use crate::document::Document;
+use crate::editor::core::{DocumentSource, Editor};
 // Use the Selection enum from the central panel module.
 use crate::ui::central_panel::Selection;
 
@@ -13,6 +14,8 @@ pub struct SidePanel {
     editor: Arc<RwLock<Editor>>,
     /// Whether this side panel is currently visible.
     pub show_side_panel: bool,
+    /// Edit buffer for creating or renaming a level marker.
+    level_name: String,
 }
 
 impl SidePanel {
@@ -21,6 +24,7 @@ impl SidePanel {
         Self {
             editor,
             show_side_panel: true,
+            level_name: String::new(),
         }
     }
 
@@ -42,6 +46,9 @@ impl SidePanel {
                         ui.separator();
                         self.show_levels(ui);
                         ui.separator();
+                        self.show_archive_maps(ui);
+                        self.show_resources(ui);
+                        ui.separator();
                         self.show_properties(ui);
                     });
             });
@@ -73,25 +80,24 @@ impl SidePanel {
     fn show_levels(&mut self, ui: &mut Ui) {
         ui.heading("Levels");
 
-        let ed_read = self.editor.read();
-        let doc_opt = ed_read.document();
-
-        if doc_opt.is_none() {
-            ui.label("No document loaded.");
-            return;
-        }
+        let doc_opt = self.editor.read().document();
+        let doc_arc = match doc_opt {
+            Some(doc) => doc,
+            None => {
+                ui.label("No document loaded.");
+                return;
+            }
+        };
         // Acquire the document and fetch the list of levels.
-        let doc_arc = doc_opt.unwrap();
-        let levels = {
+        let (levels, selected) = {
             let doc = doc_arc.read();
-            doc.available_levels()
+            let selected = doc.selected_level.read().clone();
+            (doc.available_levels(), selected)
         };
 
         if levels.is_empty() {
             ui.label("No levels found. Check your WAD file.");
-            return;
         }
-        drop(ed_read);
 
         // Display each level as a button.
         for level_name in &levels {
@@ -107,6 +113,88 @@ impl SidePanel {
                 return;
             }
         }
+
+        self.show_level_markers(ui, &doc_arc, selected);
+    }
+
+    /// A marker name field with buttons to add an empty level or rename the
+    /// current one. Changes are written on save.
+    fn show_level_markers(&mut self, ui: &mut Ui, doc_arc: &Arc<RwLock<Document>>, selected: Option<String>) {
+        let (mut create, mut rename) = (false, false);
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.level_name).hint_text("Marker").desired_width(80.0));
+            create = ui.button("New").clicked();
+            rename = ui.add_enabled(selected.is_some(), egui::Button::new("Rename")).clicked();
+        });
+
+        let name = self.level_name.trim().to_ascii_uppercase();
+        let result = match (create, rename, selected) {
+            (true, _, _) => doc_arc.write().create_level(&name).map(|_| format!("Created level {}.", name)),
+            (_, true, Some(old)) => doc_arc
+                .write()
+                .rename_level(&old, &name)
+                .map(|_| format!("Renamed level {} to {}.", old, name)),
+            _ => return,
+        };
+
+        let mut ed_write = self.editor.write();
+        match result {
+            Ok(message) => {
+                ed_write.status_message = message;
+                ed_write.error_message = None;
+                self.level_name.clear();
+            }
+            Err(e) => {
+                error!("Level marker change failed: {}", e);
+                ed_write.error_message = Some(e.to_string());
+            }
+        }
+    }
+
+    /// Lists the map WADs of the open PK3, if any, to switch between them.
+    fn show_archive_maps(&self, ui: &mut Ui) {
+        let (maps, current) = {
+            let ed_read = self.editor.read();
+            let current = match ed_read.source() {
+                Some(DocumentSource::Archive { entry, .. }) => Some(entry.clone()),
+                _ => None,
+            };
+            (ed_read.archive_maps().to_vec(), current)
+        };
+        if maps.is_empty() {
+            return;
+        }
+        ui.heading("Archive Maps");
+
+        for entry in &maps {
+            let selected = current.as_deref() == Some(entry.as_str());
+            if ui.selectable_label(selected, entry).clicked() && !selected {
+                let mut ed_write = self.editor.write();
+                ed_write.cancel_current_operation();
+                if let Err(e) = ed_write.open_archive_map(entry) {
+                    error!("Failed to open {}: {}", entry, e);
+                    ed_write.error_message = Some(e);
+                }
+                return;
+            }
+        }
+        ui.separator();
+    }
+
+    /// Lists the resource WADs, base IWAD first; later entries override earlier ones.
+    fn show_resources(&self, ui: &mut Ui) {
+        ui.heading("Resources");
+
+        let resources = self.editor.read().resources();
+        let resources = resources.read();
+        if resources.is_empty() {
+            ui.label("No IWAD loaded (File > Load Resource WADs...).");
+            return;
+        }
+        for wad in resources.wads() {
+            let kind = if wad.is_iwad { "IWAD" } else { "PWAD" };
+            ui.label(format!("{} ({}, {} lumps)", wad.name, kind, wad.directory.len()));
+        }
     }
 
     /// Displays properties of the currently selected object.
@@ -0,0 +1,327 @@
+// src/ui/texture_browser.rs
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use eframe::egui::{self, Context, Ui};
+use parking_lot::RwLock;
+use rfd::FileDialog;
+
+use crate::document::Document;
+use crate::editor::commands::CommandType;
+use crate::editor::core::Editor;
+use crate::ui::previews::{thumbnail, PreviewCache, PreviewKind};
+use crate::utils::tga;
+
+const THUMBNAIL_SIZE: f32 = 64.0;
+
+/// The part of a wall or sector a picked entry goes on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Surface {
+    Upper,
+    Middle,
+    Lower,
+    Floor,
+    Ceiling,
+}
+
+/// One row of the list.
+struct Entry {
+    name: String,
+    /// Known up front for textures; flats report theirs once decoded.
+    size: Option<[usize; 2]>,
+}
+
+/// Lists every wall texture or flat of the document and its resources, with
+/// thumbnails and how often the map uses each. Clicking an entry puts it on
+/// the selected linedefs or sectors. Docked on the right, or floating.
+pub struct TextureBrowser {
+    editor: Arc<RwLock<Editor>>,
+    previews: PreviewCache,
+    kind: PreviewKind,
+    wall_surface: Surface,
+    sector_surface: Surface,
+    filter: String,
+    docked: bool,
+}
+
+impl TextureBrowser {
+    pub fn new(editor: Arc<RwLock<Editor>>) -> Self {
+        Self {
+            editor,
+            previews: PreviewCache::default(),
+            kind: PreviewKind::Texture,
+            wall_surface: Surface::Middle,
+            sector_surface: Surface::Floor,
+            filter: String::new(),
+            docked: true,
+        }
+    }
+
+    /// Called every frame, before the central panel so the docked browser
+    /// takes its space. Draws the browser if the editor has it enabled.
+    pub fn update(&mut self, ctx: &Context) {
+        let (mut open, doc_arc) = {
+            let editor = self.editor.read();
+            (editor.show_texture_browser, editor.document())
+        };
+        if !open {
+            return;
+        }
+
+        if self.docked {
+            egui::SidePanel::right("texture_browser")
+                .resizable(true)
+                .default_width(300.0)
+                .show(ctx, |ui| self.contents(ui, doc_arc.as_ref()));
+        } else {
+            egui::Window::new("Texture Browser")
+                .open(&mut open)
+                .default_width(300.0)
+                .default_height(480.0)
+                .show(ctx, |ui| self.contents(ui, doc_arc.as_ref()));
+            self.editor.write().show_texture_browser = open;
+        }
+    }
+
+    fn contents(&mut self, ui: &mut Ui, doc_arc: Option<&Arc<RwLock<Document>>>) {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.kind, PreviewKind::Texture, "Textures");
+            ui.selectable_value(&mut self.kind, PreviewKind::Flat, "Flats");
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.button(if self.docked { "Float" } else { "Dock" }).clicked() {
+                    self.docked = !self.docked;
+                }
+            });
+        });
+        ui.horizontal(|ui| match self.kind {
+            PreviewKind::Texture => {
+                ui.label("Apply to:");
+                ui.radio_value(&mut self.wall_surface, Surface::Upper, "Upper");
+                ui.radio_value(&mut self.wall_surface, Surface::Middle, "Middle");
+                ui.radio_value(&mut self.wall_surface, Surface::Lower, "Lower");
+            }
+            PreviewKind::Flat => {
+                ui.label("Apply to:");
+                ui.radio_value(&mut self.sector_surface, Surface::Floor, "Floor");
+                ui.radio_value(&mut self.sector_surface, Surface::Ceiling, "Ceiling");
+            }
+        });
+        ui.add(egui::TextEdit::singleline(&mut self.filter).hint_text("Filter by name"));
+        ui.separator();
+
+        let doc_arc = match doc_arc {
+            Some(doc_arc) => doc_arc,
+            None => {
+                ui.label("No document loaded.");
+                return;
+            }
+        };
+
+        let mut picked = None;
+        let mut exported = None;
+        {
+            let doc = doc_arc.read();
+            self.previews.refresh(doc_arc, &doc);
+            let entries = self.entries();
+            if entries.is_empty() {
+                ui.label("Nothing found. Load an IWAD with File > Load Resource WADs.");
+                return;
+            }
+            let usage = usage_counts(&doc, self.kind);
+            let ctx = ui.ctx().clone();
+            ui.label(format!("{} entries", entries.len()));
+            egui::ScrollArea::vertical()
+                .auto_shrink([false, false])
+                .show_rows(ui, THUMBNAIL_SIZE + 4.0, entries.len(), |ui, range| {
+                    for entry in &entries[range] {
+                        let preview = self.previews.get(&ctx, &doc, self.kind, &entry.name);
+                        let size = entry.size.or_else(|| preview.as_ref().map(|t| t.size()));
+                        let response = ui
+                            .horizontal(|ui| {
+                                match &preview {
+                                    Some(texture) => {
+                                        thumbnail(ui, texture, THUMBNAIL_SIZE);
+                                    }
+                                    None => {
+                                        ui.add_sized([THUMBNAIL_SIZE, THUMBNAIL_SIZE], egui::Label::new("?"));
+                                    }
+                                }
+                                ui.vertical(|ui| {
+                                    ui.strong(&entry.name);
+                                    if let Some([w, h]) = size {
+                                        ui.label(format!("{} x {}", w, h));
+                                    }
+                                    let count = usage.get(&entry.name).copied().unwrap_or(0);
+                                    ui.label(format!("Used {} time(s)", count));
+                                });
+                            })
+                            .response
+                            .interact(egui::Sense::click());
+                        let response = response.on_hover_text("Click to apply to the selection");
+                        if response.clicked() {
+                            picked = Some(entry.name.clone());
+                        }
+                        response.context_menu(|ui| {
+                            if ui.button("Export as TGA...").clicked() {
+                                exported = Some(entry.name.clone());
+                                ui.close_menu();
+                            }
+                        });
+                    }
+                });
+        }
+
+        if let Some(name) = picked {
+            self.apply(doc_arc, &name);
+        }
+        if let Some(name) = exported {
+            self.export_tga(doc_arc, &name);
+        }
+    }
+
+    /// Saves the decoded texture or flat `name` as a TGA file.
+    fn export_tga(&self, doc_arc: &Arc<RwLock<Document>>, name: &str) {
+        let image = match self.previews.image(&doc_arc.read(), self.kind, name) {
+            Some(image) => image,
+            None => {
+                self.editor.write().error_message = Some(format!("Could not decode {}.", name));
+                return;
+            }
+        };
+        let path = match FileDialog::new()
+            .add_filter("TGA Images", &["tga"])
+            .set_file_name(format!("{}.tga", name))
+            .save_file()
+        {
+            Some(path) => path,
+            None => return,
+        };
+        let mut editor = self.editor.write();
+        match tga::save(&image, &path, true) {
+            Ok(()) => editor.status_message = format!("Exported {} to {}.", name, path.display()),
+            Err(e) => editor.error_message = Some(format!("Failed to export {}: {}", name, e)),
+        }
+    }
+
+    /// The entries of the current tab that match the filter.
+    fn entries(&self) -> Vec<Entry> {
+        let needle = self.filter.to_ascii_uppercase();
+        match self.kind {
+            PreviewKind::Texture => {
+                let mut entries: Vec<Entry> = self
+                    .previews
+                    .texture_set()
+                    .map(|set| {
+                        set.textures
+                            .iter()
+                            .filter(|t| t.name.contains(&needle))
+                            .map(|t| Entry { name: t.name.clone(), size: Some([t.width, t.height]) })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                entries.sort_by(|a, b| a.name.cmp(&b.name));
+                entries.dedup_by(|a, b| a.name == b.name);
+                entries
+            }
+            PreviewKind::Flat => self
+                .previews
+                .flat_names()
+                .iter()
+                .filter(|name| name.contains(&needle))
+                .map(|name| Entry { name: name.clone(), size: None })
+                .collect(),
+        }
+    }
+
+    /// Puts `name` on the selected linedefs' sidedefs or the selected sectors.
+    fn apply(&self, doc_arc: &Arc<RwLock<Document>>, name: &str) {
+        let selection = self.editor.read().selection();
+        let commands: Vec<CommandType> = match self.kind {
+            PreviewKind::Texture => {
+                let doc = doc_arc.read();
+                let linedefs = doc.linedefs.read();
+                let mut sidedefs: Vec<usize> = selection
+                    .linedefs
+                    .iter()
+                    .filter_map(|&i| linedefs.get(i))
+                    .flat_map(|line| [line.right, line.left])
+                    .filter(|&side| side >= 0)
+                    .map(|side| side as usize)
+                    .collect();
+                sidedefs.sort_unstable();
+                sidedefs.dedup();
+                let texture = Some(name.to_string());
+                sidedefs
+                    .into_iter()
+                    .map(|sidedef_id| CommandType::ModifySideDef {
+                        sidedef_id,
+                        x_offset: None,
+                        y_offset: None,
+                        upper_tex: if self.wall_surface == Surface::Upper { texture.clone() } else { None },
+                        lower_tex: if self.wall_surface == Surface::Lower { texture.clone() } else { None },
+                        mid_tex: if self.wall_surface == Surface::Middle { texture.clone() } else { None },
+                    })
+                    .collect()
+            }
+            PreviewKind::Flat => {
+                let flat = Some(name.to_string());
+                selection
+                    .sectors
+                    .iter()
+                    .map(|&sector_id| CommandType::ModifySector {
+                        sector_id,
+                        floor_height: None,
+                        ceiling_height: None,
+                        floor_tex: if self.sector_surface == Surface::Floor { flat.clone() } else { None },
+                        ceiling_tex: if self.sector_surface == Surface::Ceiling { flat.clone() } else { None },
+                        light: None,
+                        r#type: None,
+                        tag: None,
+                    })
+                    .collect()
+            }
+        };
+
+        let mut editor = self.editor.write();
+        if commands.is_empty() {
+            editor.status_message = match self.kind {
+                PreviewKind::Texture => "Select linedefs to apply a texture to.".to_string(),
+                PreviewKind::Flat => "Select sectors to apply a flat to.".to_string(),
+            };
+            return;
+        }
+        let count = commands.len();
+        // One undo step for the whole selection.
+        editor.execute_command(Box::new(CommandType::BatchCommand { commands }));
+        if editor.error_message.is_none() {
+            editor.status_message = format!("Applied {} to {} object(s).", name, count);
+        }
+    }
+}
+
+/// How many sidedef surfaces (for textures) or sector planes (for flats)
+/// use each name.
+fn usage_counts(doc: &Document, kind: PreviewKind) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    let mut count = |name: &str| {
+        if !name.is_empty() && name != "-" {
+            *counts.entry(name.to_ascii_uppercase()).or_insert(0) += 1;
+        }
+    };
+    match kind {
+        PreviewKind::Texture => {
+            for side in doc.sidedefs.read().iter() {
+                count(&side.upper_tex);
+                count(&side.mid_tex);
+                count(&side.lower_tex);
+            }
+        }
+        PreviewKind::Flat => {
+            for sector in doc.sectors.read().iter() {
+                count(&sector.floor_tex);
+                count(&sector.ceiling_tex);
+            }
+        }
+    }
+    counts
+}
@@ -0,0 +1,63 @@
+// src/utils/adler.rs
+
+//! Adler-32 (RFC 1950), the quick checksum zlib and Eureka use.
+
+const MOD_ADLER: u32 = 65521;
+/// The most bytes that can be summed before `b` could overflow a u32.
+const NMAX: usize = 5552;
+
+/// A running Adler-32 checksum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+impl Default for Adler32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Adler32 {
+    pub fn new() -> Self {
+        Self { a: 1, b: 0 }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for chunk in data.chunks(NMAX) {
+            for &byte in chunk {
+                self.a += byte as u32;
+                self.b += self.a;
+            }
+            self.a %= MOD_ADLER;
+            self.b %= MOD_ADLER;
+        }
+    }
+
+    pub fn value(&self) -> u32 {
+        (self.b << 16) | self.a
+    }
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let mut adler = Adler32::new();
+    adler.update(data);
+    adler.value()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_values() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+        let long = vec![0xFF; 100_000];
+        let mut split = Adler32::new();
+        split.update(&long[..7]);
+        split.update(&long[7..]);
+        assert_eq!(split.value(), adler32(&long));
+    }
+}
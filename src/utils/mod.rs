@@ -1,2 +1,4 @@
+pub mod adler;
 pub mod geometry;
+pub mod tga;
 pub use geometry::{Point2D, Line2D, Vector2D};
\ No newline at end of file
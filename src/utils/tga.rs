@@ -0,0 +1,265 @@
+// src/utils/tga.rs
+
+//! Truevision TGA images.
+//!
+//! Reads uncompressed and RLE images that are color-mapped (8-bit indices),
+//! greyscale (8-bit) or true color (15/16, 24 and 32-bit), in either row
+//! order. Writes 32-bit BGRA, optionally RLE compressed, with rows stored top
+//! to bottom.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use image::RgbaImage;
+use thiserror::Error;
+
+const HEADER_SIZE: usize = 18;
+/// Descriptor bit: rows are stored top to bottom.
+const TOP_TO_BOTTOM: u8 = 0x20;
+/// Descriptor bit: columns are stored right to left.
+const RIGHT_TO_LEFT: u8 = 0x10;
+/// Descriptor bits: how many attribute (alpha) bits each pixel has.
+const ATTRIBUTE_BITS: u8 = 0x0F;
+
+#[derive(Debug, Error)]
+pub enum TgaError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("TGA data is truncated")]
+    Truncated,
+
+    #[error("Unsupported TGA: image type {image_type}, {depth} bits per pixel")]
+    Unsupported { image_type: u8, depth: u8 },
+
+    #[error("{width}x{height} is too large for TGA, which stops at 65535 pixels a side")]
+    TooLarge { width: u32, height: u32 },
+}
+
+/// Decodes a TGA file to RGBA.
+pub fn decode(data: &[u8]) -> Result<RgbaImage, TgaError> {
+    let header = data.get(..HEADER_SIZE).ok_or(TgaError::Truncated)?;
+    let id_length = header[0] as usize;
+    let has_colormap = header[1] == 1;
+    let image_type = header[2];
+    let colormap_first = u16::from_le_bytes([header[3], header[4]]) as usize;
+    let colormap_length = u16::from_le_bytes([header[5], header[6]]) as usize;
+    let colormap_depth = header[7];
+    let width = u16::from_le_bytes([header[12], header[13]]) as usize;
+    let height = u16::from_le_bytes([header[14], header[15]]) as usize;
+    let depth = header[16];
+    let descriptor = header[17];
+    let alpha_bits = descriptor & ATTRIBUTE_BITS;
+    let unsupported = TgaError::Unsupported { image_type, depth };
+
+    let mut pos = HEADER_SIZE + id_length;
+    let colormap = if has_colormap {
+        let entry_size = bytes_per_pixel(colormap_depth).ok_or(TgaError::Unsupported { image_type, depth: colormap_depth })?;
+        let size = colormap_length * entry_size;
+        let entries = data.get(pos..pos + size).ok_or(TgaError::Truncated)?;
+        pos += size;
+        entries.chunks_exact(entry_size).map(|c| read_color(c, colormap_depth, alpha_bits)).collect()
+    } else {
+        Vec::new()
+    };
+
+    let (compressed, kind) = match image_type {
+        1..=3 => (false, image_type),
+        9..=11 => (true, image_type - 8),
+        _ => return Err(unsupported),
+    };
+    let pixel_size = match (kind, depth) {
+        (1, 8) if has_colormap => 1,
+        (3, 8) => 1,
+        (2, 15 | 16 | 24 | 32) => bytes_per_pixel(depth).unwrap(),
+        _ => return Err(unsupported),
+    };
+    let to_rgba = |bytes: &[u8]| -> [u8; 4] {
+        match kind {
+            1 => {
+                let index = (bytes[0] as usize).wrapping_sub(colormap_first);
+                colormap.get(index).copied().unwrap_or([0, 0, 0, 255])
+            }
+            3 => [bytes[0], bytes[0], bytes[0], 255],
+            _ => read_color(bytes, depth, alpha_bits),
+        }
+    };
+
+    let count = width * height;
+    // The header's size is not trusted further than the data could fill,
+    // which is 128 pixels per byte at best.
+    let mut pixels: Vec<[u8; 4]> = Vec::with_capacity(count.min(data.len().saturating_sub(pos) * 128));
+    if compressed {
+        while pixels.len() < count {
+            let packet = *data.get(pos).ok_or(TgaError::Truncated)?;
+            pos += 1;
+            let run = ((packet & 0x7F) as usize + 1).min(count - pixels.len());
+            if packet & 0x80 != 0 {
+                let bytes = data.get(pos..pos + pixel_size).ok_or(TgaError::Truncated)?;
+                pos += pixel_size;
+                let color = to_rgba(bytes);
+                pixels.extend(std::iter::repeat_n(color, run));
+            } else {
+                let bytes = data.get(pos..pos + run * pixel_size).ok_or(TgaError::Truncated)?;
+                pos += run * pixel_size;
+                pixels.extend(bytes.chunks_exact(pixel_size).map(to_rgba));
+            }
+        }
+    } else {
+        let bytes = data.get(pos..pos + count * pixel_size).ok_or(TgaError::Truncated)?;
+        pixels.extend(bytes.chunks_exact(pixel_size).map(to_rgba));
+    }
+
+    // Many writers leave the alpha channel zeroed without saying there is
+    // none; such an image is meant to be opaque.
+    if depth == 32 && alpha_bits == 0 && pixels.iter().all(|p| p[3] == 0) {
+        pixels.iter_mut().for_each(|p| p[3] = 255);
+    }
+
+    let mut image = RgbaImage::new(width as u32, height as u32);
+    for (i, color) in pixels.into_iter().enumerate() {
+        let (mut x, mut y) = (i % width, i / width);
+        if descriptor & TOP_TO_BOTTOM == 0 {
+            y = height - 1 - y;
+        }
+        if descriptor & RIGHT_TO_LEFT != 0 {
+            x = width - 1 - x;
+        }
+        image.get_pixel_mut(x as u32, y as u32).0 = color;
+    }
+    Ok(image)
+}
+
+/// Encodes `image` as a 32-bit TGA, RLE compressed if `rle` is set.
+pub fn encode(image: &RgbaImage, rle: bool) -> Result<Vec<u8>, TgaError> {
+    let too_large = || TgaError::TooLarge { width: image.width(), height: image.height() };
+    let width = u16::try_from(image.width()).map_err(|_| too_large())?;
+    let height = u16::try_from(image.height()).map_err(|_| too_large())?;
+    let mut data = vec![0u8; HEADER_SIZE];
+    data[2] = if rle { 10 } else { 2 };
+    data[12..14].copy_from_slice(&width.to_le_bytes());
+    data[14..16].copy_from_slice(&height.to_le_bytes());
+    data[16] = 32;
+    data[17] = TOP_TO_BOTTOM | 8;
+
+    let bgra: Vec<[u8; 4]> = image.pixels().map(|p| [p[2], p[1], p[0], p[3]]).collect();
+    if !rle {
+        data.extend(bgra.iter().flatten());
+        return Ok(data);
+    }
+    // Packets never cross rows, as the specification asks.
+    for row in bgra.chunks(image.width().max(1) as usize) {
+        let mut i = 0;
+        while i < row.len() {
+            let run = row[i..].iter().take(128).take_while(|&&p| p == row[i]).count();
+            if run > 1 {
+                data.push(0x80 | (run - 1) as u8);
+                data.extend_from_slice(&row[i]);
+                i += run;
+            } else {
+                // A raw packet lasts until the next pair of equal pixels.
+                let mut end = i + 1;
+                while end < row.len() && end - i < 128 && (end + 1 >= row.len() || row[end] != row[end + 1]) {
+                    end += 1;
+                }
+                data.push((end - i - 1) as u8);
+                data.extend(row[i..end].iter().flatten());
+                i = end;
+            }
+        }
+    }
+    Ok(data)
+}
+
+pub fn load(path: &Path) -> Result<RgbaImage, TgaError> {
+    decode(&fs::read(path)?)
+}
+
+pub fn save(image: &RgbaImage, path: &Path, rle: bool) -> Result<(), TgaError> {
+    fs::write(path, encode(image, rle)?)?;
+    Ok(())
+}
+
+fn bytes_per_pixel(depth: u8) -> Option<usize> {
+    match depth {
+        8 => Some(1),
+        15 | 16 => Some(2),
+        24 => Some(3),
+        32 => Some(4),
+        _ => None,
+    }
+}
+
+/// A little-endian BGR(A) or ARGB1555 color as RGBA. The top bit of a
+/// 16-bit color is only alpha if the header gives one attribute bit; most
+/// files leave it clear without meaning transparency.
+fn read_color(bytes: &[u8], depth: u8, alpha_bits: u8) -> [u8; 4] {
+    match depth {
+        15 | 16 => {
+            let v = u16::from_le_bytes([bytes[0], bytes[1]]);
+            let scale = |c: u16| ((c & 0x1F) * 255 / 31) as u8;
+            let alpha = if depth == 16 && alpha_bits == 1 && v & 0x8000 == 0 { 0 } else { 255 };
+            [scale(v >> 10), scale(v >> 5), scale(v), alpha]
+        }
+        24 => [bytes[2], bytes[1], bytes[0], 255],
+        32 => [bytes[2], bytes[1], bytes[0], bytes[3]],
+        _ => [bytes[0], bytes[0], bytes[0], 255],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> RgbaImage {
+        RgbaImage::from_fn(5, 3, |x, y| {
+            if y == 1 {
+                image::Rgba([10, 20, 30, 255])
+            } else {
+                image::Rgba([x as u8 * 40, y as u8, 7, if x == 4 { 0 } else { 255 }])
+            }
+        })
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let image = sample();
+        for rle in [false, true] {
+            let data = encode(&image, rle).unwrap();
+            assert_eq!(decode(&data).unwrap(), image, "rle = {}", rle);
+        }
+        let (raw, packed) = (encode(&image, false).unwrap(), encode(&image, true).unwrap());
+        assert!(packed.len() < raw.len());
+        assert!(matches!(decode(&raw[..30]), Err(TgaError::Truncated)));
+        assert!(matches!(encode(&RgbaImage::new(65536, 1), false), Err(TgaError::TooLarge { .. })));
+        // A header alone claiming the largest size fails rather than allocating for it.
+        let huge = [0, 0, 10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 32, 8];
+        assert!(matches!(decode(&huge), Err(TgaError::Truncated)));
+    }
+
+    #[test]
+    fn test_decode_16_bit_alpha() {
+        // 2x1, 16-bit true color: white with the top bit clear, then set.
+        let header = |attribute_bits| vec![0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 1, 0, 16, attribute_bits];
+        let pixels = [0xFF, 0x7F, 0xFF, 0xFF];
+        let opaque = decode(&[header(0), pixels.to_vec()].concat()).unwrap();
+        assert_eq!(opaque.get_pixel(0, 0).0, [255, 255, 255, 255]);
+        let masked = decode(&[header(1), pixels.to_vec()].concat()).unwrap();
+        assert_eq!(masked.get_pixel(0, 0).0, [255, 255, 255, 0]);
+        assert_eq!(masked.get_pixel(1, 0).0, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_decode_bottom_up_colormapped() {
+        // 2x2, 8-bit color-mapped, RLE, rows stored bottom to top.
+        let mut data = vec![0, 1, 9, 0, 0, 2, 0, 24, 0, 0, 0, 0, 2, 0, 2, 0, 8, 0];
+        data.extend_from_slice(&[0, 0, 255, 0, 255, 0]); // red, green (BGR)
+        data.extend_from_slice(&[0x81, 0]); // bottom row: two red
+        data.extend_from_slice(&[0x01, 1, 0]); // top row: green, red
+        let image = decode(&data).unwrap();
+        assert_eq!(image.get_pixel(0, 0).0, [0, 255, 0, 255]);
+        assert_eq!(image.get_pixel(1, 0).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(0, 1).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(1, 1).0, [255, 0, 0, 255]);
+    }
+}